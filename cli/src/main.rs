@@ -3,57 +3,281 @@ extern crate zecpaperlib;
 
 mod version;
 
-use clap::{Arg, App};
+use clap::{Arg, App, SubCommand, ArgMatches};
 use zecpaperlib::paper::*;
 use zecpaperlib::pdf;
+use zecpaperlib::html;
+use zecpaperlib::vanity::{generate_vanity_wallet, resume_vanity_wallet, vanity_difficulty, pattern_difficulty, VanityPattern, VanityConfig, VanityMode, VanityEvent, CancelHandle};
+use zecpaperlib::checkpoint::{load_checkpoint, CheckpointConfig, VanityState};
+use zecpaperlib::dice::{parse_rolls, check_rolls, rolls_to_entropy, Die};
+use zecpaperlib::entropy::{EntropySource, SystemEntropy, DeviceEntropy};
+use zecpaperlib::shamir::{split_wallet, restore_from_shares, Share};
+use zecpaperlib::keycrypt::{encrypt_wallet, decrypt_wallet, decrypt_key, is_encrypted_wallet};
+use zecpaperlib::recipient::{parse_recipient, encrypt_to_recipients, Recipient};
+use zecpaperlib::verify::{verify_wallet, verify_key, verify_unified_key};
+use zecpaperlib::wallet::PaperWallet;
 use std::io;
 use std::io::prelude::*;
+use std::time::Duration;
+
+fn testnet_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("testnet")
+        .long("testnet")
+        .conflicts_with("network")
+        .help("Generate Testnet addresses. Same as --network test")
+}
+
+fn network_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("network")
+        .long("network")
+        .help("Which network to generate addresses for. Defaults to main")
+        .takes_value(true)
+        .value_name("NETWORK")
+        .possible_values(&["main", "test", "regtest"])
+}
+
+fn coin_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("coin")
+        .long("coin")
+        .help("Generate addresses for a Zcash-derived coin instead, with the prefixes, version bytes and coin type read from this JSON file")
+        .takes_value(true)
+        .value_name("FILE")
+        .conflicts_with_all(&["network", "testnet"])
+}
+
+fn format_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("format")
+        .short("f")
+        .long("format")
+        .help("What format to generate the output in")
+        .takes_value(true)
+        .value_name("FORMAT")
+        .possible_values(&["pdf", "json", "csv", "text", "html"])
+        .default_value("json")
+}
+
+fn watch_only_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("watch_only")
+        .long("watch-only")
+        .help("Only write the addresses and viewing keys, without any private keys or seeds. Use this to monitor the balances of a wallet that is kept offline")
+}
+
+fn recipient_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("recipient")
+        .short("r")
+        .long("recipient")
+        .takes_value(true)
+        .multiple(true)
+        .number_of_values(1)
+        .help("Encrypt the output file (JSON or PDF) to this age public key (age1...), so it is never written in plaintext. Can be given more than once. Decrypt it with `age -d -i <identity file>`")
+}
+
+fn output_arg<'a, 'b>(index: u64) -> Arg<'a, 'b> {
+    Arg::with_name("output")
+        .short("o")
+        .long("output")
+        .index(index)
+        .help("Name of output file.")
+}
+
+/// The arguments that `restore` and `combine` share, for which network and how many addresses to re-derive
+fn restore_args<'a, 'b>(app: App<'a, 'b>) -> App<'a, 'b> {
+    app.arg(testnet_arg())
+        .arg(network_arg())
+        .arg(coin_arg())
+        .arg(format_arg())
+        .arg(watch_only_arg())
+        .arg(recipient_arg())
+        .arg(Arg::with_name("first")
+                .long("first")
+                .help("First account number to restore")
+                .takes_value(true)
+                .default_value("0")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Account number '{}' is not a number", i))
+                }))
+        .arg(Arg::with_name("accounts")
+                .short("z")
+                .long("accounts")
+                .help("Number of accounts (Z addresses) to restore")
+                .takes_value(true)
+                .default_value("1")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Number of accounts '{}' is not a number", i))
+                }))
+        .arg(Arg::with_name("t_addresses")
+                .short("t")
+                .long("taddrs")
+                .help("Number of T addresses to restore")
+                .takes_value(true)
+                .default_value("0")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Number of addresses '{}' is not a number", i))
+                }))
+        .arg(Arg::with_name("u_addresses")
+                .short("u")
+                .long("uaddrs")
+                .help("Number of Unified addresses to restore")
+                .takes_value(true)
+                .default_value("0")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Number of addresses '{}' is not a number", i))
+                }))
+}
 
 fn main() { 
     let matches = App::new("zecpaperwaller")
        .version(version::version())
        .about("A command line Zcash Sapling paper wallet generator")
-       .arg(Arg::with_name("testnet")
-                .long("testnet")
-                .help("Generate Testnet addresses"))
-        .arg(Arg::with_name("format")
-                .short("f")
-                .long("format")
-                .help("What format to generate the output in")
-                .takes_value(true)
-                .value_name("FORMAT")
-                .possible_values(&["pdf", "json"])
-                .default_value("json"))
+       .arg(testnet_arg())
+        .arg(network_arg())
+        .arg(coin_arg())
+        .arg(format_arg())
+        .arg(watch_only_arg())
+        .arg(recipient_arg())
         .arg(Arg::with_name("nohd")
                 .short("n")
                 .long("nohd")
                 .help("Don't reuse HD keys. Normally, zecpaperwallet will use the same HD key to derive multiple addresses. This flag will use a new seed for each address"))
-        .arg(Arg::with_name("output")
-                .short("o")
-                .long("output")
-                .index(1)
-                .help("Name of output file."))
+        .arg(Arg::with_name("diversified")
+                .short("d")
+                .long("diversified")
+                .conflicts_with("nohd")
+                .help("Generate the Z addresses as diversified addresses of a single spending key. All the addresses share one private key, but can't be linked to each other without the viewing key. With --vanity, search the diversifiers of a single account instead of new seeds, which is much faster"))
+        .arg(Arg::with_name("mnemonic")
+                .short("m")
+                .long("mnemonic")
+                .help("Derive the HD seed from a new 24-word BIP-39 mnemonic, and include the words in the output so the wallet can be backed up by hand"))
+        .arg(Arg::with_name("passphrase")
+                .long("passphrase")
+                .takes_value(true)
+                .requires("mnemonic")
+                .help("Optional BIP-39 passphrase to use along with the mnemonic. You'll need it (in addition to the words) to restore the wallet"))
+        .arg(output_arg(1))
         .arg(Arg::with_name("entropy")
                 .short("e")
                 .long("entropy")
                 .takes_value(true)
                 .help("Provide additional entropy to the random number generator. Any random string, containing 32-64 characters"))
+        .arg(Arg::with_name("dice")
+                .long("dice")
+                .takes_value(true)
+                .value_name("DIE")
+                .possible_values(&["coin", "d6", "d20"])
+                .conflicts_with("entropy")
+                .help("Provide the additional entropy by rolling dice or flipping a coin. Asks for enough rolls to make up 256 bits, and checks them for bias"))
+        .arg(Arg::with_name("shares")
+                .long("shares")
+                .takes_value(true)
+                .requires("threshold")
+                .conflicts_with_all(&["nohd", "watch_only", "recipient", "vanity_prefix", "vanity_suffix", "vanity_contains", "resume"])
+                .help("Split the HD seed into this many shares, each written to its own file, so that no single person can restore the wallet. The wallet itself is written as watch-only. Use with --threshold")
+                .validator(|i:String| match i.parse::<u8>() {
+                        Ok(n) if n > 0 => return Ok(()),
+                        _              => return Err(format!("Number of shares '{}' should be between 1 and 255", i))
+                }))
+        .arg(Arg::with_name("threshold")
+                .long("threshold")
+                .takes_value(true)
+                .requires("shares")
+                .help("How many of the seed shares are needed to restore the wallet")
+                .validator(|i:String| match i.parse::<u8>() {
+                        Ok(n) if n > 0 => return Ok(()),
+                        _              => return Err(format!("Threshold '{}' should be between 1 and 255", i))
+                }))
+        .arg(Arg::with_name("encrypt")
+                .long("encrypt")
+                .conflicts_with_all(&["watch_only", "shares"])
+                .help("Encrypt the private keys and the HD seed with a passphrase, so the wallet can't be spent without it. Use the decrypt command to get them back"))
+        .arg(Arg::with_name("encrypt_passphrase")
+                .long("encrypt-passphrase")
+                .takes_value(true)
+                .requires("encrypt")
+                .help("Passphrase to encrypt the private keys with. If it's not given, you'll be asked to type it in"))
+        .arg(Arg::with_name("rng_device")
+                .long("rng-device")
+                .takes_value(true)
+                .value_name("FILE")
+                .help("Read the random numbers from a hardware random number generator device (like /dev/hwrng) instead of the system's random number generator"))
         .arg(Arg::with_name("vanity_prefix")
                 .long("vanity")
-                .help("Generate a vanity address with the given prefix")
+                .help("Generate a vanity address with the given prefix. Can be given more than once to look for any of several prefixes")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+        .arg(Arg::with_name("vanity_suffix")
+                .long("vanity-suffix")
+                .help("Generate a vanity address that ends with the given string. Can be given more than once")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+        .arg(Arg::with_name("vanity_contains")
+                .long("vanity-contains")
+                .help("Generate a vanity address that contains the given string anywhere after the prefix. Can be given more than once")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1))
+        .arg(Arg::with_name("collect_all")
+                .long("collect-all")
+                .help("With several vanity patterns, keep going until there is an address for every pattern, instead of stopping at the first match"))
+        .arg(Arg::with_name("vanity_timeout")
+                .long("vanity-timeout")
+                .help("Give up the vanity search after this many seconds")
+                .takes_value(true)
+                .validator(|i:String| match i.parse::<u64>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Timeout '{}' is not a number", i))
+                }))
+        .arg(Arg::with_name("checkpoint")
+                .long("checkpoint")
+                .help("Save the progress of the vanity search to this file, so it can be resumed with --resume if it is interrupted. The file is encrypted with a passphrase, and deleted once the wallet is saved")
+                .takes_value(true))
+        .arg(Arg::with_name("checkpoint_interval")
+                .long("checkpoint-interval")
+                .help("How often to save the vanity search checkpoint, in seconds")
+                .takes_value(true)
+                .default_value("60")
+                .validator(|i:String| match i.parse::<u64>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Checkpoint interval '{}' is not a number", i))
+                }))
+        .arg(Arg::with_name("checkpoint_passphrase")
+                .long("checkpoint-passphrase")
+                .help("Passphrase to encrypt the vanity search checkpoint with. If it's not given, you'll be asked to type it in")
                 .takes_value(true))
+        .arg(Arg::with_name("resume")
+                .long("resume")
+                .help("Resume an interrupted vanity search from its checkpoint file. The patterns and number of threads are read from the checkpoint")
+                .takes_value(true)
+                .conflicts_with_all(&["vanity_prefix", "vanity_suffix", "vanity_contains", "checkpoint"]))
+        .arg(Arg::with_name("vanity_max_attempts")
+                .long("vanity-max-attempts")
+                .help("Give up the vanity search after checking this many addresses")
+                .takes_value(true)
+                .validator(|i:String| match i.parse::<u64>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Number of attempts '{}' is not a number", i))
+                }))
         .arg(Arg::with_name("threads")
                 .long("threads")
                 .help("Number of threads to use for the vanity address generator. Set this to the number of CPUs you have")
                 .takes_value(true)
-                .default_value("1"))
+                .default_value("1")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(n) if n > 0 => return Ok(()),
+                        _              => return Err(format!("Number of threads '{}' should be a number greater than 0", i))
+                }))
         .arg(Arg::with_name("t_addresses")
                 .short("t")
                 .long("taddrs")
                 .help("Number of T addresses to generate")
                 .takes_value(true)
                 .default_value("0")
-                .validator(|i:String| match i.parse::<i32>() {
+                .validator(|i:String| match i.parse::<u32>() {
                         Ok(_)   => return Ok(()),
                         Err(_)  => return Err(format!("Number of addresses '{}' is not a number", i))
                 }))
@@ -63,13 +287,120 @@ fn main() {
                 .help("Number of Z addresses (Sapling) to generate")
                 .takes_value(true)
                 .default_value("1")                
-                .validator(|i:String| match i.parse::<i32>() {
+                .validator(|i:String| match i.parse::<u32>() {
                         Ok(_)   => return Ok(()),
                         Err(_)  => return Err(format!("Number of addresses '{}' is not a number", i))
                 }))
+        .arg(Arg::with_name("u_addresses")
+                .short("u")
+                .long("uaddrs")
+                .help("Number of Unified addresses (Orchard + Sapling) to generate")
+                .takes_value(true)
+                .default_value("0")
+                .validator(|i:String| match i.parse::<u32>() {
+                        Ok(_)   => return Ok(()),
+                        Err(_)  => return Err(format!("Number of addresses '{}' is not a number", i))
+                }))
+        .subcommand(restore_args(SubCommand::with_name("restore")
+                .about("Re-derive the addresses of an existing paper wallet from its HD seed or mnemonic")
+                .arg(Arg::with_name("seed")
+                        .index(1)
+                        .required(true)
+                        .help("The hex HDSeed printed on the wallet, or the BIP-39 seed words (in quotes)"))
+                .arg(output_arg(2))
+                .arg(Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .takes_value(true)
+                        .help("BIP-39 passphrase, if the wallet was generated with one"))))
+        .subcommand(restore_args(SubCommand::with_name("combine")
+                .about("Combine the seed shares of a wallet that was split with --shares, and re-derive its addresses")
+                .arg(Arg::with_name("share")
+                        .short("s")
+                        .long("share")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help("A seed share (starting with zecshare1). Give it once for each share"))
+                .arg(output_arg(1))
+                .arg(Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .takes_value(true)
+                        .help("BIP-39 passphrase, if the wallet was generated with seed words and a passphrase"))))
+        .subcommand(SubCommand::with_name("decrypt")
+                .about("Decrypt the private keys and seeds of a wallet that was generated with --encrypt")
+                .arg(Arg::with_name("wallet")
+                        .index(1)
+                        .required_unless("key")
+                        .help("The wallet JSON file to decrypt"))
+                .arg(output_arg(2))
+                .arg(format_arg())
+                .arg(recipient_arg())
+                .arg(Arg::with_name("key")
+                        .short("k")
+                        .long("key")
+                        .takes_value(true)
+                        .conflicts_with("wallet")
+                        .help("Decrypt a single encrypted key (starting with zecencrypted1) instead, and print it"))
+                .arg(Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .takes_value(true)
+                        .help("The passphrase the keys were encrypted with. If it's not given, you'll be asked to type it in")))
+        .subcommand(SubCommand::with_name("verify")
+                .about("Check that an existing wallet is consistent, by re-deriving the addresses and viewing keys from the private keys (and HD seed)")
+                .arg(Arg::with_name("wallet")
+                        .index(1)
+                        .required_unless("key")
+                        .help("The wallet JSON file to verify"))
+                .arg(Arg::with_name("key")
+                        .short("k")
+                        .long("key")
+                        .takes_value(true)
+                        .conflicts_with("wallet")
+                        .help("Verify a single private key instead, and print the address it derives"))
+                .arg(Arg::with_name("sapling_key")
+                        .long("sapling-key")
+                        .takes_value(true)
+                        .requires("key")
+                        .help("The Sapling private key of a unified address. A unified address is derived from both its keys, so pass the Orchard key with --key and the Sapling key with this"))
+                .arg(Arg::with_name("address")
+                        .long("address")
+                        .takes_value(true)
+                        .requires("key")
+                        .help("The address printed along with the private key, to check against"))
+                .arg(Arg::with_name("passphrase")
+                        .long("passphrase")
+                        .takes_value(true)
+                        .help("BIP-39 passphrase, if the wallet was generated with one"))
+                .arg(Arg::with_name("decrypt_passphrase")
+                        .long("decrypt-passphrase")
+                        .takes_value(true)
+                        .conflicts_with("key")
+                        .help("Passphrase the wallet was encrypted with (--encrypt). The wallet is decrypted before it is verified. If it's encrypted and this isn't given, you'll be asked to type it in"))
+                .arg(coin_arg()
+                        .help("The JSON file of the Zcash-derived coin the wallet was generated for with --coin. Keys of the Zcash networks are always recognized")))
        .get_matches();  
 
-    let is_testnet: bool = matches.is_present("testnet");
+    // The subcommands and the wallet writers print their own errors, and return false if there was one
+    let subcommand = match matches.subcommand() {
+        ("restore", Some(m)) | ("combine", Some(m)) => Some(restore(m)),
+        ("decrypt", Some(m))                        => Some(decrypt(m)),
+        ("verify", Some(m))                         => Some(verify(m)),
+        _                                           => None
+    };
+    match subcommand {
+        Some(true)  => return,
+        Some(false) => std::process::exit(1),
+        None        => {}
+    }
+
+    let network = match parse_network(&matches) {
+        Ok(n)  => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
     
     let nohd: bool    = matches.is_present("nohd");
 
@@ -80,17 +411,20 @@ fn main() {
     // Writing to PDF requires a filename
     if format == "pdf" && filename.is_none() {
         eprintln!("Need an output file name when writing to PDF");
-        return;
+        std::process::exit(1);
     }
 
-    // Get the filename and output format
-    let filename = matches.value_of("output");
-    let format   = matches.value_of("format").unwrap();
-
-    // Writing to PDF requires a filename
-    if format == "pdf" && filename.is_none() {
-        eprintln!("Need an output file name when writing to PDF");
-        return;
+    // Encrypted output is binary, so it has to go to a file too
+    let recipients = match parse_recipients(&matches) {
+        Ok(r)  => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    if !recipients.is_empty() && filename.is_none() {
+        eprintln!("Need an output file name when encrypting to a recipient");
+        std::process::exit(1);
     }
 
     // Number of t addresses to generate
@@ -99,36 +433,114 @@ fn main() {
     // Number of z addresses to generate
     let z_addresses = matches.value_of("z_addresses").unwrap().parse::<u32>().unwrap();    
 
-    let addresses = if !matches.value_of("vanity_prefix").is_none() {
-        if z_addresses != 1 {
-            eprintln!("Can only generate 1 zaddress in vanity mode. You specified {}", z_addresses);
-            return;
+    // Number of unified addresses to generate
+    let u_addresses = matches.value_of("u_addresses").unwrap().parse::<u32>().unwrap();
+
+    // All the vanity patterns, in the order prefixes, suffixes and substrings
+    let mut patterns = vec![];
+    patterns.extend(matches.values_of("vanity_prefix").into_iter().flatten().map(|s| VanityPattern::Prefix(s.to_string())));
+    patterns.extend(matches.values_of("vanity_suffix").into_iter().flatten().map(|s| VanityPattern::Suffix(s.to_string())));
+    patterns.extend(matches.values_of("vanity_contains").into_iter().flatten().map(|s| VanityPattern::Contains(s.to_string())));
+
+    // The vanity search checkpoint, which is deleted once the wallet is saved
+    let mut checkpoint = None;
+
+    let addresses = if let Some(path) = matches.value_of("resume") {
+        let (mut config, state) = match load_checkpoint(path, &checkpoint_passphrase(&matches)) {
+            Ok(c)  => c,
+            Err(e) => {
+                eprintln!("Couldn't load the checkpoint {}: {}", path, e);
+                std::process::exit(1);
+            }
+        };
+        set_vanity_limits(&matches, &mut config);
+        checkpoint = config.checkpoint.as_ref().map(|c| c.path.clone());
+
+        println!("Resuming the vanity search for {} patterns, after {} tries", config.patterns.len(), state.attempts());
+        match run_vanity(&config, Some(state), &mut *entropy_source(&matches)) {
+            Some(w) => w,
+            None    => std::process::exit(1)
+        }
+    } else if !patterns.is_empty() {
+        // Either 1 z address (the default), or 1 t address with "-t 1 -z 0"
+        let is_taddr = match (z_addresses, t_addresses) {
+            (1, 0) => false,
+            (0, 1) => true,
+            _      => {
+                eprintln!("Can only generate 1 address in vanity mode. Use \"-t 1 -z 0\" for a vanity t-address. You specified {} z-addresses and {} t-addresses", z_addresses, t_addresses);
+                std::process::exit(1);
+            }
+        };
+
+        if u_addresses != 0 {
+            eprintln!("Can't generate vanity unified addresses");
+            std::process::exit(1);
         }
 
-        if t_addresses != 0 {
-            eprintln!("Can't generate vanity t-addressses yet");
-            return;
+        // Searching diversifiers checks about 2 indexes for every address, since half of them aren't valid
+        let mode = if matches.is_present("diversified") { VanityMode::Diversifier } else { VanityMode::Seeds };
+        if mode == VanityMode::Diversifier && is_taddr {
+            eprintln!("Can't generate diversified t-addresses");
+            std::process::exit(1);
         }
+        let tries_per_address = if mode == VanityMode::Diversifier { 2.0 } else { 1.0 };
 
         let num_threads = matches.value_of("threads").unwrap().parse::<u32>().unwrap();
 
-        let prefix = matches.value_of("vanity_prefix").unwrap().to_string();
-        println!("Generating address starting with \"{}\"", prefix);
-        let addresses = match generate_vanity_wallet(is_testnet, num_threads, prefix) {
-            Ok(w) => w,
-            Err(e) => {
-                eprintln!("{}", e);
-                return;
-            }
+        let collect_all = matches.is_present("collect_all");
+        // The fixed part of the address that comes before the pattern
+        let start = if is_taddr {
+            [0u8; 20].to_base58check(&params(&network).taddress_version, &[])[..2].to_string()
+        } else {
+            format!("{}1", params(&network).zaddress_prefix)
         };
+        for pattern in patterns.iter() {
+            let description = match pattern {
+                VanityPattern::Prefix(s) => format!("starting with \"{}{}\"", start, s),
+                _                        => format!("{}", pattern)
+            };
+            match pattern_difficulty(&network, is_taddr, pattern) {
+                Ok(d)  => println!("Generating address {}. This will need about {:.0} tries", description, d * tries_per_address),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            };
+        }
+        if patterns.len() > 1 && !collect_all {
+            if let Ok(d) = vanity_difficulty(&network, is_taddr, &patterns) {
+                println!("The first address matching any of them will need about {:.0} tries", d * tries_per_address);
+            }
+        }
 
-        // return
-        addresses
+        let mut config = VanityConfig::new(network, is_taddr, patterns);
+        config.num_threads = num_threads;
+        config.mode        = mode;
+        config.collect_all = collect_all;
+        if let Some(path) = matches.value_of("checkpoint") {
+            config.checkpoint = Some(CheckpointConfig::new(path, &checkpoint_passphrase(&matches)));
+        }
+        set_vanity_limits(&matches, &mut config);
+        checkpoint = config.checkpoint.as_ref().map(|c| c.path.clone());
+
+        match run_vanity(&config, None, &mut *entropy_source(&matches)) {
+            Some(w) => w,
+            None    => std::process::exit(1)
+        }
     } else {
         // Get user entropy. 
         let mut entropy: Vec<u8> = Vec::new();
-        // If the user hasn't specified any, read from the stdin
-        if matches.value_of("entropy").is_none() {
+        if let Some(die) = matches.value_of("dice") {
+            match read_dice_entropy(Die::from_name(die).unwrap()) {
+                Ok(e)  => entropy = e,
+                Err(e) => {
+                    eprintln!("[ERROR]");
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                }
+            }
+        } else if matches.value_of("entropy").is_none() {
+            // If the user hasn't specified any, read from the stdin
             // Read from stdin
             println!("Provide additional entropy for generating random numbers. Type in a string of random characters, press [ENTER] when done");
             let mut buffer = String::new();
@@ -141,32 +553,622 @@ fn main() {
             entropy.extend(matches.value_of("entropy").unwrap().as_bytes());
         }
 
-        print!("Generating {} Sapling addresses and {} Transparent addresses...", z_addresses, t_addresses);
+        print!("Generating {} Sapling addresses, {} Transparent addresses and {} Unified addresses...", z_addresses, t_addresses, u_addresses);
         io::stdout().flush().ok();
-        let addresses = generate_wallet(is_testnet, nohd, z_addresses, t_addresses, &entropy); 
+        let mnemonic = if matches.is_present("mnemonic") {
+            Some(matches.value_of("passphrase").unwrap_or(""))
+        } else {
+            None
+        };
+
+        let mut source = entropy_source(&matches);
+
+        let result = if matches.is_present("diversified") {
+            if t_addresses != 0 || u_addresses != 0 {
+                eprintln!("[ERROR]");
+                eprintln!("Can't generate t-addresses or unified addresses along with diversified addresses");
+                std::process::exit(1);
+            }
+            generate_diversified_wallet(&network, z_addresses, &mut *source, &entropy, mnemonic)
+        } else {
+            let options = WalletOptions {
+                network,
+                nohd,
+                zcount  : z_addresses,
+                tcount  : t_addresses,
+                ucount  : u_addresses,
+                mnemonic: mnemonic.map(String::from),
+            };
+            generate_wallet(&options, &mut *source, &entropy)
+        };
+
+        let addresses = match result {
+            Ok(w) => w,
+            Err(e) => {
+                eprintln!("[ERROR]");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        };
         println!("[OK]");
 
         addresses
     };
 
-    // If the default format is present, write to the console if the filename is absent
-    if format == "json" {
+    let addresses = if matches.is_present("encrypt") {
+        let passphrase = match matches.value_of("encrypt_passphrase") {
+            Some(p) => p.to_string(),
+            None    => match read_new_passphrase("Type in a passphrase to encrypt the private keys with, press [ENTER] when done") {
+                Some(p) => p,
+                None    => {
+                    eprintln!("The passphrases don't match");
+                    std::process::exit(1);
+                }
+            }
+        };
+
+        print!("Encrypting the private keys and seeds...");
+        io::stdout().flush().ok();
+        match encrypt_wallet(&addresses, &passphrase, &mut *entropy_source(&matches)) {
+            Ok(w)  => { println!("[OK]"); w },
+            Err(e) => {
+                eprintln!("[ERROR]");
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else {
+        addresses
+    };
+
+    let saved = if matches.is_present("shares") {
+        save_shares(&matches, &addresses, format, filename)
+    } else {
+        save_wallet(&addresses, format, filename, matches.is_present("watch_only"), &recipients)
+    };
+    if !saved {
+        std::process::exit(1);
+    }
+
+    if let Some(path) = checkpoint {
+        delete_checkpoint(&path);
+    }
+}
+
+/// Delete the vanity search checkpoint once the wallet is saved, since it has the keys in it. It isn't there if the
+/// search finished before the first checkpoint was written.
+fn delete_checkpoint(path: &str) {
+    match std::fs::remove_file(path) {
+        Ok(())                                       => println!("Deleted the checkpoint {}", path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {},
+        Err(e)                                       => eprintln!("Couldn't delete the checkpoint {}: {}. Delete it yourself, it has the keys in it", path, e),
+    }
+}
+
+/// The random number generator to use for new keys: the hardware RNG from --rng-device, or the system's
+fn entropy_source(matches: &ArgMatches) -> Box<dyn EntropySource> {
+    match matches.value_of("rng_device") {
+        Some(path) => Box::new(DeviceEntropy::new(path)),
+        None       => Box::new(SystemEntropy)
+    }
+}
+
+/// Split the wallet's HD seed into shares and write each one to its own file, named after the output file. The wallet
+/// itself is written as watch-only, so the only way to spend the funds is to combine the shares. Returns false if
+/// anything couldn't be written.
+fn save_shares(matches: &ArgMatches, addresses: &PaperWallet, format: &str, filename: Option<&str>) -> bool {
+    let count     = matches.value_of("shares").unwrap().parse::<u8>().unwrap();
+    let threshold = matches.value_of("threshold").unwrap().parse::<u8>().unwrap();
+
+    let shares = match split_wallet(addresses, threshold, count, &mut *entropy_source(matches)) {
+        Ok(s)  => s,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    if !save_wallet(addresses, format, filename, true, &[]) {
+        return false;
+    }
+
+    for share in shares.iter() {
+        let encoded = match share.encode() {
+            Ok(s)  => s,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+
+        let share_file = filename.map(|f| share_filename(f, share.index));
+        if format == "pdf" {
+            // We already know the output file name was specified
+            let share_file = share_file.unwrap();
+            print!("Writing share {} of {} to {:?} as a PDF file...", share.index, share.count, share_file);
+            io::stdout().flush().ok();
+            match pdf::save_share_to_pdf(addresses, share, &share_file) {
+                Ok(_)   => { println!("[OK]");},
+                Err(e)  => {
+                    eprintln!("[ERROR]");
+                    eprintln!("{}", e);
+                    return false;
+                }
+            };
+        } else {
+            let json = json::stringify_pretty(json::object!{
+                "share"     => encoded,
+                "index"     => share.index,
+                "threshold" => share.threshold,
+                "count"     => share.count
+            }, 2);
+
+            match share_file {
+                None       => println!("{}", json),
+                Some(file) => match std::fs::write(&file, json) {
+                    Ok(_)   => println!("Wrote share {} of {} to {:?}", share.index, share.count, file),
+                    Err(e)  => {
+                        eprintln!("Couldn't write to {:?}: {}", file, e);
+                        return false;
+                    }
+                }
+            }
+        }
+    }
+
+    println!("Any {} of the {} shares are needed to restore the wallet with the combine command", threshold, count);
+    true
+}
+
+/// The file to write share `index` to, like "wallet-share1.pdf" for "wallet.pdf"
+fn share_filename(filename: &str, index: u8) -> String {
+    let path = std::path::Path::new(filename);
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or(filename);
+    let name = match path.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}-share{}.{}", stem, index, ext),
+        None      => format!("{}-share{}", stem, index)
+    };
+
+    path.with_file_name(name).to_string_lossy().to_string()
+}
+
+/// The age recipients from --recipient. Returns an empty list if the output isn't encrypted.
+fn parse_recipients(matches: &ArgMatches) -> Result<Vec<Recipient>, zecpaperlib::Error> {
+    matches.values_of("recipient").into_iter().flatten().map(parse_recipient).collect()
+}
+
+/// The network from --network, --testnet (which is short for --network test) or the coin file passed with --coin
+fn parse_network(matches: &ArgMatches) -> Result<Network, zecpaperlib::Error> {
+    if let Some(path) = matches.value_of("coin") {
+        let coin = CoinParams::from_json(&std::fs::read_to_string(path)?)?;
+        return Ok(Network::Custom(Box::new(coin)));
+    }
+
+    if matches.is_present("testnet") {
+        return Ok(Network::Test);
+    }
+
+    // --network only takes the possible values, so it always parses
+    Network::from_name(matches.value_of("network").unwrap_or("main"))
+}
+
+/// Re-derive an existing wallet from its seed (or from its seed shares, for `combine`), and write it out like a newly
+/// generated one. Returns false if it failed
+fn restore(matches: &ArgMatches) -> bool {
+    let network = match parse_network(matches) {
+        Ok(n)  => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    let filename = matches.value_of("output");
+    let format   = matches.value_of("format").unwrap();
+
+    // Writing to PDF requires a filename
+    if format == "pdf" && filename.is_none() {
+        eprintln!("Need an output file name when writing to PDF");
+        return false;
+    }
+
+    // Encrypted output is binary, so it has to go to a file too
+    let recipients = match parse_recipients(matches) {
+        Ok(r)  => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    if !recipients.is_empty() && filename.is_none() {
+        eprintln!("Need an output file name when encrypting to a recipient");
+        return false;
+    }
+
+    let first    = matches.value_of("first").unwrap().parse::<u32>().unwrap();
+    let accounts = matches.value_of("accounts").unwrap().parse::<u32>().unwrap();
+    let taddrs   = matches.value_of("t_addresses").unwrap().parse::<u32>().unwrap();
+    let uaddrs   = matches.value_of("u_addresses").unwrap().parse::<u32>().unwrap();
+
+    // `combine` passes in the seed shares, and `restore` the seed itself
+    let shares = match matches.values_of("share") {
+        Some(shares) => match shares.map(Share::decode).collect::<Result<Vec<_>, _>>() {
+            Ok(s)  => Some(s),
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        },
+        None => None
+    };
+
+    print!("Restoring {} Sapling addresses starting at account {}, {} Transparent addresses and {} Unified addresses...", accounts, first, taddrs, uaddrs);
+    io::stdout().flush().ok();
+    let accounts = first..first.saturating_add(accounts);
+    let passphrase = matches.value_of("passphrase").unwrap_or("");
+    let result = match shares {
+        Some(shares) => restore_from_shares(&shares, &network, accounts, taddrs, uaddrs, passphrase),
+        None         => restore_wallet(matches.value_of("seed").unwrap(), &network, accounts, taddrs, uaddrs, passphrase)
+    };
+    let addresses = match result {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("[ERROR]");
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    println!("[OK]");
+
+    save_wallet(&addresses, format, filename, matches.is_present("watch_only"), &recipients)
+}
+
+/// Verify a wallet file or a single private key. Returns true if everything matched
+fn verify(matches: &ArgMatches) -> bool {
+    let network = match parse_network(matches) {
+        Ok(n)  => n,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+
+    if let Some(key) = matches.value_of("key") {
+        let result = match matches.value_of("sapling_key") {
+            Some(sapling_key) => verify_unified_key(key, sapling_key, Some(&network)),
+            None              => verify_key(key, Some(&network))
+        };
+        let entry = match result {
+            Ok(e) => e,
+            Err(e) => {
+                eprintln!("{}", e);
+                return false;
+            }
+        };
+
+        println!("{}", json::stringify_pretty(entry.to_json(), 2));
+
+        return match matches.value_of("address") {
+            Some(address) if address.trim() != entry.address() => {
+                eprintln!("[MISMATCH] The private key is for {}, not {}", entry.address(), address.trim());
+                false
+            },
+            Some(_) => { println!("[OK] The private key matches the address"); true },
+            None    => true
+        };
+    }
+
+    let filename = matches.value_of("wallet").unwrap();
+    let wallet = match std::fs::read_to_string(filename).map_err(zecpaperlib::Error::from).and_then(|s| PaperWallet::from_json(&s)) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", filename, e);
+            return false;
+        }
+    };
+
+    // The keys of an encrypted wallet have to be decrypted before they can be checked
+    let wallet = if is_encrypted_wallet(&wallet) {
+        let passphrase = match matches.value_of("decrypt_passphrase") {
+            Some(p) => p.to_string(),
+            None    => read_passphrase("The wallet is encrypted. Type in the passphrase the keys were encrypted with, press [ENTER] when done")
+        };
+        match decrypt_wallet(&wallet, &passphrase) {
+            Ok(w)  => w,
+            Err(e) => {
+                eprintln!("Couldn't decrypt {}: {}", filename, e);
+                return false;
+            }
+        }
+    } else {
+        wallet
+    };
+
+    let reports = verify_wallet(&wallet, matches.value_of("passphrase").unwrap_or(""), Some(&network));
+    for report in reports.iter() {
+        if report.is_ok() {
+            println!("[OK] {} {}", report.num, report.address);
+        } else {
+            println!("[MISMATCH] {} {}", report.num, report.address);
+            for problem in report.problems.iter() {
+                println!("    {}", problem);
+            }
+        }
+    }
+
+    let failed = reports.iter().filter(|r| !r.is_ok()).count();
+    if failed == 0 {
+        println!("All {} addresses verified", reports.len());
+    } else {
+        eprintln!("{} of {} addresses failed verification", failed, reports.len());
+    }
+
+    failed == 0
+}
+
+/// The time and attempt limits and the checkpoint interval, which can be changed when resuming a search
+fn set_vanity_limits(matches: &ArgMatches, config: &mut VanityConfig) {
+    config.time_limit   = matches.value_of("vanity_timeout").map(|t| Duration::from_secs(t.parse::<u64>().unwrap()));
+    config.max_attempts = matches.value_of("vanity_max_attempts").map(|n| n.parse::<u64>().unwrap());
+    if let Some(checkpoint) = config.checkpoint.as_mut() {
+        checkpoint.interval = Duration::from_secs(matches.value_of("checkpoint_interval").unwrap().parse::<u64>().unwrap());
+    }
+}
+
+/// Decrypt a wallet file or a single key that was encrypted with --encrypt. Returns false if it failed
+fn decrypt(matches: &ArgMatches) -> bool {
+    let passphrase = match matches.value_of("passphrase") {
+        Some(p) => p.to_string(),
+        None    => read_passphrase("Type in the passphrase the keys were encrypted with, press [ENTER] when done")
+    };
+
+    if let Some(key) = matches.value_of("key") {
+        return match decrypt_key(key, &passphrase) {
+            Ok(k)  => { println!("{}", k); true },
+            Err(e) => { eprintln!("{}", e); false }
+        };
+    }
+
+    let filename = matches.value_of("output");
+    let format   = matches.value_of("format").unwrap();
+
+    // Writing to PDF requires a filename
+    if format == "pdf" && filename.is_none() {
+        eprintln!("Need an output file name when writing to PDF");
+        return false;
+    }
+
+    // Encrypted output is binary, so it has to go to a file too
+    let recipients = match parse_recipients(matches) {
+        Ok(r)  => r,
+        Err(e) => {
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    if !recipients.is_empty() && filename.is_none() {
+        eprintln!("Need an output file name when encrypting to a recipient");
+        return false;
+    }
+
+    let wallet_file = matches.value_of("wallet").unwrap();
+    let wallet = match std::fs::read_to_string(wallet_file).map_err(zecpaperlib::Error::from).and_then(|s| PaperWallet::from_json(&s)) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("Couldn't read {}: {}", wallet_file, e);
+            return false;
+        }
+    };
+
+    print!("Decrypting the private keys...");
+    io::stdout().flush().ok();
+    let addresses = match decrypt_wallet(&wallet, &passphrase) {
+        Ok(w) => w,
+        Err(e) => {
+            eprintln!("[ERROR]");
+            eprintln!("{}", e);
+            return false;
+        }
+    };
+    println!("[OK]");
+
+    save_wallet(&addresses, format, filename, false, &recipients)
+}
+
+fn read_passphrase(prompt: &str) -> String {
+    println!("{}", prompt);
+    let mut buffer = String::new();
+    io::stdin().lock().read_line(&mut buffer).unwrap();
+
+    buffer.trim_end_matches(|c| c == '\r' || c == '\n').to_string()
+}
+
+/// Ask for a new passphrase twice, so a typo doesn't lock the user out. Returns None if they don't match.
+fn read_new_passphrase(prompt: &str) -> Option<String> {
+    let passphrase = read_passphrase(prompt);
+    if read_passphrase("Type it in again to confirm") != passphrase {
+        return None;
+    }
+
+    Some(passphrase)
+}
+
+fn checkpoint_passphrase(matches: &ArgMatches) -> String {
+    if let Some(p) = matches.value_of("checkpoint_passphrase") {
+        return p.to_string();
+    }
+
+    read_passphrase("Type in a passphrase for the vanity search checkpoint, press [ENTER] when done")
+}
+
+/// Ask the user to roll the die until there are enough rolls for 256 bits of entropy, then check the rolls for bias and
+/// hash them into the user entropy.
+fn read_dice_entropy(die: Die) -> Result<Vec<u8>, zecpaperlib::Error> {
+    let needed = die.rolls_needed();
+    match die {
+        Die::Coin => println!("Flip a coin {} times and type in the results as H or T. You can enter them over several lines", needed),
+        _         => println!("Roll a {} {} times and type in the numbers, separated by spaces. You can enter them over several lines",
+                              format!("{:?}", die).to_lowercase(), needed),
+    }
+
+    let mut rolls = vec![];
+    let stdin = io::stdin();
+    while rolls.len() < needed {
+        print!("[{}/{}] ", rolls.len(), needed);
+        io::stdout().flush().ok();
+
+        let mut buffer = String::new();
+        if stdin.lock().read_line(&mut buffer)? == 0 {
+            return Err(zecpaperlib::Error::InvalidInput(format!("Only got {} of the {} rolls needed", rolls.len(), needed)));
+        }
+
+        match parse_rolls(die, &buffer) {
+            Ok(r)  => rolls.extend(r),
+            Err(e) => eprintln!("{}. Type in that line again", e)
+        }
+    }
+
+    let report = check_rolls(die, &rolls)?;
+    println!("Got {} rolls. Chi-square {:.1} (should be under {:.1}), longest run {}",
+             report.rolls, report.chi_square, report.critical, report.longest_run);
+
+    rolls_to_entropy(die, &rolls)
+}
+
+/// Run (or resume) the vanity search, printing the progress to the console. Returns None if it failed or was stopped.
+fn run_vanity(config: &VanityConfig, state: Option<VanityState>, source: &mut dyn EntropySource) -> Option<PaperWallet> {
+    let num_threads = config.num_threads;
+    let collect_all = config.collect_all;
+
+    let observer = |event: VanityEvent| match event {
+        VanityEvent::Progress(p) => {
+            let eta = match p.eta {
+                Some(eta) => { let (s, d) = pretty_duration(eta.as_secs_f64()); format!("{} {}", s, d) },
+                None      => "unknown".to_string()
+            };
+            print!("Checking addresses at {:.0}/sec on {} CPU threads. [50% ETA = {}]   \r", p.rate, num_threads, eta);
+            io::stdout().flush().ok();
+        },
+        VanityEvent::Found(pattern, entry) => {
+            println!();   // To clear the previous inline output to stdout;
+            if collect_all {
+                println!("Found {} ({})", entry.address(), pattern);
+            }
+        }
+    };
+
+    let result = match state {
+        Some(state) => resume_vanity_wallet(config, state, source, &CancelHandle::new(), observer),
+        None        => generate_vanity_wallet(config, source, &CancelHandle::new(), observer)
+    };
+
+    match result {
+        Ok(w) => Some(w),
+        Err(e) => {
+            println!();
+            eprintln!("{}", e);
+            if let (zecpaperlib::Error::Cancelled(_), Some(checkpoint)) = (&e, &config.checkpoint) {
+                eprintln!("Saved the progress to {}. Run with --resume {} to carry on", checkpoint.path, checkpoint.path);
+            }
+            None
+        }
+    }
+}
+
+fn pretty_duration(secs: f64) -> (String, String) {
+    let mut expected_dur  = "sec";
+    let mut expected_time = secs;
+
+    if expected_time > 60.0 {
+        expected_time /= 60.0;
+        expected_dur = "min";
+    }
+    if expected_time > 60.0 {
+        expected_time /= 60.0;
+        expected_dur = "hours";
+    }
+    if expected_time > 24.0 {
+        expected_time /= 24.0;
+        expected_dur = "days";
+    }
+    if expected_time > 30.0 {
+        expected_time /= 30.0;
+        expected_dur = "months";
+    }
+    if expected_time > 12.0 {
+        expected_time /= 12.0;
+        expected_dur = "years";
+    }
+
+    return (format!("{:.*}", 0, expected_time), expected_dur.to_string());
+}
+
+/// Write the wallet out in the given format. JSON is written to the console if there's no filename.
+/// Watch-only wallets have only the addresses and viewing keys. Returns false if it couldn't be written.
+fn save_wallet(addresses: &PaperWallet, format: &str, filename: Option<&str>, watch_only: bool, recipients: &[Recipient]) -> bool {
+    // The text based formats are written to the console if the filename is absent
+    if format != "pdf" {
+        let output = match format {
+            "csv"  => addresses.to_csv_string(watch_only),
+            "text" => addresses.to_text_string(watch_only),
+            "html" => match html::wallet_to_html(addresses, watch_only) {
+                Ok(h)  => h,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return false;
+                }
+            },
+            _      => if watch_only { addresses.to_watch_only_json_string() } else { addresses.to_json_string() }
+        };
         if filename.is_none() {
-            println!("{}", addresses);
+            println!("{}", output);
+            true
+        } else if !recipients.is_empty() {
+            write_encrypted(output.as_bytes(), filename.unwrap(), recipients)
         } else {
-            std::fs::write(filename.unwrap(), addresses).expect("Couldn't write to file!");
-            println!("Wrote {:?} as a plaintext file", filename);
+            match std::fs::write(filename.unwrap(), output) {
+                Ok(_)   => { println!("Wrote {:?} as a plaintext file", filename); true },
+                Err(e)  => { eprintln!("Couldn't write to {:?}: {}", filename, e); false }
+            }
         }
-    } else if format == "pdf" {
+    } else {
         // We already know the output file name was specified
+        if !recipients.is_empty() {
+            return match pdf::wallet_to_pdf(addresses, watch_only) {
+                Ok(pdf) => write_encrypted(&pdf, filename.unwrap(), recipients),
+                Err(e)  => { eprintln!("{}", e); false }
+            };
+        }
+
         print!("Writing {:?} as a PDF file...", filename.unwrap());
         io::stdout().flush().ok();
-        match pdf::save_to_pdf(is_testnet, &addresses, filename.unwrap()) {
-            Ok(_)   => { println!("[OK]");},
+        let result = if watch_only {
+            pdf::save_watch_only_to_pdf(addresses, filename.unwrap())
+        } else {
+            pdf::save_to_pdf(addresses, filename.unwrap())
+        };
+        match result {
+            Ok(_)   => { println!("[OK]"); true },
             Err(e)  => {
                 eprintln!("[ERROR]");
                 eprintln!("{}", e);
+                false
             }
-        };       
+        }
     }    
+}
+
+/// Encrypt the output to the age recipients, and write it to the file. The plaintext is never written to the disk.
+/// Returns false if it couldn't be written.
+fn write_encrypted(data: &[u8], filename: &str, recipients: &[Recipient]) -> bool {
+    print!("Writing {:?} encrypted to {} recipient(s)...", filename, recipients.len());
+    io::stdout().flush().ok();
+    match encrypt_to_recipients(data, recipients).and_then(|e| std::fs::write(filename, e).map_err(zecpaperlib::Error::from)) {
+        Ok(_)   => { println!("[OK]"); true },
+        Err(e)  => {
+            eprintln!("[ERROR]");
+            eprintln!("{}", e);
+            false
+        }
+    }
 }
\ No newline at end of file
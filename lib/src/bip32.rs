@@ -0,0 +1,94 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha512;
+use crate::error::Error;
+
+/// Child indexes at or above this are hardened
+pub const HARDENED: u32 = 1 << 31;
+
+/// A BIP-32 extended secp256k1 private key. This is used to derive the transparent addresses
+/// from the same HD seed as the Sapling addresses, at the BIP-44 path m/44'/cointype'/0'/0/index
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExtendedPrivKey {
+    pub secret_key: secp256k1::SecretKey,
+    pub chain_code: [u8; 32],
+}
+
+impl ExtendedPrivKey {
+    /// The master key for the seed, from HMAC-SHA512("Bitcoin seed", seed)
+    pub fn master(seed: &[u8]) -> Result<ExtendedPrivKey, Error> {
+        let mut mac = Hmac::<Sha512>::new_varkey(b"Bitcoin seed")
+            .map_err(|_| Error::Derivation("Invalid HMAC key".to_string()))?;
+        mac.input(seed);
+
+        let i = mac.result().code();
+        let secret_key = secp256k1::SecretKey::parse_slice(&i[..32])
+            .map_err(|_| Error::Derivation("Seed produces an invalid master key".to_string()))?;
+
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+
+        Ok(ExtendedPrivKey { secret_key, chain_code })
+    }
+
+    /// Derive the child key at `index`. Indexes >= HARDENED are hardened children.
+    pub fn derive_child(&self, index: u32) -> Result<ExtendedPrivKey, Error> {
+        let mut mac = Hmac::<Sha512>::new_varkey(&self.chain_code)
+            .map_err(|_| Error::Derivation("Invalid HMAC key".to_string()))?;
+
+        if index >= HARDENED {
+            mac.input(&[0u8]);
+            mac.input(&self.secret_key.serialize());
+        } else {
+            mac.input(&secp256k1::PublicKey::from_secret_key(&self.secret_key).serialize_compressed());
+        }
+        mac.input(&index.to_be_bytes());
+
+        // The child key is parse256(IL) + parent key (mod n). This fails (with negligible probability)
+        // if IL >= n or the result is 0, in which case BIP-32 says to use the next index.
+        let i = mac.result().code();
+        let tweak = secp256k1::SecretKey::parse_slice(&i[..32])
+            .map_err(|_| Error::Derivation(format!("Invalid child key at index {}", index)))?;
+
+        let mut secret_key = self.secret_key.clone();
+        secret_key.tweak_add_assign(&tweak)
+            .map_err(|_| Error::Derivation(format!("Invalid child key at index {}", index)))?;
+
+        let mut chain_code = [0u8; 32];
+        chain_code.copy_from_slice(&i[32..]);
+
+        Ok(ExtendedPrivKey { secret_key, chain_code })
+    }
+
+    /// Derive the key at `path` from the seed's master key
+    pub fn from_path(seed: &[u8], path: &[u32]) -> Result<ExtendedPrivKey, Error> {
+        let mut key = ExtendedPrivKey::master(seed)?;
+        for index in path {
+            key = key.derive_child(*index)?;
+        }
+
+        Ok(key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Test vector 1 from BIP-32
+    #[test]
+    fn test_bip32_vectors() {
+        use crate::bip32::{ExtendedPrivKey, HARDENED};
+
+        let seed = hex::decode("000102030405060708090a0b0c0d0e0f").unwrap();
+
+        let testdata = [
+            (vec![],                "e8f32e723decf4051aefac8e2c93c9c5b214313817cdb01a1494b917c8436b35", "873dff81c02f525623fd1fe5167eac3a55a049de3d314bb42ee227ffed37d508"),
+            (vec![HARDENED],        "edb2e14f9ee77d26dd93b4ecede8d16ed408ce149b6cd80b0715a2d911a0afea", "47fdacbd0f1097043b78c63c20c34ef4ed9a111d980047ad16282c7ae6236141"),
+            (vec![HARDENED, 1],     "3c6cb8d0f6a264c91ea8b5030fadaa8e538b020f0a387421a12de9319dc93368", "2a7857631386ba23dacac34180dd1983734e444fdbf774041578e9b6adb37c19"),
+        ];
+
+        for (path, sk, chain_code) in testdata.iter() {
+            let key = ExtendedPrivKey::from_path(&seed, path).unwrap();
+            assert_eq!(hex::encode(key.secret_key.serialize()), *sk);
+            assert_eq!(hex::encode(key.chain_code), *chain_code);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+use std::fs;
+use std::io::{self, Write};
+use std::time::Duration;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, KeyInit};
+use json::{object, JsonValue};
+use crate::paper::{Network, CoinParams};
+use crate::vanity::{VanityConfig, VanityMode, VanityPattern};
+use crate::entropy::EntropySource;
+use crate::wallet::WalletEntry;
+use crate::error::Error;
+
+/// scrypt parameters for the checkpoint key. log_n = 15 takes about 100ms and 32MB, which is only paid when saving
+/// and loading. They are stored in the checkpoint, so they can be changed later without breaking old checkpoints.
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+/// Don't let a (possibly tampered) checkpoint make us use more than 1GB of memory, or take more than 16 times as long
+const MAX_LOG_N: u8 = 20;
+const MAX_MEMORY: u64 = 1 << 30;
+const MAX_P: u32 = 16;
+
+/// Where (and how often) a vanity search saves its progress. The checkpoint has the starting seeds of the threads, so
+/// it is encrypted with the passphrase.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CheckpointConfig {
+    pub path      : String,
+    pub passphrase: String,
+    pub interval  : Duration,
+}
+
+impl CheckpointConfig {
+    /// Save to the file every minute
+    pub fn new(path: &str, passphrase: &str) -> CheckpointConfig {
+        CheckpointConfig {
+            path      : path.to_string(),
+            passphrase: passphrase.to_string(),
+            interval  : Duration::from_secs(60),
+        }
+    }
+}
+
+/// The progress of a single vanity thread. The thread started from `seed`, and has checked the `checked` seeds after
+/// it, so it carries on from `seed + checked`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ThreadState {
+    pub seed   : [u8; 32],
+    pub checked: u64,
+}
+
+/// Everything needed to carry on with a vanity search exactly where it left off
+#[derive(Clone, Debug, PartialEq)]
+pub struct VanityState {
+    pub network    : Network,
+    pub is_taddr   : bool,
+    pub mode       : VanityMode,
+    pub patterns   : Vec<VanityPattern>,
+    pub collect_all: bool,
+    pub threads    : Vec<ThreadState>,
+    /// Time spent on the search before this run
+    pub elapsed    : Duration,
+    /// The addresses found so far, one slot per pattern
+    pub found      : Vec<Option<WalletEntry>>,
+}
+
+impl VanityState {
+    /// Total number of addresses checked across all the threads
+    pub fn attempts(&self) -> u64 {
+        self.threads.iter().map(|t| t.checked).sum()
+    }
+
+    fn to_json(&self) -> JsonValue {
+        let patterns: Vec<JsonValue> = self.patterns.iter().map(|p| {
+            let kind = match p {
+                VanityPattern::Prefix(_)   => "prefix",
+                VanityPattern::Suffix(_)   => "suffix",
+                VanityPattern::Contains(_) => "contains",
+            };
+            object!{ "type" => kind, "text" => p.text() }
+        }).collect();
+
+        let threads: Vec<JsonValue> = self.threads.iter().map(|t| object!{
+            "seed"    => hex::encode(t.seed),
+            "checked" => t.checked
+        }).collect();
+
+        let found: Vec<JsonValue> = self.found.iter().map(|f| match f {
+            Some(entry) => entry.to_json(),
+            None        => JsonValue::Null
+        }).collect();
+
+        let mut j = object!{
+            "network"      => self.network.name(),
+            "is_taddr"     => self.is_taddr,
+            "mode"         => match self.mode { VanityMode::Seeds => "seeds", VanityMode::Diversifier => "diversifier" },
+            "collect_all"  => self.collect_all,
+            "patterns"     => patterns,
+            "threads"      => threads,
+            "elapsed_secs" => self.elapsed.as_secs(),
+            "found"        => found
+        };
+
+        // Custom coins are saved along with the search, so it can be resumed without the coin's file
+        if let Network::Custom(coin) = &self.network {
+            j["coin"] = coin.to_json();
+        }
+
+        j
+    }
+
+    fn from_json(j: &JsonValue) -> Result<VanityState, Error> {
+        let missing = |name: &str| Error::Encoding(format!("Missing \"{}\" in the checkpoint", name));
+
+        let patterns = j["patterns"].members().map(|p| {
+            let text = p["text"].as_str().ok_or_else(|| missing("text"))?.to_string();
+            match p["type"].as_str() {
+                Some("prefix")   => Ok(VanityPattern::Prefix(text)),
+                Some("suffix")   => Ok(VanityPattern::Suffix(text)),
+                Some("contains") => Ok(VanityPattern::Contains(text)),
+                t                => Err(Error::Encoding(format!("Unknown vanity pattern type {:?}", t)))
+            }
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let network = match j["network"].as_str() {
+            Some("custom") => Network::Custom(Box::new(CoinParams::from_json(&j["coin"].dump())?)),
+            Some(name)     => Network::from_name(name)?,
+            None           => return Err(missing("network"))
+        };
+
+        let mode = match j["mode"].as_str() {
+            Some("seeds")       => VanityMode::Seeds,
+            Some("diversifier") => VanityMode::Diversifier,
+            Some(m)             => return Err(Error::Encoding(format!("Unknown vanity search mode {}", m))),
+            None                => return Err(missing("mode"))
+        };
+
+        let threads = j["threads"].members().map(|t| {
+            let bytes = hex::decode(t["seed"].as_str().ok_or_else(|| missing("seed"))?)
+                .map_err(|e| Error::Encoding(format!("Invalid thread seed: {}", e)))?;
+            if bytes.len() != 32 {
+                return Err(Error::Encoding(format!("Thread seed should be 32 bytes, got {}", bytes.len())));
+            }
+
+            let mut seed = [0u8; 32];
+            seed.copy_from_slice(&bytes);
+            Ok(ThreadState { seed, checked: t["checked"].as_u64().ok_or_else(|| missing("checked"))? })
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        let found = j["found"].members().map(|f| if f.is_null() {
+            Ok(None)
+        } else {
+            WalletEntry::from_json(f).map(Some)
+        }).collect::<Result<Vec<_>, Error>>()?;
+
+        if patterns.is_empty() || threads.is_empty() || found.len() != patterns.len() {
+            return Err(Error::Encoding("The checkpoint doesn't have any patterns or threads".to_string()));
+        }
+
+        Ok(VanityState {
+            network,
+            is_taddr   : j["is_taddr"].as_bool().ok_or_else(|| missing("is_taddr"))?,
+            mode,
+            patterns,
+            collect_all: j["collect_all"].as_bool().unwrap_or(false),
+            threads,
+            elapsed    : Duration::from_secs(j["elapsed_secs"].as_u64().unwrap_or(0)),
+            found,
+        })
+    }
+}
+
+/// Encrypt the search state with the passphrase and write it to the checkpoint file. The file is written next to the
+/// old one and then renamed over it, so an interruption while saving doesn't lose the previous checkpoint. On unix, only
+/// the owner can read it. The salt and nonce come from `source`.
+pub fn save_checkpoint(state: &VanityState, checkpoint: &CheckpointConfig, source: &mut dyn EntropySource) -> Result<(), Error> {
+    save_checkpoint_with(state, checkpoint, source, SCRYPT_LOG_N)
+}
+
+fn save_checkpoint_with(state: &VanityState, checkpoint: &CheckpointConfig, source: &mut dyn EntropySource, log_n: u8) -> Result<(), Error> {
+    let mut salt  = [0u8; 32];
+    let mut nonce = [0u8; 12];
+    source.fill_bytes(&mut salt)?;
+    source.fill_bytes(&mut nonce)?;
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&derive_key(&checkpoint.passphrase, &salt, log_n, SCRYPT_R, SCRYPT_P)?));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), state.to_json().dump().as_bytes())
+        .map_err(|_| Error::Encoding("Couldn't encrypt the checkpoint".to_string()))?;
+
+    let j = object!{
+        "version"    => 1,
+        "kdf"        => "scrypt",
+        "log_n"      => log_n,
+        "r"          => SCRYPT_R,
+        "p"          => SCRYPT_P,
+        "salt"       => hex::encode(salt),
+        "nonce"      => hex::encode(nonce),
+        "ciphertext" => hex::encode(ciphertext)
+    };
+
+    let tmp = format!("{}.tmp", checkpoint.path);
+    write_private(&tmp, json::stringify_pretty(j, 2).as_bytes())?;
+    fs::rename(&tmp, &checkpoint.path)?;
+
+    Ok(())
+}
+
+/// Write a new file with mode 0600 on unix. The mode only applies to new files, so a file left over from an interrupted
+/// save is removed first.
+fn write_private(path: &str, data: &[u8]) -> Result<(), Error> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+        _ => {}
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(path)?.write_all(data)?;
+    Ok(())
+}
+
+/// Read and decrypt a checkpoint written by `save_checkpoint`. Returns the search state, and a config that resumes the
+/// search with the same patterns and threads, saving to the same checkpoint file. The config has no time or attempt
+/// limits, the caller can set them before resuming.
+pub fn load_checkpoint(path: &str, passphrase: &str) -> Result<(VanityConfig, VanityState), Error> {
+    let j = json::parse(&fs::read_to_string(path)?)?;
+    if j["version"].as_u32() != Some(1) || j["kdf"].as_str() != Some("scrypt") {
+        return Err(Error::Encoding(format!("{} is not a vanity checkpoint", path)));
+    }
+
+    let field = |name: &str| -> Result<Vec<u8>, Error> {
+        let s = j[name].as_str().ok_or_else(|| Error::Encoding(format!("Missing \"{}\" in the checkpoint", name)))?;
+        hex::decode(s).map_err(|e| Error::Encoding(format!("Invalid \"{}\" in the checkpoint: {}", name, e)))
+    };
+    let (salt, nonce, ciphertext) = (field("salt")?, field("nonce")?, field("ciphertext")?);
+    if nonce.len() != 12 {
+        return Err(Error::Encoding(format!("Checkpoint nonce should be 12 bytes, got {}", nonce.len())));
+    }
+
+    let scrypt_param = |name: &str| j[name].as_u32().ok_or_else(|| Error::Encoding(format!("Missing \"{}\" in the checkpoint", name)));
+    let (log_n, r, p) = (scrypt_param("log_n")?, scrypt_param("r")?, scrypt_param("p")?);
+    if log_n > MAX_LOG_N as u32 || 128 * r as u64 * (1u64 << log_n) > MAX_MEMORY || p > MAX_P {
+        return Err(Error::Encoding(format!("Checkpoint asks for scrypt with log_n = {}, r = {} and p = {}, which is too large", log_n, r, p)));
+    }
+
+    let cipher = ChaCha20Poly1305::new(Key::from_slice(&derive_key(passphrase, &salt, log_n as u8, r, p)?));
+    let plaintext = cipher.decrypt(Nonce::from_slice(&nonce), &ciphertext[..])
+        .map_err(|_| Error::InvalidInput("Couldn't decrypt the checkpoint. Is the passphrase right?".to_string()))?;
+    let plaintext = String::from_utf8(plaintext)
+        .map_err(|_| Error::Encoding("The checkpoint isn't valid UTF-8".to_string()))?;
+
+    let state = VanityState::from_json(&json::parse(&plaintext)?)?;
+
+    let mut config = VanityConfig::new(state.network.clone(), state.is_taddr, state.patterns.clone());
+    config.num_threads = state.threads.len() as u32;
+    config.mode        = state.mode;
+    config.collect_all = state.collect_all;
+    config.checkpoint  = Some(CheckpointConfig::new(path, passphrase));
+
+    Ok((config, state))
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<[u8; 32], Error> {
+    let params = scrypt::Params::new(log_n, r, p, 32)
+        .map_err(|e| Error::InvalidInput(format!("Invalid scrypt parameters: {}", e)))?;
+
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| Error::InvalidInput(format!("Couldn't derive the checkpoint key: {}", e)))?;
+
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_checkpoint_roundtrip() {
+        use std::time::Duration;
+        use crate::checkpoint::{save_checkpoint, save_checkpoint_with, load_checkpoint, CheckpointConfig, ThreadState, VanityState};
+        use crate::vanity::{VanityMode, VanityPattern};
+        use crate::paper::{generate_wallet, WalletOptions, params, Network};
+        use crate::entropy::SystemEntropy;
+
+        let entry = generate_wallet(&WalletOptions::new(Network::Regtest), &mut SystemEntropy, &[]).unwrap().entries.remove(0);
+        let state = VanityState {
+            network    : Network::Regtest,
+            is_taddr   : false,
+            mode       : VanityMode::Diversifier,
+            patterns   : vec![VanityPattern::Prefix("abc".to_string()), VanityPattern::Contains("xyz".to_string())],
+            collect_all: true,
+            threads    : vec![ThreadState { seed: [7; 32], checked: 15000 }, ThreadState { seed: [255; 32], checked: 5000 }],
+            elapsed    : Duration::from_secs(1234),
+            found      : vec![None, Some(entry)],
+        };
+        assert_eq!(state.attempts(), 20000);
+
+        let path = std::env::temp_dir().join(format!("zecpaper-checkpoint-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+        save_checkpoint(&state, &CheckpointConfig::new(path, "correct horse"), &mut SystemEntropy).unwrap();
+
+        // The seeds aren't in the file in the clear, and only the owner can read it
+        let contents = std::fs::read_to_string(path).unwrap();
+        assert!(!contents.contains(&hex::encode([7u8; 32])));
+        assert!(!contents.contains("abc"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        // Saving again replaces the file, even if an earlier save left its temp file behind
+        std::fs::write(format!("{}.tmp", path), "partial").unwrap();
+        save_checkpoint(&state, &CheckpointConfig::new(path, "correct horse"), &mut SystemEntropy).unwrap();
+        assert!(!std::path::Path::new(&format!("{}.tmp", path)).exists());
+
+        let (config, loaded) = load_checkpoint(path, "correct horse").unwrap();
+        assert_eq!(loaded, state);
+        assert_eq!(config.patterns, state.patterns);
+        assert_eq!(config.num_threads, 2);
+        assert_eq!(config.mode, VanityMode::Diversifier);
+        assert!(config.collect_all);
+        assert_eq!(config.checkpoint.unwrap().path, path);
+
+        load_checkpoint(path, "wrong horse").expect_err("Wrong passphrase");
+
+        // The scrypt parameters are read from the file, not assumed
+        save_checkpoint_with(&state, &CheckpointConfig::new(path, "correct horse"), &mut SystemEntropy, 10).unwrap();
+        let j = json::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(j["log_n"], 10);
+        assert_eq!(load_checkpoint(path, "correct horse").unwrap().1, state);
+
+        // But not if they would take too much memory or time
+        for (name, value) in [("log_n", 21), ("r", 65536), ("p", 1000)].iter() {
+            let mut tampered = j.clone();
+            tampered[*name] = (*value).into();
+            std::fs::write(path, tampered.dump()).unwrap();
+            load_checkpoint(path, "correct horse").expect_err("scrypt parameters too large");
+        }
+        let mut tampered = j.clone();
+        tampered.remove("r");
+        std::fs::write(path, tampered.dump()).unwrap();
+        load_checkpoint(path, "correct horse").expect_err("Missing r");
+        std::fs::remove_file(path).unwrap();
+        load_checkpoint(path, "correct horse").expect_err("Missing file");
+
+        // Custom coins are saved with the state
+        let mut coin = params(&Network::Main);
+        coin.ticker = "ABC".to_string();
+        coin.zaddress_prefix = "abcs".to_string();
+        let custom = VanityState { network: Network::Custom(Box::new(coin)), found: vec![None, None], ..state };
+        assert_eq!(VanityState::from_json(&custom.to_json()).unwrap(), custom);
+
+        // The search mode has to be there
+        let mut j = custom.to_json();
+        j.remove("mode");
+        VanityState::from_json(&j).expect_err("Missing mode");
+
+        // And so does the network. A testnet flag isn't enough
+        let mut j = custom.to_json();
+        j.remove("network");
+        j["is_testnet"] = true.into();
+        VanityState::from_json(&j).expect_err("Missing network");
+    }
+}
@@ -0,0 +1,193 @@
+use crate::paper::double_sha256;
+use crate::error::Error;
+
+/// How many bits of entropy the rolls have to add up to
+pub const REQUIRED_BITS: f64 = 256.0;
+
+/// A physical source of randomness that the user reads off by hand
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Die {
+    Coin,
+    D6,
+    D20,
+}
+
+impl Die {
+    pub fn from_name(name: &str) -> Result<Die, Error> {
+        match name {
+            "coin" => Ok(Die::Coin),
+            "d6"   => Ok(Die::D6),
+            "d20"  => Ok(Die::D20),
+            _      => Err(Error::InvalidInput(format!("Unknown die '{}', expected coin, d6 or d20", name)))
+        }
+    }
+
+    pub fn sides(&self) -> u32 {
+        match self {
+            Die::Coin => 2,
+            Die::D6   => 6,
+            Die::D20  => 20,
+        }
+    }
+
+    /// Number of rolls (or flips) needed for 256 bits of entropy: 256 coin flips, 100 d6 rolls or 60 d20 rolls
+    pub fn rolls_needed(&self) -> usize {
+        (REQUIRED_BITS / (self.sides() as f64).log2()).ceil() as usize
+    }
+
+    /// Critical value of the chi-square test with `sides - 1` degrees of freedom, at a significance of 0.001. Fair
+    /// dice fail the test about once in a thousand tries.
+    fn chi_square_critical(&self) -> f64 {
+        match self {
+            Die::Coin => 10.828,
+            Die::D6   => 20.515,
+            Die::D20  => 43.820,
+        }
+    }
+}
+
+/// The results of the bias checks on a set of rolls
+#[derive(Clone, Debug, PartialEq)]
+pub struct RollReport {
+    pub rolls      : usize,
+    /// Pearson's chi-square statistic against a uniform distribution
+    pub chi_square : f64,
+    pub critical   : f64,
+    /// The longest run of the same result
+    pub longest_run: usize,
+}
+
+/// Parse rolls typed in by the user. Results can be separated by spaces or commas. For coins and d6, they can also be
+/// typed in without separators, like "HTTHT" or "35216". Coin flips are H/T (or 1/0), and die rolls are the number on
+/// the face. Returns the rolls as 0..sides.
+pub fn parse_rolls(die: Die, input: &str) -> Result<Vec<u8>, Error> {
+    let mut rolls = vec![];
+
+    for token in input.split(|c: char| c.is_whitespace() || c == ',').filter(|t| !t.is_empty()) {
+        match die {
+            Die::Coin => for c in token.chars() {
+                rolls.push(match c.to_ascii_uppercase() {
+                    'H' | '1' => 1,
+                    'T' | '0' => 0,
+                    _         => return Err(Error::InvalidInput(format!("Invalid coin flip '{}', expected H or T", c)))
+                });
+            },
+            Die::D6 => for c in token.chars() {
+                match c.to_digit(10) {
+                    Some(d) if d >= 1 && d <= 6 => rolls.push((d - 1) as u8),
+                    _ => return Err(Error::InvalidInput(format!("Invalid d6 roll '{}', expected 1 to 6", c)))
+                }
+            },
+            Die::D20 => match token.parse::<u32>() {
+                Ok(d) if d >= 1 && d <= 20 => rolls.push((d - 1) as u8),
+                _ => return Err(Error::InvalidInput(format!("Invalid d20 roll '{}', expected 1 to 20", token)))
+            },
+        }
+    }
+
+    Ok(rolls)
+}
+
+/// Check that there are enough rolls, and that they don't look biased. The rolls are tested with a chi-square test
+/// against a fair die, and for runs of the same result that are too long to happen by chance (less than 1 in 1000).
+pub fn check_rolls(die: Die, rolls: &[u8]) -> Result<RollReport, Error> {
+    if rolls.len() < die.rolls_needed() {
+        return Err(Error::InvalidInput(format!("Need at least {} rolls for {} bits of entropy, got {}",
+                                                die.rolls_needed(), REQUIRED_BITS, rolls.len())));
+    }
+
+    let sides = die.sides() as usize;
+    let mut counts = vec![0usize; sides];
+    for r in rolls {
+        counts[*r as usize] += 1;
+    }
+
+    let expected = rolls.len() as f64 / sides as f64;
+    let chi_square = counts.iter().map(|c| (*c as f64 - expected).powi(2) / expected).sum::<f64>();
+
+    let mut longest_run = 1;
+    let mut run = 1;
+    for i in 1..rolls.len() {
+        run = if rolls[i] == rolls[i-1] { run + 1 } else { 1 };
+        longest_run = longest_run.max(run);
+    }
+
+    let report = RollReport { rolls: rolls.len(), chi_square, critical: die.chi_square_critical(), longest_run };
+
+    if chi_square > report.critical {
+        return Err(Error::InvalidInput(format!("The rolls look biased (chi-square {:.1}, should be under {:.1}). Use a different die, or roll again",
+                                                chi_square, report.critical)));
+    }
+
+    // The chance of a run this long starting anywhere in the rolls
+    let run_chance = rolls.len() as f64 * (1.0 / sides as f64).powi(longest_run as i32 - 1);
+    if run_chance < 0.001 {
+        return Err(Error::InvalidInput(format!("The rolls have {} of the same result in a row, which is very unlikely with a fair die. Roll again",
+                                                longest_run)));
+    }
+
+    Ok(report)
+}
+
+/// Check the rolls, and hash them into 32 bytes of entropy. The result is used as the user entropy in
+/// `generate_wallet`, where it is mixed with the system entropy.
+pub fn rolls_to_entropy(die: Die, rolls: &[u8]) -> Result<Vec<u8>, Error> {
+    check_rolls(die, rolls)?;
+
+    let mut data = format!("{:?}:", die).into_bytes();
+    data.extend_from_slice(rolls);
+    Ok(double_sha256(&data))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_parse_rolls() {
+        use crate::dice::{parse_rolls, Die};
+
+        assert_eq!(parse_rolls(Die::Coin, "HT ht, 10").unwrap(), vec![1, 0, 1, 0, 1, 0]);
+        assert_eq!(parse_rolls(Die::D6, "123 4,5\n6").unwrap(), vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(parse_rolls(Die::D20, "1 20, 13").unwrap(), vec![0, 19, 12]);
+
+        parse_rolls(Die::Coin, "HTX").expect_err("Not a coin flip");
+        parse_rolls(Die::D6, "1270").expect_err("Not a d6 roll");
+        parse_rolls(Die::D20, "1 21").expect_err("Not a d20 roll");
+        parse_rolls(Die::D20, "1220").expect_err("d20 rolls need separators");
+    }
+
+    #[test]
+    fn test_check_rolls() {
+        use crate::dice::{check_rolls, rolls_to_entropy, Die};
+        use rand::{Rng, ChaChaRng, SeedableRng};
+
+        assert_eq!(Die::Coin.rolls_needed(), 256);
+        assert_eq!(Die::D6.rolls_needed(), 100);
+        assert_eq!(Die::D20.rolls_needed(), 60);
+
+        // Fair rolls from a fixed seed pass
+        let mut rng = ChaChaRng::from_seed([3; 32]);
+        for die in [Die::Coin, Die::D6, Die::D20].iter() {
+            let rolls: Vec<u8> = (0..die.rolls_needed()).map(|_| rng.gen_range(0, die.sides()) as u8).collect();
+            let report = check_rolls(*die, &rolls).unwrap();
+            assert!(report.chi_square < report.critical);
+
+            let e = rolls_to_entropy(*die, &rolls).unwrap();
+            assert_eq!(e.len(), 32);
+            check_rolls(*die, &rolls[1..]).expect_err("Too few rolls");
+        }
+
+        // A d6 that rolls too many sixes
+        let loaded: Vec<u8> = (0..100).map(|i| if i % 3 == 0 { (i / 3 % 5) as u8 } else { 5 }).collect();
+        check_rolls(Die::D6, &loaded).expect_err("Biased");
+
+        // A fair looking sequence of flips with a very long run in the middle
+        let mut flips: Vec<u8> = (0..256).map(|i| (i % 2) as u8).collect();
+        for f in flips[100..125].iter_mut() {
+            *f = 1;
+        }
+        for f in flips[125..150].iter_mut() {
+            *f = 0;
+        }
+        check_rolls(Die::Coin, &flips).expect_err("Long run");
+    }
+}
@@ -0,0 +1,178 @@
+use std::fs::File;
+use std::io::Read;
+use rand::{RngCore, ChaChaRng, SeedableRng};
+use sha2::{Sha256, Digest};
+use crate::dice::{rolls_to_entropy, Die};
+use crate::error::Error;
+
+/// How many bytes of user entropy are needed to generate a wallet when the entropy source fails. Without a working
+/// source, the user entropy is all there is, so it has to be enough on its own.
+pub const MIN_USER_ENTROPY: usize = 32;
+
+/// Where the random bytes for new keys come from. The caller picks the source and passes it to `generate_wallet`, so
+/// every build (including wasm and embedded ones) decides explicitly where its randomness comes from. The bytes are
+/// always mixed with the user's entropy before they are used.
+pub trait EntropySource {
+    /// A short description of the source, for showing to the user
+    fn name(&self) -> String;
+
+    /// Fill `dest` with random bytes, or return an error if the source isn't available
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error>;
+}
+
+/// The operating system's random number generator
+#[cfg(feature = "systemrand")]
+pub struct SystemEntropy;
+
+#[cfg(feature = "systemrand")]
+impl EntropySource for SystemEntropy {
+    fn name(&self) -> String {
+        "system".to_string()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        rand::rngs::OsRng::new()
+            .and_then(|mut rng| rng.try_fill_bytes(dest))
+            .map_err(|e| Error::Rng(format!("Couldn't get entropy from the system: {}", e)))
+    }
+}
+
+/// A hardware random number generator that is read as a device file, like /dev/hwrng
+pub struct DeviceEntropy {
+    path: String,
+}
+
+impl DeviceEntropy {
+    pub fn new(path: &str) -> DeviceEntropy {
+        DeviceEntropy { path: path.to_string() }
+    }
+}
+
+impl EntropySource for DeviceEntropy {
+    fn name(&self) -> String {
+        format!("device {}", self.path)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        File::open(&self.path)
+            .and_then(|mut f| f.read_exact(dest))
+            .map_err(|e| Error::Rng(format!("Couldn't read entropy from {}: {}", self.path, e)))
+    }
+}
+
+/// Dice rolls or coin flips. The rolls are checked for bias and hashed into 32 bytes, which are then stretched to
+/// however many bytes are asked for.
+pub struct DiceEntropy {
+    entropy: Vec<u8>,
+    counter: u64,
+}
+
+impl DiceEntropy {
+    pub fn new(die: Die, rolls: &[u8]) -> Result<DiceEntropy, Error> {
+        Ok(DiceEntropy { entropy: rolls_to_entropy(die, rolls)?, counter: 0 })
+    }
+}
+
+impl EntropySource for DiceEntropy {
+    fn name(&self) -> String {
+        "dice".to_string()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        for chunk in dest.chunks_mut(32) {
+            let mut state = Sha256::new();
+            state.input(&self.entropy);
+            state.input(&self.counter.to_le_bytes());
+            self.counter += 1;
+
+            chunk.copy_from_slice(&state.result()[..chunk.len()]);
+        }
+
+        Ok(())
+    }
+}
+
+/// No entropy source. The wallet is generated from the user's entropy alone, which has to be at least
+/// `MIN_USER_ENTROPY` bytes. This is for builds without a system RNG, like the browser, where the caller collects
+/// the randomness itself.
+pub struct NoEntropy;
+
+impl EntropySource for NoEntropy {
+    fn name(&self) -> String {
+        "none".to_string()
+    }
+
+    fn fill_bytes(&mut self, _dest: &mut [u8]) -> Result<(), Error> {
+        Err(Error::Rng("No entropy source".to_string()))
+    }
+}
+
+/// A deterministic source that always produces the same bytes for the same seed. Only for tests, never use it to
+/// generate real wallets!
+pub struct DeterministicEntropy {
+    rng: ChaChaRng,
+}
+
+impl DeterministicEntropy {
+    pub fn new(seed: [u8; 32]) -> DeterministicEntropy {
+        DeterministicEntropy { rng: ChaChaRng::from_seed(seed) }
+    }
+}
+
+impl EntropySource for DeterministicEntropy {
+    fn name(&self) -> String {
+        "deterministic".to_string()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_entropy_sources() {
+        use crate::entropy::{EntropySource, DeterministicEntropy, DeviceEntropy, DiceEntropy, NoEntropy};
+        use crate::dice::Die;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+
+        // The deterministic source gives the same wallet every time
+        let w1 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Main) }, &mut DeterministicEntropy::new([7; 32]), &[]).unwrap();
+        let w2 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Main) }, &mut DeterministicEntropy::new([7; 32]), &[]).unwrap();
+        let w3 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Main) }, &mut DeterministicEntropy::new([8; 32]), &[]).unwrap();
+        assert_eq!(w1.to_json_string(), w2.to_json_string());
+        assert_ne!(w1.to_json_string(), w3.to_json_string());
+
+        // ...and the user entropy still changes it
+        let w4 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Main) }, &mut DeterministicEntropy::new([7; 32]), b"user").unwrap();
+        assert_ne!(w1.to_json_string(), w4.to_json_string());
+
+        // Without a source, there has to be enough user entropy
+        generate_wallet(&WalletOptions::new(Network::Main), &mut NoEntropy, &[]).expect_err("No entropy");
+        generate_wallet(&WalletOptions::new(Network::Main), &mut NoEntropy, &[1; 31]).expect_err("Too little entropy");
+        generate_wallet(&WalletOptions::new(Network::Main), &mut NoEntropy, &[1; 32]).unwrap();
+
+        // A device file that doesn't exist is an error too
+        let mut device = DeviceEntropy::new("/nonexistent/hwrng");
+        device.fill_bytes(&mut [0; 32]).expect_err("No such device");
+        generate_wallet(&WalletOptions::new(Network::Main), &mut device, &[]).expect_err("No such device");
+
+        let path = std::env::temp_dir().join(format!("zecpaper-hwrng-{}", std::process::id()));
+        std::fs::write(&path, &[5u8; 64]).unwrap();
+        let mut device = DeviceEntropy::new(path.to_str().unwrap());
+        let mut bytes = [0u8; 32];
+        device.fill_bytes(&mut bytes).unwrap();
+        assert_eq!(bytes, [5; 32]);
+        std::fs::remove_file(&path).unwrap();
+
+        // Dice rolls are stretched to any length, without repeating
+        let rolls: Vec<u8> = (0..100).map(|i| ((i * 7 + i / 6) % 6) as u8).collect();
+        let mut dice = DiceEntropy::new(Die::D6, &rolls).unwrap();
+        let mut bytes = [0u8; 64];
+        dice.fill_bytes(&mut bytes).unwrap();
+        assert_ne!(&bytes[..32], &bytes[32..]);
+        DiceEntropy::new(Die::D6, &rolls[..50]).err().expect("Too few rolls");
+    }
+}
@@ -0,0 +1,57 @@
+use std::fmt;
+use std::io;
+
+/// Errors returned by the public functions in zecpaperlib. None of the library functions
+/// should panic on bad input, so that hosts like the Qt app and the browser can report the
+/// problem and recover.
+#[derive(Debug)]
+pub enum Error {
+    /// A key or address couldn't be encoded/decoded (bech32, base58, JSON)
+    Encoding(String),
+    /// A key couldn't be derived, for eg. no valid diversifier was found
+    Derivation(String),
+    /// The random number generator couldn't be initialized
+    Rng(String),
+    /// The PDF couldn't be rendered
+    Pdf(String),
+    /// Reading or writing a file failed
+    Io(io::Error),
+    /// The caller passed in an invalid argument, like a vanity prefix with illegal characters
+    InvalidInput(String),
+    /// A long running operation (like a vanity search) was cancelled, or ran into its time or attempt limit
+    Cancelled(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Encoding(e)      => write!(f, "Encoding error: {}", e),
+            Error::Derivation(e)    => write!(f, "Key derivation error: {}", e),
+            Error::Rng(e)           => write!(f, "Random number generator error: {}", e),
+            Error::Pdf(e)           => write!(f, "PDF error: {}", e),
+            Error::Io(e)            => write!(f, "I/O error: {}", e),
+            Error::InvalidInput(e)  => write!(f, "{}", e),
+            Error::Cancelled(e)     => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::Io(e)
+    }
+}
+
+impl From<bech32::Error> for Error {
+    fn from(e: bech32::Error) -> Error {
+        Error::Encoding(format!("{}", e))
+    }
+}
+
+impl From<json::Error> for Error {
+    fn from(e: json::Error) -> Error {
+        Error::Encoding(format!("{}", e))
+    }
+}
@@ -0,0 +1,107 @@
+//! Arithmetic in the finite field GF(2^8), for the Shamir secret sharing in `shamir`. Elements are bytes, addition is
+//! XOR and multiplication is modulo the AES polynomial x^8 + x^4 + x^3 + x + 1. None of these functions branch or
+//! index tables on secret data, so they don't leak the seed bytes through timing.
+
+/// The low byte of the reduction polynomial x^8 + x^4 + x^3 + x + 1
+const POLY: u8 = 0x1b;
+
+pub fn add(a: u8, b: u8) -> u8 {
+    a ^ b
+}
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    let mut a = a;
+    let mut p = 0u8;
+    for i in 0..8 {
+        // Add a if bit i of b is set, then multiply a by x
+        p ^= a & 0u8.wrapping_sub((b >> i) & 1);
+        a = (a << 1) ^ (POLY & 0u8.wrapping_sub(a >> 7));
+    }
+    p
+}
+
+/// The multiplicative inverse, a^254. 0 has no inverse, and returns 0.
+pub fn inv(a: u8) -> u8 {
+    let mut result = 1u8;
+    let mut base = a;
+    // 254 = 0b11111110
+    for i in 0..8 {
+        if (254 >> i) & 1 == 1 {
+            result = mul(result, base);
+        }
+        base = mul(base, base);
+    }
+    result
+}
+
+pub fn div(a: u8, b: u8) -> u8 {
+    mul(a, inv(b))
+}
+
+/// Evaluate the polynomial with the given coefficients (lowest degree first) at x
+pub fn eval(coefficients: &[u8], x: u8) -> u8 {
+    coefficients.iter().rev().fold(0, |acc, c| add(mul(acc, x), *c))
+}
+
+/// The value at x = 0 of the lowest degree polynomial through the points, by Lagrange interpolation. The x values
+/// have to be distinct and non-zero.
+pub fn interpolate_at_zero(points: &[(u8, u8)]) -> u8 {
+    let mut result = 0u8;
+    for (i, (xi, yi)) in points.iter().enumerate() {
+        // The basis polynomial for point i at 0 is the product of xj / (xj - xi) over the other points
+        let mut basis = 1u8;
+        for (j, (xj, _)) in points.iter().enumerate() {
+            if i != j {
+                basis = mul(basis, div(*xj, add(*xj, *xi)));
+            }
+        }
+        result = add(result, mul(*yi, basis));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_field() {
+        use crate::gf256::{add, mul, inv, div};
+
+        // Known products from the AES spec
+        assert_eq!(mul(0x57, 0x83), 0xc1);
+        assert_eq!(mul(0x57, 0x13), 0xfe);
+        assert_eq!(mul(0x53, 0xca), 0x01);
+        assert_eq!(inv(0x53), 0xca);
+        assert_eq!(inv(0), 0);
+
+        for a in 0..=255u8 {
+            assert_eq!(add(a, a), 0);
+            assert_eq!(mul(a, 1), a);
+            assert_eq!(mul(a, 0), 0);
+            if a != 0 {
+                assert_eq!(mul(a, inv(a)), 1);
+            }
+            for b in 1..=255u8 {
+                assert_eq!(mul(a, b), mul(b, a));
+                assert_eq!(div(mul(a, b), b), a);
+            }
+        }
+    }
+
+    #[test]
+    fn test_interpolate() {
+        use crate::gf256::{eval, interpolate_at_zero};
+
+        let poly = [0x42, 0x13, 0xf7];
+        let points: Vec<(u8, u8)> = (1..=5u8).map(|x| (x, eval(&poly, x))).collect();
+        assert_eq!(eval(&poly, 0), 0x42);
+
+        // Any 3 points give back the constant term
+        assert_eq!(interpolate_at_zero(&points[0..3]), 0x42);
+        assert_eq!(interpolate_at_zero(&points[2..5]), 0x42);
+        assert_eq!(interpolate_at_zero(&[points[0], points[2], points[4]]), 0x42);
+        assert_eq!(interpolate_at_zero(&points), 0x42);
+
+        // ...but 2 points don't
+        assert_ne!(interpolate_at_zero(&points[0..2]), 0x42);
+    }
+}
@@ -0,0 +1,199 @@
+use crate::wallet::{PaperWallet, WalletEntry};
+use crate::keycrypt::is_encrypted_key;
+use crate::error::Error;
+
+use qrcode::QrCode;
+use qrcode::render::svg;
+
+/// The page styles. Each address gets half an A4 page, like the PDF, so two of them fit on every printed page.
+const STYLE: &str = r#"
+body { font-family: "Courier New", Courier, monospace; margin: 0; }
+.page { width: 200mm; margin: 0 auto; page-break-after: always; }
+.page:last-child { page-break-after: auto; }
+.wallet { height: 140mm; border-bottom: 2px solid black; box-sizing: border-box; padding: 5mm; overflow: hidden; }
+.section { display: flex; align-items: flex-start; }
+.section + .section { border-top: 1px dashed black; margin-top: 4mm; padding-top: 4mm; }
+.qr svg { width: 40mm; height: 40mm; }
+.text { flex: 1; margin: 0 5mm; word-break: break-all; }
+h1 { font-size: 14pt; margin: 0 0 2mm 0; }
+h2 { font-size: 12pt; margin: 3mm 0 1mm 0; }
+.key { font-size: 10pt; }
+.small { font-size: 8pt; }
+@media print { .page { margin: 0; } }
+"#;
+
+/**
+ * Render the wallet as a single self-contained HTML page, laid out like the PDF. The CSS and the QR codes (as SVG)
+ * are inline, so it can be opened and printed from any browser on an offline machine.
+ */
+pub fn wallet_to_html(wallet: &PaperWallet, watch_only: bool) -> Result<String, Error> {
+    let title = if watch_only {
+        format!("{} Watch-Only Wallet", wallet.ticker)
+    } else {
+        format!("{} Sapling Paper Wallet", wallet.ticker)
+    };
+
+    let mut html = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n",
+                           escape(&title), STYLE);
+
+    // 2 addresses per page
+    for page in wallet.entries.chunks(2) {
+        html.push_str("<div class=\"page\">\n");
+        for entry in page {
+            html.push_str("<div class=\"wallet\">\n");
+            html.push_str(&address_section(entry, &wallet.ticker)?);
+            if watch_only {
+                html.push_str(&vk_section(entry)?);
+            } else {
+                html.push_str(&pk_section(entry, &wallet.ticker)?);
+            }
+            html.push_str("</div>\n");
+        }
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+    Ok(html)
+}
+
+fn title(entry: &WalletEntry, ticker: &str) -> String {
+    match entry {
+        WalletEntry::Transparent(_) => "T Address".to_string(),
+        WalletEntry::Sapling(_)     => format!("{} Address (Sapling)", ticker),
+        WalletEntry::Unified(_)     => format!("{} Unified Address", ticker),
+    }
+}
+
+fn address_section(entry: &WalletEntry, ticker: &str) -> Result<String, Error> {
+    Ok(format!("<div class=\"section\">\n<div class=\"qr\">{}</div>\n<div class=\"text\"><h1>{}</h1><div class=\"key\">{}</div></div>\n</div>\n",
+               qrcode_svg(entry.address())?, escape(&title(entry, ticker)), escape(entry.address())))
+}
+
+fn pk_section(entry: &WalletEntry, ticker: &str) -> Result<String, Error> {
+    let pk = entry.private_key();
+    let heading = if is_encrypted_key(pk) { "Encrypted Private Key (needs passphrase)" } else { "Private Key" };
+
+    let mut text = format!("<h1>{}</h1><div class=\"key\">{}</div>", heading, escape(pk));
+
+    // Unified addresses are too long to print a second time under the private key. Their Sapling key goes there
+    // instead, since the Orchard key can only be read by this tool.
+    match entry {
+        WalletEntry::Unified(e) => text.push_str(&format!("<h2>Sapling Private Key</h2><div class=\"small\">{}</div>", escape(&e.sapling_private_key))),
+        _ => text.push_str(&format!("<h2>{}</h2><div class=\"key\">{}</div>", escape(&title(entry, ticker)), escape(entry.address()))),
+    }
+
+    // If there is a mnemonic, it is the backup, so print the words instead of the (long) hex seed
+    if let Some(seed) = entry.seed() {
+        match &seed.mnemonic {
+            Some(words) => {
+                let numbered: Vec<String> = words.split_whitespace().enumerate().map(|(i, w)| format!("{}.{}", i+1, escape(w))).collect();
+                text.push_str(&format!("<h2>Seed words (BIP-39), Path: {}</h2><div class=\"small\">{}</div>", escape(&seed.path), numbered.join(" ")));
+            },
+            None => {
+                let label = if seed.encrypted.is_some() { "Encrypted HDSeed (needs passphrase)" } else { "HDSeed" };
+                text.push_str(&format!("<div class=\"small\">{}: {}, Path: {}</div>", label, escape(&seed.encoded_seed()), escape(&seed.path)));
+            },
+        }
+    }
+
+    Ok(format!("<div class=\"section\">\n<div class=\"text\">{}</div>\n<div class=\"qr\">{}</div>\n</div>\n", text, qrcode_svg(pk)?))
+}
+
+fn vk_section(entry: &WalletEntry) -> Result<String, Error> {
+    let vk = match entry.viewing_key() {
+        Some(vk) => vk,
+        None     => {
+            // Transparent addresses are public, so the address is all that's needed to watch them
+            return Ok("<div class=\"section\">\n<div class=\"text\"><h1>Viewing Key (Watch-Only)</h1><div class=\"key\">No viewing key. Use the address to monitor the balance.</div></div>\n</div>\n".to_string());
+        }
+    };
+
+    let mut text = format!("<h1>Viewing Key (Watch-Only)</h1><div class=\"key\">{}</div>", escape(vk));
+
+    // The incoming viewing key can only see incoming funds, so it can be handed out to auditors separately
+    if let WalletEntry::Sapling(e) = entry {
+        if let Some(keys) = &e.account_keys {
+            text.push_str(&format!("<h2>Incoming Viewing Key</h2><div class=\"key\">{}</div>", escape(&keys.incoming_viewing_key)));
+        }
+    }
+
+    Ok(format!("<div class=\"section\">\n<div class=\"text\">{}</div>\n<div class=\"qr\">{}</div>\n</div>\n", text, qrcode_svg(vk)?))
+}
+
+/**
+ * Render a QR code as an inline SVG element. The size is set by the CSS, so the codes are all the same size on the
+ * page however long the data is.
+ */
+fn qrcode_svg(data: &str) -> Result<String, Error> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| Error::Encoding(format!("Couldn't generate QR code: {:?}", e)))?;
+    let image = code.render::<svg::Color>()
+        .quiet_zone(true)
+        .module_dimensions(1, 1)
+        .build();
+
+    // Drop the XML declaration, which isn't allowed inside HTML, and add a viewBox so the code scales with the CSS.
+    // With 1 unit per module, the image is the code plus 4 modules of quiet zone on each side.
+    let size = code.width() + 8;
+    match image.find("<svg ") {
+        Some(start) => Ok(image[start..].replacen("<svg ", &format!("<svg viewBox=\"0 0 {} {}\" ", size, size), 1)),
+        None        => Err(Error::Encoding("Couldn't generate QR code SVG".to_string()))
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_html() {
+        use crate::html::wallet_to_html;
+        use crate::entropy::SystemEntropy;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+
+        let w = generate_wallet(&WalletOptions { zcount: 2, tcount: 1, ucount: 1, ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        let html = wallet_to_html(&w, false).unwrap();
+
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<title>ZEC Sapling Paper Wallet</title>"));
+        assert!(!html.contains("<?xml"));
+        // No external resources, so it works offline
+        assert!(!html.contains("src="));
+        assert!(!html.contains("href="));
+
+        // 4 addresses on 2 pages, each with a QR code for the address and the private key
+        assert_eq!(html.matches("<div class=\"page\">").count(), 2);
+        assert_eq!(html.matches("<svg viewBox=").count(), 8);
+        for e in w.entries.iter() {
+            assert!(html.contains(e.address()));
+            assert!(html.contains(e.private_key()));
+        }
+        assert!(html.contains("HDSeed: "));
+        assert_eq!(html.matches("<h2>Sapling Private Key</h2>").count(), 1);
+
+        let html = wallet_to_html(&w, true).unwrap();
+        assert!(html.contains("<title>ZEC Watch-Only Wallet</title>"));
+        for e in w.entries.iter() {
+            assert!(html.contains(e.address()));
+            assert!(!html.contains(e.private_key()));
+        }
+        assert!(!html.contains("HDSeed"));
+        assert!(html.contains("No viewing key"));
+    }
+
+    #[test]
+    fn test_escape() {
+        use crate::html::{wallet_to_html, escape};
+        use crate::wallet::PaperWallet;
+
+        assert_eq!(escape("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+
+        // The ticker comes from the user's coin file, so it is escaped
+        let mut w = PaperWallet::default();
+        w.ticker = "<script>".to_string();
+        let html = wallet_to_html(&w, false).unwrap();
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt; Sapling Paper Wallet"));
+    }
+}
@@ -0,0 +1,342 @@
+//! Passphrase encryption of the private keys on a paper wallet, in the spirit of BIP-38, so a sheet that is
+//! photographed or found can't be spent without the passphrase.
+//!
+//! The encoding:
+//! 1. A 32-byte key and a 12-byte nonce are derived from the passphrase with scrypt (N = 2^log_n, r = 8, p = 1)
+//!    and a random 16-byte salt.
+//! 2. The encoded private key (the "secret-extended-key-...", "secret-orchard-sk-..." or WIF string) is encrypted
+//!    with ChaCha20-Poly1305, with the version and log_n bytes as the associated data. HD seeds are encrypted the
+//!    same way, with a 0xff byte (which can't start a key string), the seed length, the seed and the entropy of the
+//!    seed words (if any) as the plaintext.
+//! 3. The result is bech32m encoded with the "zecencrypted" prefix. The data is the version byte (0), log_n, the
+//!    salt and the ciphertext (which ends with the 16-byte tag).
+//!
+//! Decrypting gives back the original key string (or seed and words), so it can be imported as usual. A wrong
+//! passphrase fails the tag check, instead of giving a different key.
+
+use bech32::{ToBase32, FromBase32, Variant};
+use bip39::Mnemonic;
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use crate::entropy::EntropySource;
+use crate::error::Error;
+use crate::wallet::{PaperWallet, WalletEntry, HDSeed};
+
+pub const ENCRYPTED_KEY_PREFIX: &str = "zecencrypted";
+
+const VERSION: u8 = 0;
+const SALT_LEN: usize = 16;
+/// The first byte of an encrypted seed's plaintext. It's never valid UTF-8, so a seed can't be mistaken for a key.
+const SEED_MARKER: u8 = 0xff;
+
+/// scrypt parameters. log_n = 15 takes about 100ms and 32MB for each key. log_n is stored in the encrypted key, so it
+/// can be raised later without breaking old sheets.
+const SCRYPT_LOG_N: u8 = 15;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+/// Don't let a (possibly tampered) encrypted key make us use more than 1GB of memory
+const MAX_LOG_N: u8 = 20;
+
+/// True if `s` looks like a key encrypted by `encrypt_key`
+pub fn is_encrypted_key(s: &str) -> bool {
+    s.trim().starts_with(&format!("{}1", ENCRYPTED_KEY_PREFIX))
+}
+
+/// True if any of the private keys or HD seeds of the entry are encrypted
+pub fn is_encrypted_entry(entry: &WalletEntry) -> bool {
+    let sapling_key_encrypted = match entry {
+        WalletEntry::Unified(e) => is_encrypted_key(&e.sapling_private_key),
+        _                       => false
+    };
+
+    is_encrypted_key(entry.private_key()) || sapling_key_encrypted || entry.seed().map_or(false, |s| s.encrypted.is_some())
+}
+
+/// True if the wallet was encrypted with `encrypt_wallet`, and has to be decrypted before its keys can be used
+pub fn is_encrypted_wallet(wallet: &PaperWallet) -> bool {
+    wallet.entries.iter().any(is_encrypted_entry)
+}
+
+/// Encrypt an encoded private key with the passphrase. The salt comes from `source`.
+pub fn encrypt_key(key: &str, passphrase: &str, source: &mut dyn EntropySource) -> Result<String, Error> {
+    encrypt_key_with(key, passphrase, source, SCRYPT_LOG_N)
+}
+
+fn encrypt_key_with(key: &str, passphrase: &str, source: &mut dyn EntropySource, log_n: u8) -> Result<String, Error> {
+    if is_encrypted_key(key) {
+        return Err(Error::InvalidInput("The key is already encrypted".to_string()));
+    }
+
+    encrypt_bytes(key.as_bytes(), passphrase, source, log_n)
+}
+
+fn encrypt_bytes(plaintext: &[u8], passphrase: &str, source: &mut dyn EntropySource, log_n: u8) -> Result<String, Error> {
+    if passphrase.is_empty() {
+        return Err(Error::InvalidInput("The passphrase to encrypt the keys with can't be empty".to_string()));
+    }
+
+    let mut salt = [0u8; SALT_LEN];
+    source.fill_bytes(&mut salt)?;
+
+    let header = [VERSION, log_n];
+    let (cipher, nonce) = derive_cipher(passphrase, &salt, log_n)?;
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce), Payload { msg: plaintext, aad: &header })
+        .map_err(|_| Error::Encoding("Couldn't encrypt the key".to_string()))?;
+
+    let mut payload = header.to_vec();
+    payload.extend_from_slice(&salt);
+    payload.extend_from_slice(&ciphertext);
+
+    Ok(bech32::encode(ENCRYPTED_KEY_PREFIX, payload.to_base32(), Variant::Bech32m)?)
+}
+
+/// Decrypt a key encrypted by `encrypt_key`, giving back the encoded private key
+pub fn decrypt_key(encrypted: &str, passphrase: &str) -> Result<String, Error> {
+    let plaintext = decrypt_bytes(encrypted, passphrase)?;
+    if plaintext.first() == Some(&SEED_MARKER) {
+        return Err(Error::InvalidInput("This is an encrypted HD seed, not a key. Decrypt the wallet file to get the seed back".to_string()));
+    }
+
+    String::from_utf8(plaintext).map_err(|_| Error::Encoding("The decrypted key isn't valid UTF-8".to_string()))
+}
+
+fn decrypt_bytes(encrypted: &str, passphrase: &str) -> Result<Vec<u8>, Error> {
+    let (hrp, data, variant) = bech32::decode(encrypted.trim())?;
+    if hrp != ENCRYPTED_KEY_PREFIX || variant != Variant::Bech32m {
+        return Err(Error::Encoding(format!("Not an encrypted key, expected it to start with {}1", ENCRYPTED_KEY_PREFIX)));
+    }
+
+    let payload = Vec::<u8>::from_base32(&data)?;
+    if payload.len() < 2 + SALT_LEN + 16 {
+        return Err(Error::Encoding("Encrypted key is too short".to_string()));
+    }
+    if payload[0] != VERSION {
+        return Err(Error::Encoding(format!("Unknown encrypted key version {}", payload[0])));
+    }
+    if payload[1] > MAX_LOG_N {
+        return Err(Error::Encoding(format!("Encrypted key asks for scrypt with log_n = {}, which is too large", payload[1])));
+    }
+
+    let (header, rest) = payload.split_at(2);
+    let (salt, ciphertext) = rest.split_at(SALT_LEN);
+
+    let (cipher, nonce) = derive_cipher(passphrase, salt, header[1])?;
+    cipher.decrypt(Nonce::from_slice(&nonce), Payload { msg: ciphertext, aad: header })
+        .map_err(|_| Error::InvalidInput("Couldn't decrypt the key. Is the passphrase right?".to_string()))
+}
+
+/// Encrypt an HD seed and its seed words with the passphrase. The words are stored as their BIP-39 entropy, which
+/// keeps the encrypted seed short enough to fit on the sheet.
+pub fn encrypt_seed(seed: &HDSeed, passphrase: &str, source: &mut dyn EntropySource) -> Result<String, Error> {
+    encrypt_seed_with(seed, passphrase, source, SCRYPT_LOG_N)
+}
+
+fn encrypt_seed_with(seed: &HDSeed, passphrase: &str, source: &mut dyn EntropySource, log_n: u8) -> Result<String, Error> {
+    if seed.encrypted.is_some() {
+        return Err(Error::InvalidInput("The seed is already encrypted".to_string()));
+    }
+    if seed.seed.is_empty() || seed.seed.len() > 255 {
+        return Err(Error::InvalidInput(format!("Can't encrypt an HD seed of {} bytes", seed.seed.len())));
+    }
+
+    // marker || seed length || seed || entropy of the seed words
+    let mut plaintext = vec![SEED_MARKER, seed.seed.len() as u8];
+    plaintext.extend_from_slice(&seed.seed);
+    if let Some(words) = &seed.mnemonic {
+        let m = Mnemonic::parse(words.as_str()).map_err(|e| Error::InvalidInput(format!("Invalid seed words: {}", e)))?;
+        plaintext.extend_from_slice(&m.to_entropy());
+    }
+
+    encrypt_bytes(&plaintext, passphrase, source, log_n)
+}
+
+/// Decrypt a seed encrypted by `encrypt_seed`, giving back the seed and the seed words, if there were any
+pub fn decrypt_seed(encrypted: &str, passphrase: &str) -> Result<(Vec<u8>, Option<String>), Error> {
+    let plaintext = decrypt_bytes(encrypted, passphrase)?;
+    let (len, rest) = match plaintext.split_first() {
+        Some((&SEED_MARKER, rest)) => match rest.split_first() {
+            Some((len, rest)) if *len as usize <= rest.len() => (*len as usize, rest),
+            _ => return Err(Error::Encoding("The decrypted seed is too short".to_string()))
+        },
+        _ => return Err(Error::InvalidInput("This is an encrypted key, not an HD seed".to_string()))
+    };
+
+    let (seed, entropy) = rest.split_at(len);
+    let words = if entropy.is_empty() {
+        None
+    } else {
+        let m = Mnemonic::from_entropy(entropy).map_err(|e| Error::Encoding(format!("Invalid seed words: {}", e)))?;
+        Some(m.to_string())
+    };
+
+    Ok((seed.to_vec(), words))
+}
+
+/// Encrypt all the private keys in the wallet, and the HD seeds along with their seed words
+pub fn encrypt_wallet(wallet: &PaperWallet, passphrase: &str, source: &mut dyn EntropySource) -> Result<PaperWallet, Error> {
+    encrypt_wallet_with(wallet, passphrase, source, SCRYPT_LOG_N)
+}
+
+fn encrypt_wallet_with(wallet: &PaperWallet, passphrase: &str, source: &mut dyn EntropySource, log_n: u8) -> Result<PaperWallet, Error> {
+    let mut ans = wallet.clone();
+
+    // The addresses of an HD wallet share one seed, so it's only encrypted (and run through scrypt) once
+    let mut encrypted_seeds: Vec<(Vec<u8>, Option<String>, String)> = vec![];
+
+    for entry in ans.entries.iter_mut() {
+        for private_key in private_keys(entry) {
+            *private_key = encrypt_key_with(private_key, passphrase, source, log_n)?;
+        }
+
+        if let Some(seed) = seed_mut(entry) {
+            let encrypted = match encrypted_seeds.iter().find(|(s, m, _)| *s == seed.seed && *m == seed.mnemonic) {
+                Some((_, _, encrypted)) => encrypted.clone(),
+                None                    => {
+                    let encrypted = encrypt_seed_with(seed, passphrase, source, log_n)?;
+                    encrypted_seeds.push((seed.seed.clone(), seed.mnemonic.clone(), encrypted.clone()));
+                    encrypted
+                }
+            };
+
+            *seed = HDSeed { seed: vec![], path: seed.path.clone(), mnemonic: None, encrypted: Some(encrypted) };
+        }
+    }
+
+    Ok(ans)
+}
+
+/// Decrypt all the encrypted private keys and seeds in the wallet. Keys that aren't encrypted are left as they are.
+pub fn decrypt_wallet(wallet: &PaperWallet, passphrase: &str) -> Result<PaperWallet, Error> {
+    let mut ans = wallet.clone();
+    for entry in ans.entries.iter_mut() {
+        for private_key in private_keys(entry) {
+            if is_encrypted_key(private_key) {
+                *private_key = decrypt_key(private_key, passphrase)?;
+            }
+        }
+
+        if let Some(seed) = seed_mut(entry) {
+            if let Some(encrypted) = seed.encrypted.take() {
+                let (bytes, words) = decrypt_seed(&encrypted, passphrase)?;
+                seed.seed = bytes;
+                seed.mnemonic = words;
+            }
+        }
+    }
+
+    Ok(ans)
+}
+
+/// The private keys of an entry. Unified addresses have two, the Orchard and the Sapling key.
+fn private_keys(entry: &mut WalletEntry) -> Vec<&mut String> {
+    match entry {
+        WalletEntry::Sapling(e)     => vec![&mut e.private_key],
+        WalletEntry::Transparent(e) => vec![&mut e.private_key],
+        WalletEntry::Unified(e)     => vec![&mut e.private_key, &mut e.sapling_private_key],
+    }
+}
+
+fn seed_mut(entry: &mut WalletEntry) -> Option<&mut HDSeed> {
+    match entry {
+        WalletEntry::Sapling(e)     => e.seed.as_mut(),
+        WalletEntry::Transparent(e) => e.seed.as_mut(),
+        WalletEntry::Unified(e)     => e.seed.as_mut(),
+    }
+}
+
+fn derive_cipher(passphrase: &str, salt: &[u8], log_n: u8) -> Result<(ChaCha20Poly1305, [u8; 12]), Error> {
+    let params = scrypt::Params::new(log_n, SCRYPT_R, SCRYPT_P, 44)
+        .map_err(|e| Error::InvalidInput(format!("Invalid scrypt parameters: {}", e)))?;
+
+    let mut derived = [0u8; 44];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut derived)
+        .map_err(|e| Error::InvalidInput(format!("Couldn't derive the encryption key: {}", e)))?;
+
+    let mut nonce = [0u8; 12];
+    nonce.copy_from_slice(&derived[32..]);
+    Ok((ChaCha20Poly1305::new(Key::from_slice(&derived[..32])), nonce))
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_encrypt_key() {
+        use crate::keycrypt::{encrypt_key, decrypt_key, is_encrypted_key};
+        use crate::entropy::SystemEntropy;
+
+        let pk = "secret-extended-key-main1qv8qrtyqqqqpqqpuxs6v7mv9n7z7r6yqj2z4pt0sxtyduvsnhg4pqn5jq9z6wmuxfrtdvcxh9hkl7zvavr0z4y9yr6u0ypn0ffz86huqetgxmwqqvmyvnmu4wd0ajq0hqnxkcnvzcndvt5xqfyaewewr5v8ycv7rsdtsvn43qjn49ccx30jvv4t0mwqmp8yr4wcxs6e9j0kk2ykhhdv0aayg8fnkz98fpj6dhzyrxgw3r2sgtmnv9g3uk0zhp4hwucphxpw3ahvmakw8l9xs0mz6yhd4u";
+        let encrypted = encrypt_key(pk, "correct horse", &mut SystemEntropy).unwrap();
+        assert!(is_encrypted_key(&encrypted));
+        assert!(!is_encrypted_key(pk));
+        assert_eq!(decrypt_key(&encrypted, "correct horse").unwrap(), pk);
+
+        // The salt is random, so the same key encrypts differently every time
+        assert_ne!(encrypt_key(pk, "correct horse", &mut SystemEntropy).unwrap(), encrypted);
+
+        decrypt_key(&encrypted, "battery staple").expect_err("Wrong passphrase");
+        decrypt_key(pk, "correct horse").expect_err("Not encrypted");
+        encrypt_key(pk, "", &mut SystemEntropy).expect_err("Empty passphrase");
+        encrypt_key(&encrypted, "correct horse", &mut SystemEntropy).expect_err("Already encrypted");
+
+        // A mistyped character is caught by the bech32 checksum
+        let mut typo = encrypted.clone().into_bytes();
+        typo[30] = if typo[30] == b'q' { b'p' } else { b'q' };
+        decrypt_key(&String::from_utf8(typo).unwrap(), "correct horse").expect_err("Typo");
+    }
+
+    #[test]
+    fn test_encrypt_wallet() {
+        use crate::keycrypt::{encrypt_wallet_with, decrypt_wallet, decrypt_key, decrypt_seed, is_encrypted_key};
+        use crate::entropy::SystemEntropy;
+        use crate::paper::{generate_wallet, restore_wallet, WalletOptions, Network};
+        use crate::wallet::{PaperWallet, WalletEntry};
+
+        let w = generate_wallet(&WalletOptions { tcount: 1, ucount: 1, mnemonic: Some("words".to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+
+        // A small log_n to keep the test fast
+        let encrypted = encrypt_wallet_with(&w, "pass", &mut SystemEntropy, 10).unwrap();
+        assert_eq!(encrypted.len(), 3);
+        for (e, orig) in encrypted.entries.iter().zip(w.entries.iter()) {
+            assert!(is_encrypted_key(e.private_key()));
+            if let WalletEntry::Unified(u) = e {
+                assert!(is_encrypted_key(&u.sapling_private_key));
+            }
+            assert_eq!(e.address(), orig.address());
+            assert_eq!(e.viewing_key(), orig.viewing_key());
+
+            // The seed and the words are encrypted too, and the seed is only encrypted once
+            let seed = e.seed().unwrap();
+            assert!(seed.seed.is_empty() && seed.mnemonic.is_none());
+            assert!(is_encrypted_key(seed.encrypted.as_ref().unwrap()));
+            assert_eq!(seed.encrypted, encrypted.entries[0].seed().unwrap().encrypted);
+            assert_eq!(seed.path, orig.seed().unwrap().path);
+        }
+
+        // The encrypted wallet survives a JSON round trip, and decrypts back to the original
+        let encrypted = PaperWallet::from_json(&encrypted.to_json_string()).unwrap();
+        assert!(!encrypted.to_json_string().contains(w.entries[0].seed().unwrap().mnemonic.as_ref().unwrap().as_str()));
+        let decrypted = decrypt_wallet(&encrypted, "pass").unwrap();
+        assert_eq!(decrypted, w);
+
+        // The decrypted words (with the BIP-39 passphrase) restore the whole wallet
+        let words = decrypted.entries[0].seed().unwrap().mnemonic.clone().unwrap();
+        let restored = restore_wallet(&words, &Network::Main, 0..1, 1, 1, "words").unwrap();
+        assert_eq!(restored.entries, w.entries);
+
+        decrypt_wallet(&encrypted, "wrong").expect_err("Wrong passphrase");
+
+        // Seeds and keys can't be mixed up
+        let encrypted_seed = encrypted.entries[0].seed().unwrap().encrypted.clone().unwrap();
+        decrypt_key(&encrypted_seed, "pass").expect_err("Not a key");
+        decrypt_seed(encrypted.entries[0].private_key(), "pass").expect_err("Not a seed");
+
+        // A raw HD seed round trips too
+        let w = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap();
+        let encrypted = encrypt_wallet_with(&w, "pass", &mut SystemEntropy, 10).unwrap();
+        assert!(is_encrypted_key(&encrypted.entries[1].seed().unwrap().encrypted.clone().unwrap()));
+        assert_eq!(decrypt_wallet(&encrypted, "pass").unwrap(), w);
+
+        // Wallets that aren't encrypted don't change
+        assert_eq!(decrypt_wallet(&w, "pass").unwrap(), w);
+    }
+}
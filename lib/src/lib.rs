@@ -1,3 +1,18 @@
+pub mod bip32;
+pub mod checkpoint;
+pub mod dice;
+pub mod entropy;
+pub mod error;
+mod gf256;
+pub mod html;
+pub mod keycrypt;
 pub mod paper;
+pub mod recipient;
+pub mod shamir;
+pub mod vanity;
+pub mod verify;
+pub mod wallet;
 #[cfg(feature = "printpdf")]
-pub mod pdf;
\ No newline at end of file
+pub mod pdf;
+
+pub use crate::error::Error;
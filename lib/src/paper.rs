@@ -1,18 +1,19 @@
-use std::thread;
-use hex;
-use base58::{ToBase58};
-use bech32::{Bech32, u5, ToBase32};
-use rand::{Rng, ChaChaRng, FromEntropy, SeedableRng};
-use json::{array, object};
+use base58::{ToBase58, FromBase58};
+use bech32::{u5, ToBase32, FromBase32, Variant};
+use rand::{Rng, ChaChaRng, SeedableRng};
 use sha2::{Sha256, Digest};
-use std::io;
-use std::io::Write;
-use std::sync::mpsc;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::Arc;
-use std::panic;
-use std::time::{SystemTime};
-use zcash_primitives::zip32::{DiversifierIndex, DiversifierKey, ChildIndex, ExtendedSpendingKey, ExtendedFullViewingKey};
+use json::{object, JsonValue};
+use std::ops::Range;
+use bip39::Mnemonic;
+use zcash_primitives::zip32::{DiversifierIndex, ChildIndex, ExtendedSpendingKey, ExtendedFullViewingKey};
+use zcash_primitives::primitives::PaymentAddress;
+use zcash_primitives::jubjub::{JubjubEngine, ToUniform, fs::Fs};
+use zcash_primitives::keys::{prf_expand, prf_expand_vec};
+use zcash_address::unified::{self, Encoding};
+use crate::wallet::{PaperWallet, WalletEntry, SaplingEntry, SaplingAccountKeys, TransparentEntry, UnifiedEntry, HDSeed};
+use crate::error::Error;
+use crate::entropy::{EntropySource, MIN_USER_ENTROPY};
+use crate::bip32::{ExtendedPrivKey, HARDENED};
 
 /// A trait for converting a [u8] to base58 encoded string.
 pub trait ToBase58Check {
@@ -43,37 +44,206 @@ pub fn double_sha256(payload: &[u8]) -> Vec<u8> {
     h2.to_vec()
 }
 
+/// Decode a base58check encoded string, and verify the checksum. The returned payload still has the version prefix
+pub fn from_base58check(s: &str) -> Result<Vec<u8>, Error> {
+    let data = s.from_base58().map_err(|e| Error::Encoding(format!("Invalid base58 string: {:?}", e)))?;
+    if data.len() < 4 {
+        return Err(Error::Encoding("Base58 string is too short".to_string()));
+    }
+
+    let (payload, checksum) = data.split_at(data.len() - 4);
+    if double_sha256(payload)[..4] != *checksum {
+        return Err(Error::Encoding("Invalid base58 checksum".to_string()));
+    }
+
+    Ok(payload.to_vec())
+}
+
+/// The Zcash network that addresses and keys are generated for
+#[derive(Clone, Debug, PartialEq)]
+pub enum Network {
+    Main,
+    Test,
+    /// A local regtest node. Regtest uses the testnet coin type and t-address versions, but has its own Bech32 HRPs.
+    Regtest,
+    /// A Zcash-derived chain that uses Sapling with its own prefixes and coin type. See `CoinParams::from_json`.
+    Custom(Box<CoinParams>),
+}
+
+impl Network {
+    /// All the Zcash networks, in the order they are tried when decoding keys
+    pub fn all() -> Vec<Network> {
+        vec![Network::Main, Network::Test, Network::Regtest]
+    }
+
+    /// The networks to try when decoding a key: the `hint` first (which may be a custom coin), then the Zcash networks
+    pub fn candidates(hint: Option<&Network>) -> Vec<Network> {
+        let mut networks: Vec<Network> = hint.into_iter().cloned().collect();
+        networks.extend(Network::all().into_iter().filter(|n| Some(n) != hint));
+        networks
+    }
+
+    /// The name used on the command line and in checkpoint files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Network::Main      => "main",
+            Network::Test      => "test",
+            Network::Regtest   => "regtest",
+            Network::Custom(_) => "custom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Network, Error> {
+        Network::all().into_iter()
+            .find(|n| n.name() == name)
+            .ok_or_else(|| Error::InvalidInput(format!("Unknown network '{}', expected main, test or regtest", name)))
+    }
+
+    /// The network for encoding unified addresses and viewing keys. ZIP-316 only defines the Zcash networks.
+    pub(crate) fn address_network(&self) -> Result<zcash_address::Network, Error> {
+        match self {
+            Network::Main      => Ok(zcash_address::Network::Main),
+            Network::Test      => Ok(zcash_address::Network::Test),
+            Network::Regtest   => Ok(zcash_address::Network::Regtest),
+            Network::Custom(p) => Err(Error::InvalidInput(format!("Unified addresses aren't supported for {}", p.ticker))),
+        }
+    }
+}
+
 /// Parameters used to generate addresses and private keys. Look in chainparams.cpp (in zcashd/src)
 /// to get these values. 
 /// Usually these will be different for testnet and for mainnet.
+#[derive(Clone, Debug, PartialEq)]
 pub struct CoinParams {
+    /// The coin's name on the paper wallet, for eg. "ZEC Address (Sapling)"
+    pub ticker          : String,
     pub taddress_version: [u8; 2],
     pub tsecret_prefix  : [u8; 1],
     pub zaddress_prefix : String,
     pub zsecret_prefix  : String,
     pub zviewkey_prefix : String,
+    pub zivk_prefix     : String,
+    /// The HRP of the Bech32m encoded Orchard spending keys. There is no standard string encoding for a bare Orchard
+    /// spending key, so these keys (and the "secret-orchard-sk-*" prefixes) are specific to this tool, and other
+    /// wallets can't import them. To spend from a unified address in another wallet, import the seed (or the Sapling
+    /// key that is printed along with the Orchard key, for the funds sent to the Sapling receiver).
+    pub osecret_prefix  : String,
     pub cointype        : u32,
 }
 
-pub fn params(is_testnet: bool) -> CoinParams {
-    if is_testnet {
-        CoinParams {
-            taddress_version : [0x1D, 0x25],
-            tsecret_prefix   : [0xEF],
-            zaddress_prefix  : "ztestsapling".to_string(),
-            zsecret_prefix   : "secret-extended-key-test".to_string(),
-            zviewkey_prefix  : "zxviewtestsapling".to_string(),
-            cointype         : 1
+impl CoinParams {
+    /// Read the parameters of a custom coin from JSON, like
+    /// ```json
+    /// {
+    ///     "ticker"          : "ABC",
+    ///     "taddress_version": "1cb8",
+    ///     "tsecret_prefix"  : "80",
+    ///     "zaddress_prefix" : "abcs",
+    ///     "zsecret_prefix"  : "secret-extended-key-abc",
+    ///     "zviewkey_prefix" : "zxviewsabc",
+    ///     "zivk_prefix"     : "zivksabc",
+    ///     "cointype"        : 1234
+    /// }
+    /// ```
+    /// The version bytes are hex encoded. `osecret_prefix` is optional, since unified addresses can't be generated
+    /// for custom coins.
+    pub fn from_json(s: &str) -> Result<CoinParams, Error> {
+        let j = json::parse(s)?;
+
+        let field = |name: &str| match j[name].as_str() {
+            Some(s) => Ok(s.to_string()),
+            None    => Err(Error::Encoding(format!("Missing \"{}\" in the coin parameters", name)))
+        };
+        let bytes = |name: &str, len: usize| -> Result<Vec<u8>, Error> {
+            let b = hex::decode(field(name)?).map_err(|e| Error::Encoding(format!("Invalid \"{}\": {}", name, e)))?;
+            if b.len() != len {
+                return Err(Error::Encoding(format!("\"{}\" should be {} bytes, got {}", name, len, b.len())));
+            }
+            Ok(b)
+        };
+        let hrp = |name: &str| -> Result<String, Error> {
+            let h = field(name)?;
+            if h.is_empty() || !h.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-') {
+                return Err(Error::Encoding(format!("\"{}\" should be lowercase letters, digits and '-', got '{}'", name, h)));
+            }
+            Ok(h)
+        };
+
+        let cointype = j["cointype"].as_u32()
+            .ok_or_else(|| Error::Encoding("Missing \"cointype\" in the coin parameters".to_string()))?;
+        if cointype >= (1 << 31) {
+            return Err(Error::Encoding(format!("Coin type has to be less than {}", 1u32 << 31)));
         }
-    } else {
-        CoinParams {
+
+        let mut taddress_version = [0u8; 2];
+        taddress_version.copy_from_slice(&bytes("taddress_version", 2)?);
+        let mut tsecret_prefix = [0u8; 1];
+        tsecret_prefix.copy_from_slice(&bytes("tsecret_prefix", 1)?);
+
+        Ok(CoinParams {
+            ticker          : field("ticker")?,
+            taddress_version,
+            tsecret_prefix,
+            zaddress_prefix : hrp("zaddress_prefix")?,
+            zsecret_prefix  : hrp("zsecret_prefix")?,
+            zviewkey_prefix : hrp("zviewkey_prefix")?,
+            zivk_prefix     : hrp("zivk_prefix")?,
+            osecret_prefix  : j["osecret_prefix"].as_str().unwrap_or("").to_string(),
+            cointype,
+        })
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        object!{
+            "ticker"           => self.ticker.clone(),
+            "taddress_version" => hex::encode(self.taddress_version),
+            "tsecret_prefix"   => hex::encode(self.tsecret_prefix),
+            "zaddress_prefix"  => self.zaddress_prefix.clone(),
+            "zsecret_prefix"   => self.zsecret_prefix.clone(),
+            "zviewkey_prefix"  => self.zviewkey_prefix.clone(),
+            "zivk_prefix"      => self.zivk_prefix.clone(),
+            "osecret_prefix"   => self.osecret_prefix.clone(),
+            "cointype"         => self.cointype
+        }
+    }
+}
+
+pub fn params(network: &Network) -> CoinParams {
+    match network {
+        Network::Main => CoinParams {
+            ticker           : "ZEC".to_string(),
             taddress_version : [0x1C, 0xB8],
             tsecret_prefix   : [0x80],
             zaddress_prefix  : "zs".to_string(),
             zsecret_prefix   : "secret-extended-key-main".to_string(),
             zviewkey_prefix  : "zxviews".to_string(),
+            zivk_prefix      : "zivks".to_string(),
+            osecret_prefix   : "secret-orchard-sk-main".to_string(),
             cointype         : 133
-        }
+        },
+        Network::Test => CoinParams {
+            ticker           : "ZEC".to_string(),
+            taddress_version : [0x1D, 0x25],
+            tsecret_prefix   : [0xEF],
+            zaddress_prefix  : "ztestsapling".to_string(),
+            zsecret_prefix   : "secret-extended-key-test".to_string(),
+            zviewkey_prefix  : "zxviewtestsapling".to_string(),
+            zivk_prefix      : "zivktestsapling".to_string(),
+            osecret_prefix   : "secret-orchard-sk-test".to_string(),
+            cointype         : 1
+        },
+        Network::Regtest => CoinParams {
+            ticker           : "ZEC".to_string(),
+            taddress_version : [0x1D, 0x25],
+            tsecret_prefix   : [0xEF],
+            zaddress_prefix  : "zregtestsapling".to_string(),
+            zsecret_prefix   : "secret-extended-key-regtest".to_string(),
+            zviewkey_prefix  : "zxviewregtestsapling".to_string(),
+            zivk_prefix      : "zivkregtestsapling".to_string(),
+            osecret_prefix   : "secret-orchard-sk-regtest".to_string(),
+            cointype         : 1
+        },
+        Network::Custom(p) => (**p).clone(),
     }
 }
 
@@ -89,233 +259,246 @@ pub fn increment(s: &mut [u8; 32]) -> Result<(), ()> {
     Err(())
 }
 
-// Turn the prefix into Vec<u5>, so it can be matched directly without any encoding overhead.
-fn get_bech32_for_prefix(prefix: String) -> Result<Vec<u5>, String> {
-    // Reverse character set. Maps ASCII byte -> CHARSET index on [0,31]
-    const CHARSET_REV: [i8; 128] = [
-        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
-        15, -1, 10, 17, 21, 20, 26, 30,  7,  5, -1, -1, -1, -1, -1, -1,
-        -1, 29, -1, 24, 13, 25,  9,  8, 23, -1, 18, 22, 31, 27, 19, -1,
-        1,  0,  3, 16, 11, 28, 12, 14,  6,  4,  2, -1, -1, -1, -1, -1,
-        -1, 29, -1, 24, 13, 25,  9,  8, 23, -1, 18, 22, 31, 27, 19, -1,
-        1,  0,  3, 16, 11, 28, 12, 14,  6,  4,  2, -1, -1, -1, -1, -1
-    ];
-
-    let mut ans = Vec::new();
-    for c in prefix.chars() {
-        if CHARSET_REV[c as usize] == -1 {
-            return Err(format!("Invalid character in prefix: '{}'", c));
-        }
-        ans.push(u5::try_from_u8(CHARSET_REV[c as usize] as u8).expect("Should be able to convert to u5"));
-    }
+pub(crate) fn encode_address(spk: &ExtendedSpendingKey, network: &Network) -> Result<String, Error> {
+    let (_d, addr) = spk.default_address()
+        .map_err(|_| Error::Derivation("Couldn't find a valid diversifier for the default address".to_string()))?;
 
-    return Ok(ans);
+    encode_payment_address(&addr, network)
 }
 
-fn encode_address(spk: &ExtendedSpendingKey, is_testnet: bool) -> String {
-    let (_d, addr) = spk.default_address().expect("Cannot get result");
-
+pub(crate) fn encode_payment_address<E: JubjubEngine>(addr: &PaymentAddress<E>, network: &Network) -> Result<String, Error> {
     // Address is encoded as a bech32 string
-    let mut v = vec![0; 43];
-
-    v.get_mut(..11).unwrap().copy_from_slice(&addr.diversifier.0);
-    addr.pk_d.write(v.get_mut(11..).unwrap()).expect("Cannot write!");
-    let checked_data: Vec<u5> = v.to_base32();
-    let encoded : String = Bech32::new(params(is_testnet).zaddress_prefix.into(), checked_data).expect("bech32 failed").to_string();
+    let checked_data: Vec<u5> = payment_address_bytes(addr)?.to_base32();
+    let encoded : String = bech32::encode(&params(network).zaddress_prefix, checked_data, Variant::Bech32)?;
     
-    return encoded;
+    return Ok(encoded);
 }
 
-fn encode_privatekey(spk: &ExtendedSpendingKey, is_testnet: bool) -> String {
+/// The raw 43 byte encoding of a Sapling address (diversifier || pk_d)
+fn payment_address_bytes<E: JubjubEngine>(addr: &PaymentAddress<E>) -> Result<[u8; 43], Error> {
+    let mut v = [0u8; 43];
+
+    v[..11].copy_from_slice(&addr.diversifier.0);
+    addr.pk_d.write(&mut v[11..])?;
+
+    return Ok(v);
+}
+
+pub(crate) fn encode_privatekey(spk: &ExtendedSpendingKey, network: &Network) -> Result<String, Error> {
     // Private Key is encoded as bech32 string
     let mut vp = Vec::new();
-    spk.write(&mut vp).expect("Can't write private key");
+    spk.write(&mut vp)?;
     let c_d: Vec<u5> = vp.to_base32();
-    let encoded_pk = Bech32::new(params(is_testnet).zsecret_prefix.into(), c_d).expect("bech32 failed").to_string();
+    let encoded_pk = bech32::encode(&params(network).zsecret_prefix, c_d, Variant::Bech32)?;
 
-    return encoded_pk;
+    return Ok(encoded_pk);
 }
 
-/// A single thread that grinds through the Diversifiers to find the default key that matches the prefix
-pub fn vanity_thread(is_testnet: bool, entropy: &[u8], prefix: String, tx: mpsc::Sender<String>, please_stop: Arc<AtomicBool>) {
-    
-    let mut seed: [u8; 32] = [0; 32];
-    seed.copy_from_slice(&entropy[0..32]);
-
-    let di = DiversifierIndex::new();
-    let vanity_bytes = get_bech32_for_prefix(prefix).expect("Bad char in prefix");
+/// Decode a bech32 string whose HRP is `hrp(params(network))` for one of the `Network::candidates(hint)`. Returns the 
+/// data and the network
+fn decode_bech32<F>(s: &str, hint: Option<&Network>, hrp: F, variant: Variant) -> Result<(Vec<u8>, Network), Error>
+    where F: Fn(CoinParams) -> String
+{
+    let (found, data, v) = bech32::decode(s.trim())?;
+    if v != variant {
+        return Err(Error::Encoding(format!("Expected {:?} encoding, found {:?}", variant, v)));
+    }
 
-    let master_spk = ExtendedSpendingKey::from_path(&ExtendedSpendingKey::master(&seed),
-                            &[ChildIndex::Hardened(32), ChildIndex::Hardened(params(is_testnet).cointype), ChildIndex::Hardened(0)]);
+    let candidates = Network::candidates(hint);
+    let network = match candidates.iter().find(|n| hrp(params(n)) == found) {
+        Some(n) => n.clone(),
+        None    => {
+            let expected = candidates.iter().map(|n| format!("'{}'", hrp(params(n)))).collect::<Vec<_>>().join(", ");
+            return Err(Error::Encoding(format!("Unexpected prefix '{}', expected one of {}", found, expected)));
+        }
+    };
 
-    let mut spkv = vec![];
-    master_spk.write(&mut spkv).unwrap();
+    Ok((Vec::<u8>::from_base32(&data)?, network))
+}
 
-    let mut i: u32 = 0;
-    loop {
-        if increment(&mut seed).is_err() {
-            return;
-        }
+/// Decode a "secret-extended-key-main..." (or testnet/regtest) Sapling spending key. Returns the key and its network.
+/// Keys of a custom coin are only recognized if its network is passed as the `hint`.
+pub fn decode_privatekey(s: &str, hint: Option<&Network>) -> Result<(ExtendedSpendingKey, Network), Error> {
+    let (data, network) = decode_bech32(s, hint, |p| p.zsecret_prefix, Variant::Bech32)?;
+    let spk = ExtendedSpendingKey::read(&data[..])
+        .map_err(|e| Error::Encoding(format!("Invalid spending key: {}", e)))?;
 
-        let dk = DiversifierKey::master(&seed);
-        let (_ndk, nd) = dk.diversifier(di).unwrap();
+    Ok((spk, network))
+}
 
-        // test for nd
-        let mut isequal = true;
-        for i in 0..vanity_bytes.len() {
-            if vanity_bytes[i] != nd.0.to_base32()[i] {
-                isequal = false;
-                break;
-            }
-        }
+/// Decode a WIF encoded transparent private key. Returns the key and its network. Regtest uses the same version byte 
+/// as testnet, so regtest keys are returned as testnet keys (which have the same addresses and HD paths), unless 
+/// regtest is the `hint`. Keys of a custom coin are only recognized if its network is the `hint`.
+pub fn decode_taddr_privatekey(s: &str, hint: Option<&Network>) -> Result<(secp256k1::SecretKey, Network), Error> {
+    let payload = from_base58check(s.trim())?;
 
-        if isequal { 
-            let len = spkv.len();
-            spkv[(len-32)..len].copy_from_slice(&dk.0[0..32]);
-            let spk = ExtendedSpendingKey::read(&spkv[..]).unwrap();
+    // version || secret key || 0x01 for compressed keys
+    if payload.len() != 34 || payload[33] != 0x01 {
+        return Err(Error::Encoding("Not a compressed WIF private key".to_string()));
+    }
 
-            
-            let encoded = encode_address(&spk, is_testnet);
-            let encoded_pk = encode_privatekey(&spk, is_testnet);
-            
-            let wallet = array!{object!{
-                "num"           => 0,
-                "address"       => encoded,
-                "private_key"   => encoded_pk,
-                "type"          => "zaddr"}};
-            
-            tx.send(json::stringify_pretty(wallet, 2)).unwrap();
-            return;
-        }
+    let network = Network::candidates(hint).into_iter()
+        .find(|n| params(n).tsecret_prefix[0] == payload[0])
+        .ok_or_else(|| Error::Encoding(format!("Unexpected WIF version byte {:#04x}", payload[0])))?;
 
-        i = i + 1;
-        if i%5000 == 0 {
-            if please_stop.load(Ordering::Relaxed) {
-                return;
-            }
-            tx.send("Processed:5000".to_string()).unwrap();
-        }
+    let sk = secp256k1::SecretKey::parse_slice(&payload[1..33])
+        .map_err(|e| Error::Encoding(format!("Invalid secret key: {:?}", e)))?;
 
-        if i == 0 { return; }
-    }
+    Ok((sk, network))
 }
 
-fn pretty_duration(secs: f64) -> (String, String) {
-    let mut expected_dur  = "sec";
-    let mut expected_time = secs;
-
-    if expected_time > 60.0 {
-        expected_time /= 60.0;
-        expected_dur = "min";
-    }
-    if expected_time > 60.0 {
-        expected_time /= 60.0;
-        expected_dur = "hours";
-    }
-    if expected_time > 24.0 {
-        expected_time /= 24.0;
-        expected_dur = "days";
-    }
-    if expected_time > 30.0 {
-        expected_time /= 30.0;
-        expected_dur = "months";
-    }
-    if expected_time > 12.0 {
-        expected_time /= 12.0;
-        expected_dur = "years";
+/// Decode a Bech32m encoded Orchard spending key. Returns the key and its network.
+pub fn decode_orchard_privatekey(s: &str, hint: Option<&Network>) -> Result<(orchard::keys::SpendingKey, Network), Error> {
+    let (data, network) = decode_bech32(s, hint, |p| p.osecret_prefix, Variant::Bech32m)?;
+    if data.len() != 32 {
+        return Err(Error::Encoding(format!("Orchard spending key should be 32 bytes, got {}", data.len())));
     }
 
-    return (format!("{:.*}", 0, expected_time), expected_dur.to_string());
-}
+    let mut bytes = [0u8; 32];
+    bytes.copy_from_slice(&data);
+    let sk: Option<orchard::keys::SpendingKey> = orchard::keys::SpendingKey::from_bytes(bytes).into();
 
-/// Generate a vanity address with the given prefix.
-pub fn generate_vanity_wallet(is_testnet: bool, num_threads: u32, prefix: String) -> Result<String, String> {
-    // Test the prefix first
-    match get_bech32_for_prefix(prefix.clone()) {
-        Ok(_)  => (),
-        Err(e) => return Err(format!("{}. Note that ['b', 'i', 'o', '1'] are not allowed in addresses.", e))
-    };
+    sk.map(|sk| (sk, network))
+      .ok_or_else(|| Error::Encoding("Invalid Orchard spending key".to_string()))
+}
 
-    // Get 32 bytes of system entropy
-    let mut system_rng = ChaChaRng::from_entropy();    
-    
-    let (tx, rx) = mpsc::channel();
-    let please_stop = Arc::new(AtomicBool::new(false));
+/// Which addresses `generate_wallet` generates, and how their seeds are made
+#[derive(Clone, Debug, PartialEq)]
+pub struct WalletOptions {
+    pub network : Network,
+    /// Use a new seed for every address, instead of deriving them all from one HD seed
+    pub nohd    : bool,
+    /// Number of Sapling addresses
+    pub zcount  : u32,
+    /// Number of transparent addresses
+    pub tcount  : u32,
+    /// Number of unified addresses
+    pub ucount  : u32,
+    /// If `Some(passphrase)`, the HD seed is derived from a new 24-word BIP-39 mnemonic and the (possibly empty)
+    /// passphrase, and the words are included in the wallet. Otherwise, a raw 32-byte seed is used.
+    pub mnemonic: Option<String>,
+}
 
-    let mut handles = Vec::new();
+impl WalletOptions {
+    /// A single Sapling address from a raw HD seed
+    pub fn new(network: Network) -> WalletOptions {
+        WalletOptions {
+            network,
+            nohd    : false,
+            zcount  : 1,
+            tcount  : 0,
+            ucount  : 0,
+            mnemonic: None,
+        }
+    }
+}
 
-    for _i in 0..num_threads {
-        let testnet_local = is_testnet.clone();
-        let prefix_local = prefix.clone();
-        let tx_local = mpsc::Sender::clone(&tx);
-        let ps_local = please_stop.clone();
-    
-        let mut entropy: [u8; 32] = [0; 32];
-        system_rng.fill(&mut entropy);
-    
-        let handle = thread::spawn(move || {
-            vanity_thread(testnet_local, &entropy, prefix_local, tx_local, ps_local);
+/// Generate the addresses and private keys in the `options`.
+/// 
+/// The randomness comes from `source`, mixed with the `user_entropy`. If the source fails, the wallet is only generated
+/// if there are at least `MIN_USER_ENTROPY` bytes of user entropy.
+pub fn generate_wallet(options: &WalletOptions, source: &mut dyn EntropySource, user_entropy: &[u8]) -> Result<PaperWallet, Error> {
+    let (network, zcount, tcount, ucount) = (&options.network, options.zcount, options.tcount, options.ucount);
+    let mnemonic = options.mnemonic.as_ref().map(|p| p.as_str());
+    let mut rng = seeded_rng(source, user_entropy)?;
+
+    if !options.nohd {
+        // Allow HD addresses, so use only 1 seed        
+        let (seed, words) = new_seed(&mut rng, mnemonic)?;
+        
+        return gen_addresses_with_seed(network, zcount, tcount, ucount, |i| Ok((seed.clone(), words.clone(), i)));
+    } else {
+        // Not using HD addresses, so derive a new seed every time    
+        return gen_addresses_with_seed(network, zcount, tcount, ucount, |_| {            
+            let (seed, words) = new_seed(&mut rng, mnemonic)?;
+            
+            return Ok((seed, words, 0));
         });
-        handles.push(handle);
-    }
-    
-    let mut processed: u64   = 0;
-    let now = SystemTime::now();
+    }    
+}
 
-    let wallet: String;
+/// Generate a single Sapling account (m/32'/cointype'/0') and `count` diversified addresses for it. All the addresses
+/// share the same spending key and viewing key, but can't be linked to each other by anyone who doesn't have the 
+/// viewing key. Each address is returned with the diversifier index it was derived at, so it can be re-derived 
+/// by a full node or wallet that imports the key.
+pub fn generate_diversified_wallet(network: &Network, count: u32, source: &mut dyn EntropySource, user_entropy: &[u8], mnemonic: Option<&str>) -> Result<PaperWallet, Error> {
+    if count == 0 {
+        return Err(Error::InvalidInput("Need at least 1 diversified address".to_string()));
+    }
 
-    // Calculate the estimated time
-    let expected_combinations = (32 as f64).powf(prefix.len() as f64);
+    let mut rng = seeded_rng(source, user_entropy)?;
+    let (seed, words) = new_seed(&mut rng, mnemonic)?;
+
+    let (spk, mut path) = get_zspendingkey(network, &seed, 0);
+    path.mnemonic = words;
+
+    let encoded_pk = encode_privatekey(&spk, network)?;
+    let encoded_vk = encode_viewingkey(&spk, network)?;
+    let account_keys = encode_account_keys(&spk, network)?;
+    let efvk = ExtendedFullViewingKey::from(&spk);
+
+    let mut ans = PaperWallet::default();
+    ans.ticker = params(network).ticker;
+
+    // Not every diversifier index produces a valid address, so start each search right after the last one found.
+    let mut di = DiversifierIndex::new();
+    for i in 0..count {
+        let (found_di, addr) = efvk.address(di)
+            .map_err(|_| Error::Derivation("Ran out of diversifiers".to_string()))?;
+
+        ans.entries.push(WalletEntry::Sapling(SaplingEntry {
+            num         : i,
+            address     : encode_payment_address(&addr, network)?,
+            private_key : encoded_pk.clone(),
+            viewing_key : Some(encoded_vk.clone()),
+            account_keys: Some(account_keys.clone()),
+            seed        : Some(path.clone()),
+            diversifier_index: Some(diversifier_index_to_u64(&found_di)?),
+        }));
+
+        di = found_di;
+        di.increment().map_err(|_| Error::Derivation("Ran out of diversifiers".to_string()))?;
+    }
 
-    loop {
-        let recv = rx.recv().unwrap();
-        if recv.starts_with(&"Processed") {
-            processed = processed + 5000;
-            let timeelapsed = now.elapsed().unwrap().as_secs() + 1; // Add one second to prevent any divide by zero problems.
+    return Ok(ans);
+}
 
-            let rate = processed / timeelapsed;            
-            let expected_secs = expected_combinations / (rate as f64);
+/// Encode the address at exactly diversifier index `index`. It is an error if that index doesn't give a valid diversifier
+pub(crate) fn encode_diversified_address(spk: &ExtendedSpendingKey, index: u64, network: &Network) -> Result<String, Error> {
+    let mut di = DiversifierIndex::new();
+    di.0[..8].copy_from_slice(&index.to_le_bytes());
 
-            let (s, d) = pretty_duration(expected_secs);
+    let (found_di, addr) = ExtendedFullViewingKey::from(spk).address(di)
+        .map_err(|_| Error::Derivation("Ran out of diversifiers".to_string()))?;
+    if found_di.0 != di.0 {
+        return Err(Error::Derivation(format!("Diversifier index {} doesn't give a valid address", index)));
+    }
 
-            print!("Checking addresses at {}/sec on {} CPU threads. [50% ETA = {} {}]   \r", rate, num_threads, s, d);
-            io::stdout().flush().ok().unwrap();
-        } else {
-            // Found a solution
-            println!("");   // To clear the previous inline output to stdout;
-            wallet = recv;
+    encode_payment_address(&addr, network)
+}
 
-            please_stop.store(true, Ordering::Relaxed);
-            break;
-        } 
+/// The diversifier index is an 88-bit little endian integer, but we'll never search far enough to need more than 64 bits
+fn diversifier_index_to_u64(di: &DiversifierIndex) -> Result<u64, Error> {
+    if di.0[8..].iter().any(|b| *b != 0) {
+        return Err(Error::Derivation("Diversifier index is too large".to_string()));
     }
 
-    for handle in handles {
-        handle.join().unwrap();
-    }    
-
-    return Ok(wallet);
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&di.0[..8]);
+    Ok(u64::from_le_bytes(bytes))
 }
 
-/// Generate a series of `count` addresses and private keys. 
-pub fn generate_wallet(is_testnet: bool, nohd: bool, zcount: u32, tcount: u32, user_entropy: &[u8]) -> String {        
-    // Get 32 bytes of system entropy
+/// Mix the user's entropy with 32 bytes from the entropy source, and seed a new RNG with it. If the source fails, the
+/// user's entropy is used on its own, but only if there is enough of it.
+fn seeded_rng(source: &mut dyn EntropySource, user_entropy: &[u8]) -> Result<ChaChaRng, Error> {
+    // Get 32 bytes of entropy from the source
     let mut system_entropy:[u8; 32] = [0; 32]; 
-    #[cfg(feature = "systemrand")]
-    {
-        let result = panic::catch_unwind(|| {
-            ChaChaRng::from_entropy()
-        });
-
-        let mut system_rng = match result {
-            Ok(rng)     => rng,
-            Err(_e)     => ChaChaRng::from_seed([0; 32])
-        };
-
-        system_rng.fill(&mut system_entropy);
-    }    
+    if let Err(e) = source.fill_bytes(&mut system_entropy) {
+        if user_entropy.len() < MIN_USER_ENTROPY {
+            return Err(Error::Rng(format!("{}. Provide at least {} bytes of entropy to generate a wallet without it", e, MIN_USER_ENTROPY)));
+        }
+        system_entropy = [0; 32];
+    }
     
     // Add in user entropy to the system entropy, and produce a 32 byte hash... 
     let mut state = sha2::Sha256::new();
@@ -326,23 +509,64 @@ pub fn generate_wallet(is_testnet: bool, nohd: bool, zcount: u32, tcount: u32, u
     final_entropy.clone_from_slice(&double_sha256(&state.result()[..]));
 
     // ...which will we use to seed the RNG
-    let mut rng = ChaChaRng::from_seed(final_entropy);
+    Ok(ChaChaRng::from_seed(final_entropy))
+}
 
-    if !nohd {
-        // Allow HD addresses, so use only 1 seed        
-        let mut seed: [u8; 32] = [0; 32];
-        rng.fill(&mut seed);
-        
-        return gen_addresses_with_seed_as_json(is_testnet, zcount, tcount, |i| (seed.to_vec(), i));
-    } else {
-        // Not using HD addresses, so derive a new seed every time    
-        return gen_addresses_with_seed_as_json(is_testnet, zcount, tcount, |_| {            
-            let mut seed:[u8; 32] = [0; 32]; 
-            rng.fill(&mut seed);
-            
-            return (seed.to_vec(), 0);
-        });
-    }    
+/// Get a new HD seed from the RNG. If `mnemonic` is `Some(passphrase)`, the random bytes are used as the entropy for 
+/// a 24-word BIP-39 mnemonic, and the seed is derived from the mnemonic and passphrase, the same way mnemonic-based 
+/// Zcash wallets do it. Returns the seed and the mnemonic words, if any.
+fn new_seed(rng: &mut ChaChaRng, mnemonic: Option<&str>) -> Result<(Vec<u8>, Option<String>), Error> {
+    let mut entropy: [u8; 32] = [0; 32];
+    rng.fill(&mut entropy);
+
+    match mnemonic {
+        None             => Ok((entropy.to_vec(), None)),
+        Some(passphrase) => {
+            let m = Mnemonic::from_entropy(&entropy).map_err(|e| Error::Derivation(format!("{}", e)))?;
+            Ok((m.to_seed(passphrase).to_vec(), Some(m.to_string())))
+        }
+    }
+}
+
+/// Re-derive the Sapling addresses for the given range of accounts (m/32'/cointype'/account') from an existing HD seed, 
+/// along with `tcount` t addresses starting at the same index (m/44'/cointype'/0'/0/index) and `ucount` unified
+/// addresses starting at the same account.
+/// `seed_or_mnemonic` is either the hex "HDSeed" printed on a paper wallet, or the BIP-39 mnemonic words. 
+/// The `passphrase` is only used for mnemonics, and should be empty if the wallet was generated without one. 
+pub fn restore_wallet(seed_or_mnemonic: &str, network: &Network, accounts: Range<u32>, tcount: u32, ucount: u32, passphrase: &str) -> Result<PaperWallet, Error> {
+    if accounts.start >= accounts.end {
+        return Err(Error::InvalidInput(format!("No accounts to restore in {:?}", accounts)));
+    }
+
+    // Accounts are hardened child indexes, so they have to be < 2^31
+    if accounts.end > (1 << 31) {
+        return Err(Error::InvalidInput(format!("Account numbers have to be less than {}", 1u32 << 31)));
+    }
+
+    let (seed, words) = parse_seed(seed_or_mnemonic, passphrase)?;
+
+    let first = accounts.start;
+    gen_addresses_with_seed(network, accounts.end - accounts.start, tcount, ucount, |i| Ok((seed.clone(), words.clone(), first + i)))
+}
+
+/// Parse a hex encoded HD seed or a BIP-39 mnemonic into the seed bytes. For mnemonics, the (normalized) words are
+/// returned too.
+fn parse_seed(seed_or_mnemonic: &str, passphrase: &str) -> Result<(Vec<u8>, Option<String>), Error> {
+    let s = seed_or_mnemonic.trim();
+
+    if let Ok(seed) = hex::decode(s) {
+        // ZIP-32 requires the seed to be at least 32 bytes
+        if seed.len() < 32 || seed.len() > 252 {
+            return Err(Error::InvalidInput(format!("HD seed should be between 32 and 252 bytes, got {}", seed.len())));
+        }
+        return Ok((seed, None));
+    }
+
+    let words = s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+    match Mnemonic::parse(words.as_str()) {
+        Ok(m)  => Ok((m.to_seed(passphrase).to_vec(), Some(m.to_string()))),
+        Err(e) => Err(Error::InvalidInput(format!("Not a hex HD seed or a valid BIP-39 mnemonic: {}", e)))
+    }
 }
 
 /// Generate `count` addresses with the given seed. The addresses are derived from m/32'/cointype'/index' where 
@@ -351,103 +575,278 @@ pub fn generate_wallet(is_testnet: bool, nohd: bool, zcount: u32, tcount: u32, u
 /// Note that cointype is 1 for testnet and 133 for mainnet
 /// 
 /// get_seed is a closure that will take the address number being derived, and return a tuple containing the 
-/// seed and child number to use to derive this wallet. 
+/// seed, the mnemonic words it was derived from (if any) and child number to use to derive this wallet. 
 /// It is useful if we want to reuse (or not) the seed across multiple wallets.
-fn gen_addresses_with_seed_as_json<F>(is_testnet: bool, zcount: u32, tcount: u32, mut get_seed: F) -> String 
-    where F: FnMut(u32) -> (Vec<u8>, u32)
+fn gen_addresses_with_seed<F>(network: &Network, zcount: u32, tcount: u32, ucount: u32, mut get_seed: F) -> Result<PaperWallet, Error> 
+    where F: FnMut(u32) -> Result<(Vec<u8>, Option<String>, u32), Error>
 {
-    let mut ans = array![];
-
-    // Note that for t-addresses, we don't use HD addresses
-    let (seed, _) = get_seed(0);
-    let mut rng_seed: [u8; 32] = [0; 32];
-    rng_seed.clone_from_slice(&seed[0..32]);
+    let mut ans = PaperWallet::default();
+    ans.ticker = params(network).ticker;
     
     // First generate the Z addresses
     for i in 0..zcount {
-        let (seed, child) = get_seed(i);
-        let (addr, pk, vk, path) = get_zaddress(is_testnet, &seed, child);
-        ans.push(object!{
-                "num"           => i,
-                "address"       => addr,
-                "private_key"   => pk,
-                "viewing_key"   => vk,
-                "type"          => "zaddr",
-                "seed"          => path
-        }).unwrap(); 
+        let (seed, words, child) = get_seed(i)?;
+        let (addr, pk, vk, account_keys, mut path) = get_zaddress(network, &seed, child)?;
+        path.mnemonic = words;
+        ans.entries.push(WalletEntry::Sapling(SaplingEntry {
+                num         : i,
+                address     : addr,
+                private_key : pk,
+                viewing_key : Some(vk),
+                account_keys: Some(account_keys),
+                seed        : Some(path),
+                diversifier_index: None,
+        })); 
     }      
 
-    // Next generate the T addresses
-    // derive a RNG from the seed
-    let mut rng = ChaChaRng::from_seed(rng_seed);
-
+    // Next generate the T addresses, from the same seeds using BIP-44
     for i in 0..tcount {        
-        let (addr, pk_wif) = get_taddress(is_testnet, &mut rng);
+        let (seed, words, child) = get_seed(i)?;
+        let (addr, pk_wif, mut path) = get_taddress(network, &seed, child)?;
+        path.mnemonic = words;
+
+        ans.entries.push(WalletEntry::Transparent(TransparentEntry {
+            num         : i,
+            address     : addr,
+            private_key : pk_wif,
+            seed        : Some(path)
+        }));
+    }
 
-        ans.push(object!{
-            "num"               => i,
-            "address"           => addr,
-            "private_key"       => pk_wif,
-            "type"              => "taddr"
-        }).unwrap();
+    // And finally the unified addresses, which have an Orchard and a Sapling receiver from the same account
+    for i in 0..ucount {
+        let (seed, words, child) = get_seed(i)?;
+        let (addr, pk, sapling_pk, vk, mut path) = get_uaddress(network, &seed, child)?;
+        path.mnemonic = words;
+
+        ans.entries.push(WalletEntry::Unified(UnifiedEntry {
+            num         : i,
+            address     : addr,
+            private_key : pk,
+            sapling_private_key: sapling_pk,
+            viewing_key : vk,
+            seed        : Some(path)
+        }));
     }
 
-    return json::stringify_pretty(ans, 2);
+    return Ok(ans);
 }
 
-/// Generate a t address
-fn get_taddress(is_testnet: bool, rng: &mut ChaChaRng) -> (String, String) {
-    let mut sk_bytes: [u8; 32] = [0;32];
-
-    // There's a small chance the generated private key bytes are invalid, so
-    // we loop till we find bytes that are
-    let sk = loop {    
-        rng.fill(&mut sk_bytes);
+/// Generate a standard BIP-44 t address from the given seed at m/44'/cointype'/0'/0/index
+pub(crate) fn get_taddress(network: &Network, seed: &[u8], index: u32) -> Result<(String, String, HDSeed), Error> {
+    if index >= HARDENED {
+        return Err(Error::InvalidInput(format!("Address index {} is too large", index)));
+    }
 
-        match secp256k1::SecretKey::parse(&sk_bytes) {
-            Ok(s)  => break s,
-            Err(_) => continue
-        }
+    let key = ExtendedPrivKey::from_path(seed, &[
+                44 | HARDENED,
+                params(network).cointype | HARDENED,
+                HARDENED,
+                0,
+                index
+            ])?;
+    let path = HDSeed {
+        seed    : seed.to_vec(),
+        path    : format!("m/44'/{}'/0'/0/{}", params(network).cointype, index),
+        mnemonic: None,
+        encrypted: None,
     };
-    
-    let pubkey = secp256k1::PublicKey::from_secret_key(&sk);
+
+    let (addr, pk_wif) = encode_taddress(&key.secret_key, network);
+    return Ok((addr, pk_wif, path));
+}
+
+/// Encode the t address and WIF private key for a secp256k1 secret key
+pub(crate) fn encode_taddress(sk: &secp256k1::SecretKey, network: &Network) -> (String, String) {
+    let sk_bytes = sk.serialize();
+    let pubkey = secp256k1::PublicKey::from_secret_key(sk);
 
     // Address 
     let mut hash160 = ripemd160::Ripemd160::new();
     hash160.input(sha2::Sha256::digest(&pubkey.serialize_compressed().to_vec()));
-    let addr = hash160.result().to_base58check(&params(is_testnet).taddress_version, &[]);
+    let addr = hash160.result().to_base58check(&params(network).taddress_version, &[]);
 
     // Private Key
-    let pk_wif = sk_bytes.to_base58check(&params(is_testnet).tsecret_prefix, &[0x01]);  
+    let pk_wif = sk_bytes.to_base58check(&params(network).tsecret_prefix, &[0x01]);  
 
     return (addr, pk_wif);
 }
 
 /// Generate a standard ZIP-32 address from the given seed at 32'/44'/0'/index
-fn get_zaddress(is_testnet: bool, seed: &[u8], index: u32) -> (String, String, String, json::JsonValue) {
+pub(crate) fn get_zaddress(network: &Network, seed: &[u8], index: u32) -> Result<(String, String, String, SaplingAccountKeys, HDSeed), Error> {
+    let (spk, path) = get_zspendingkey(network, seed, index);
+
+    let encoded = encode_address(&spk, network)?;
+    let encoded_pk = encode_privatekey(&spk, network)?;
+    let encoded_vk = encode_viewingkey(&spk, network)?;
+    let account_keys = encode_account_keys(&spk, network)?;
+
+    return Ok((encoded, encoded_pk, encoded_vk, account_keys, path));
+}
+
+/// Generate a ZIP-316 unified address for the ZIP-32 account `index`, with an Orchard receiver and the account's default
+/// Sapling address as the Sapling receiver. Both the Orchard and the Sapling keys are derived at m/32'/cointype'/index'.
+/// 
+/// Returns the unified address, the Bech32m encoded Orchard spending key, the Sapling extended spending key, the unified
+/// full viewing key and the seed. There is no standard encoding for a bare Orchard spending key, so the Orchard key is
+/// only useful to this tool (see `CoinParams::osecret_prefix`). The Sapling key and the seed can be imported into
+/// other wallets.
+pub(crate) fn get_uaddress(network: &Network, seed: &[u8], index: u32) -> Result<(String, String, String, String, HDSeed), Error> {
+    let (spk, path) = get_zspendingkey(network, seed, index);
+    let osk = orchard::keys::SpendingKey::from_zip32_seed(seed, params(network).cointype, index)
+        .map_err(|e| Error::Derivation(format!("Couldn't derive the Orchard spending key: {:?}", e)))?;
+
+    let (ua, ufvk) = encode_uaddress(&osk, &spk, network)?;
+
+    return Ok((ua, encode_orchard_privatekey(&osk, network)?, encode_privatekey(&spk, network)?, ufvk, path));
+}
+
+/// The unified address and unified full viewing key for an Orchard and a Sapling spending key. The Sapling receiver is
+/// the default address, and the Orchard receiver uses the same diversifier index, as ZIP-316 recommends.
+pub(crate) fn encode_uaddress(osk: &orchard::keys::SpendingKey, spk: &ExtendedSpendingKey, network: &Network) -> Result<(String, String), Error> {
+    let address_network = network.address_network()?;
+    let (di, sapling_addr) = spk.default_address()
+        .map_err(|_| Error::Derivation("Couldn't find a valid diversifier for the default address".to_string()))?;
+
+    // The Sapling FVK in a UFVK is the ExtendedFullViewingKey without the depth, tag, child index and chain code, 
+    // i.e., the last 128 bytes (ak || nk || ovk || dk)
+    let mut efvk = Vec::new();
+    ExtendedFullViewingKey::from(spk).write(&mut efvk)?;
+    let mut sapling_fvk = [0u8; 128];
+    sapling_fvk.copy_from_slice(&efvk[efvk.len()-128..]);
+
+    let ofvk = orchard::keys::FullViewingKey::from(osk);
+    let orchard_addr = ofvk.address_at(di.0, orchard::keys::Scope::External);
+
+    let ua = unified::Address::try_from_items(vec![
+            unified::Receiver::Sapling(payment_address_bytes(&sapling_addr)?),
+            unified::Receiver::Orchard(orchard_addr.to_raw_address_bytes()),
+        ]).map_err(|e| Error::Encoding(format!("{}", e)))?;
+
+    let ufvk = unified::Ufvk::try_from_items(vec![
+            unified::Fvk::Sapling(sapling_fvk),
+            unified::Fvk::Orchard(ofvk.to_bytes()),
+        ]).map_err(|e| Error::Encoding(format!("{}", e)))?;
+
+    return Ok((ua.encode(&address_network), ufvk.encode(&address_network)));
+}
+
+/// Orchard spending keys are encoded as Bech32m, like the unified addresses, with the network's `osecret_prefix`
+pub(crate) fn encode_orchard_privatekey(osk: &orchard::keys::SpendingKey, network: &Network) -> Result<String, Error> {
+    Ok(bech32::encode(&params(network).osecret_prefix, osk.to_bytes().to_base32(), Variant::Bech32m)?)
+}
+
+/// Derive the ZIP-32 spending key at m/32'/cointype'/index'
+pub(crate) fn get_zspendingkey(network: &Network, seed: &[u8], index: u32) -> (ExtendedSpendingKey, HDSeed) {
    let spk: ExtendedSpendingKey = ExtendedSpendingKey::from_path(
             &ExtendedSpendingKey::master(seed),
             &[
                 ChildIndex::Hardened(32),
-                ChildIndex::Hardened(params(is_testnet).cointype),
+                ChildIndex::Hardened(params(network).cointype),
                 ChildIndex::Hardened(index)
             ],
         );
-    let path = object!{
-        "HDSeed"    => hex::encode(seed),
-        "path"      => format!("m/32'/{}'/{}'", params(is_testnet).cointype, index)
+    let path = HDSeed {
+        seed    : seed.to_vec(),
+        path    : format!("m/32'/{}'/{}'", params(network).cointype, index),
+        mnemonic: None,
+        encrypted: None,
     };
 
-    let encoded = encode_address(&spk, is_testnet);
-    let encoded_pk = encode_privatekey(&spk, is_testnet);
+    return (spk, path);
+}
+
+/// The same key as `get_zspendingkey`, derived without the parent fingerprint tags. ZIP-32 tags every child key with a
+/// fingerprint of its parent's full viewing key, and computing those takes 2 scalar multiplications for each of the
+/// 3 steps, which is most of the cost of deriving an account. The vanity search derives an account for every seed it
+/// tries, but only needs its address, so it uses this instead. The tag is part of the encoded key, so the returned key
+/// must only be used to derive addresses.
+///
+/// Returns None in the (about 1 in 2^260) case where a scalar doesn't fit in the 64 bytes `ToUniform` takes. Use
+/// `get_zspendingkey` then.
+pub(crate) fn get_zspendingkey_untagged(network: &Network, seed: &[u8], index: u32) -> Option<ExtendedSpendingKey> {
+    let mut xsk = ExtendedSpendingKey::master(seed);
+
+    // The key is serialized as depth || parent tag || child index || chain code || ask || nsk || ovk || dk
+    let mut master = vec![];
+    xsk.write(&mut master).ok()?;
+    let mut chain_code = [0u8; 32];
+    chain_code.copy_from_slice(&master[9..41]);
+    let mut dk = [0u8; 32];
+    dk.copy_from_slice(&master[137..169]);
+
+    // Hardened child derivation, as in ZIP-32
+    for i in [32, params(network).cointype, index].iter() {
+        let child_index = (i | HARDENED).to_le_bytes();
+        let expsk = xsk.expsk.to_bytes();
+
+        let tmp = prf_expand_vec(&chain_code, &[&[0x11], &expsk, &dk, &child_index]);
+        let (i_l, i_r) = tmp.as_bytes().split_at(32);
+
+        xsk.expsk.ask = add_to_scalar(&expsk[..32], prf_expand(i_l, &[0x13]).as_bytes())?;
+        xsk.expsk.nsk = add_to_scalar(&expsk[32..64], prf_expand(i_l, &[0x14]).as_bytes())?;
+        xsk.expsk.ovk.0.copy_from_slice(&prf_expand_vec(i_l, &[&[0x15], &expsk[64..]]).as_bytes()[..32]);
+        let child_dk = prf_expand_vec(i_l, &[&[0x16], &dk]);
+        dk.copy_from_slice(&child_dk.as_bytes()[..32]);
+        chain_code.copy_from_slice(i_r);
+    }
+
+    let mut account = vec![3, 0, 0, 0, 0];
+    account.extend_from_slice(&(index | HARDENED).to_le_bytes());
+    account.extend_from_slice(&chain_code);
+    account.extend_from_slice(&xsk.expsk.to_bytes());
+    account.extend_from_slice(&dk);
+    ExtendedSpendingKey::read(&account[..]).ok()
+}
+
+/// `(a + ToScalar(b)) mod r`, for a 32 byte scalar `a` and a 64 byte `b`, both little endian. Reducing the sum as a
+/// whole is the same as reducing `b` first, as long as the sum still fits in 64 bytes.
+fn add_to_scalar(a: &[u8], b: &[u8]) -> Option<Fs> {
+    let mut sum = [0u8; 64];
+    let mut carry = 0u16;
+    for i in 0..64 {
+        carry += b[i] as u16 + if i < a.len() { a[i] as u16 } else { 0 };
+        sum[i] = carry as u8;
+        carry >>= 8;
+    }
+
+    if carry != 0 { None } else { Some(Fs::to_uniform(&sum)) }
+}
 
+pub(crate) fn encode_viewingkey(spk: &ExtendedSpendingKey, network: &Network) -> Result<String, Error> {
     // Viewing Key is encoded as bech32 string
     let mut vv = Vec::new();
-    ExtendedFullViewingKey::from(&spk).write(&mut vv).expect("Can't write viewing key");
+    ExtendedFullViewingKey::from(spk).write(&mut vv)?;
     let c_v: Vec<u5> = vv.to_base32();
-    let encoded_vk = Bech32::new(params(is_testnet).zviewkey_prefix.into(), c_v).expect("bech32 failed").to_string();
+    let encoded_vk = bech32::encode(&params(network).zviewkey_prefix, c_v, Variant::Bech32)?;
 
-    return (encoded, encoded_pk, encoded_vk, path);
+    return Ok(encoded_vk);
+}
+
+/// Encode the least-privilege keys that make up the full viewing key. The incoming viewing key is bech32 encoded the 
+/// same way zcashd does it. There is no standard encoding for the outgoing viewing key and the diversifier key, so
+/// they are hex encoded.
+pub(crate) fn encode_account_keys(spk: &ExtendedSpendingKey, network: &Network) -> Result<SaplingAccountKeys, Error> {
+    let mut efvk = Vec::new();
+    ExtendedFullViewingKey::from(spk).write(&mut efvk)?;
+
+    // The full viewing key is the last 128 bytes, ak || nk || ovk || dk
+    let fvk = &efvk[efvk.len()-128..];
+
+    // ivk = BLAKE2s-256("Zcashivk", ak || nk), truncated to 251 bits
+    let mut ivk = [0u8; 32];
+    ivk.copy_from_slice(blake2s_simd::Params::new()
+                            .hash_length(32)
+                            .personal(b"Zcashivk")
+                            .hash(&fvk[..64])
+                            .as_bytes());
+    ivk[31] &= 0b0000_0111;
+
+    Ok(SaplingAccountKeys {
+        incoming_viewing_key: bech32::encode(&params(network).zivk_prefix, ivk.to_base32(), Variant::Bech32)?,
+        outgoing_viewing_key: hex::encode(&fvk[64..96]),
+        diversifier_key     : hex::encode(&fvk[96..128]),
+    })
 }
 
 
@@ -462,12 +861,12 @@ mod tests {
     /// Test the wallet generation and that it is generating the right number and type of addresses
     #[test]
     fn test_wallet_generation() {
-        use crate::paper::generate_wallet;
+        use crate::paper::{generate_wallet, WalletOptions, decode_privatekey, Network};
+        use crate::entropy::SystemEntropy;
         use std::collections::HashSet;
         
         // Testnet wallet
-        let w = generate_wallet(true, false, 1, 0, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions::new(Network::Test), &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 1);
         assert!(j[0]["address"].as_str().unwrap().starts_with("ztestsapling"));
         assert!(j[0]["private_key"].as_str().unwrap().starts_with("secret-extended-key-test"));
@@ -475,16 +874,24 @@ mod tests {
 
 
         // Mainnet wallet
-        let w = generate_wallet(false, false, 1, 0, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions::new(Network::Main), &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 1);
         assert!(j[0]["address"].as_str().unwrap().starts_with("zs"));
         assert!(j[0]["private_key"].as_str().unwrap().starts_with("secret-extended-key-main"));
         assert_eq!(j[0]["seed"]["path"].as_str().unwrap(), "m/32'/133'/0'");
 
+        // Regtest wallet
+        let j = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Regtest) }, &mut SystemEntropy, &[]).unwrap().to_json();
+        assert_eq!(j.len(), 2);
+        assert!(j[0]["address"].as_str().unwrap().starts_with("zregtestsapling"));
+        assert!(j[0]["private_key"].as_str().unwrap().starts_with("secret-extended-key-regtest"));
+        assert!(j[0]["viewing_key"].as_str().unwrap().starts_with("zxviewregtestsapling"));
+        assert_eq!(j[0]["seed"]["path"].as_str().unwrap(), "m/32'/1'/0'");
+        assert!(j[1]["address"].as_str().unwrap().starts_with("tm"));
+        assert_eq!(decode_privatekey(j[0]["private_key"].as_str().unwrap(), None).unwrap().1, Network::Regtest);
+
         // Check if all the addresses are the same
-        let w = generate_wallet(true, false, 3, 0, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions { zcount: 3, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 3);
 
         let mut set1 = HashSet::new();
@@ -507,7 +914,7 @@ mod tests {
 
     #[test]
     fn test_z_encoding() {
-        use crate::paper::{encode_address, encode_privatekey};
+        use crate::paper::{encode_address, encode_privatekey, Network};
         use zcash_primitives::zip32::ExtendedSpendingKey;
 
         let main_data = "[
@@ -520,8 +927,8 @@ mod tests {
             let e = hex::decode(i["encoded"].as_str().unwrap()).unwrap();
             let spk = ExtendedSpendingKey::read(&e[..]).unwrap();
 
-            assert_eq!(encode_address(&spk, false), i["address"]);
-            assert_eq!(encode_privatekey(&spk, false), i["pk"]);
+            assert_eq!(encode_address(&spk, &Network::Main).unwrap(), i["address"]);
+            assert_eq!(encode_privatekey(&spk, &Network::Main).unwrap(), i["pk"]);
         }
 
         let test_data = "[
@@ -536,24 +943,23 @@ mod tests {
             let e = hex::decode(i["encoded"].as_str().unwrap()).unwrap();
             let spk = ExtendedSpendingKey::read(&e[..]).unwrap();
 
-            assert_eq!(encode_address(&spk, true), i["address"]);
-            assert_eq!(encode_privatekey(&spk, true), i["pk"]);
+            assert_eq!(encode_address(&spk, &Network::Test).unwrap(), i["address"]);
+            assert_eq!(encode_privatekey(&spk, &Network::Test).unwrap(), i["pk"]);
         }
     }
 
      #[test]
     fn test_entroy() {
-        use crate::paper::generate_wallet;
-        use crate::paper::generate_vanity_wallet;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::vanity::{generate_vanity_wallet, VanityConfig, VanityPattern, CancelHandle};
         
         // Testnet wallet 1
-        let w1 = generate_wallet(true, false, 1, 1, &[0; 32]);
-        let j1 = json::parse(&w1).unwrap();
+        let j1 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[0; 32]).unwrap().to_json();
         assert_eq!(j1.len(), 2);
 
         // Testnet wallet 2, same user_entropy
-        let w2 = generate_wallet(true, false, 1, 1, &[0; 32]);
-        let j2 = json::parse(&w2).unwrap();
+        let j2 = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[0; 32]).unwrap().to_json();
         assert_eq!(j2.len(), 2);
 
         // Make sure that the two addresses are different
@@ -563,8 +969,9 @@ mod tests {
         assert_ne!(j1[1]["private_key"].as_str().unwrap(), j2[1]["private_key"].as_str().unwrap());
 
         // Test the vanity address generator returns different addresses for every run
-        let td1 = json::parse(&generate_vanity_wallet(false, 1, "te".to_string()).unwrap()).unwrap();
-        let td2 = json::parse(&generate_vanity_wallet(false, 1, "te".to_string()).unwrap()).unwrap();
+        let config = VanityConfig::new(Network::Main, false, vec![VanityPattern::Prefix("te".to_string())]);
+        let td1 = generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap().to_json();
+        let td2 = generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap().to_json();
         assert!(td1[0]["address"].as_str().unwrap().starts_with("zs1te"));
         assert!(td2[0]["address"].as_str().unwrap().starts_with("zs1te"));
 
@@ -573,12 +980,12 @@ mod tests {
 
     #[test]
     fn test_tandz_wallet_generation() {
-        use crate::paper::generate_wallet;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
         use std::collections::HashSet;
         
         // Testnet wallet
-        let w = generate_wallet(true, false, 1, 1, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 2);
 
         assert!(j[0]["address"].as_str().unwrap().starts_with("ztestsapling"));
@@ -590,8 +997,7 @@ mod tests {
         assert!(pk.starts_with("c") || pk.starts_with("9"));
 
         // Mainnet wallet
-        let w = generate_wallet(false, false, 1, 1, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 2);
 
         assert!(j[0]["address"].as_str().unwrap().starts_with("zs"));
@@ -603,8 +1009,7 @@ mod tests {
         assert!(pk.starts_with("L") || pk.starts_with("K") || pk.starts_with("5"));
 
         // Check if all the addresses are the same
-        let w = generate_wallet(true, false, 3, 3, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions { zcount: 3, tcount: 3, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 6);
 
         let mut set1 = HashSet::new();
@@ -621,12 +1026,12 @@ mod tests {
     /// Test nohd address generation, which does not use the same sed.
     #[test]
     fn test_nohd() {
-        use crate::paper::generate_wallet;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
         use std::collections::HashSet;
         
         // Check if all the addresses use a different seed
-        let w = generate_wallet(true, true, 3, 0, &[]);
-        let j = json::parse(&w).unwrap();
+        let j = generate_wallet(&WalletOptions { nohd: true, zcount: 3, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
         assert_eq!(j.len(), 3);
 
         let mut set1 = HashSet::new();
@@ -647,52 +1052,217 @@ mod tests {
         assert_eq!(set2.len(), 3);
     }
 
+    /// Test that mnemonic wallets carry the BIP-39 words, and the seed is derived from the words and passphrase
+    #[test]
+    fn test_mnemonic() {
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::wallet::WalletEntry;
+        use bip39::Mnemonic;
+        use std::collections::HashSet;
+
+        for passphrase in ["", "correct horse battery staple"].iter() {
+            let w = generate_wallet(&WalletOptions { zcount: 3, mnemonic: Some(passphrase.to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+            assert_eq!(w.len(), 3);
+
+            let mut words = HashSet::new();
+            for e in w.entries.iter() {
+                let seed = match e {
+                    WalletEntry::Sapling(z) => z.seed.clone().unwrap(),
+                    _ => panic!("Expected only z addresses")
+                };
+
+                let mnemonic = seed.mnemonic.unwrap();
+                assert_eq!(mnemonic.split_whitespace().count(), 24);
+                assert_eq!(Mnemonic::parse(mnemonic.as_str()).unwrap().to_seed(*passphrase).to_vec(), seed.seed);
+
+                words.insert(mnemonic);
+            }
+
+            // All addresses are derived from the same mnemonic
+            assert_eq!(words.len(), 1);
+            assert_eq!(w.to_json()[0]["seed"]["mnemonic"].as_str().unwrap(), words.iter().next().unwrap());
+        }
+
+        // In nohd mode, each address gets its own mnemonic
+        let j = generate_wallet(&WalletOptions { nohd: true, zcount: 2, mnemonic: Some("".to_string()), ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
+        assert_ne!(j[0]["seed"]["mnemonic"], j[1]["seed"]["mnemonic"]);
+
+        // ...and there are no words without the mnemonic option
+        let j = generate_wallet(&WalletOptions::new(Network::Test), &mut SystemEntropy, &[]).unwrap().to_json();
+        assert!(!j[0]["seed"].has_key("mnemonic"));
+    }
+
+    /// Test that wallets can be restored from the hex seed and the mnemonic
+    #[test]
+    fn test_restore() {
+        use crate::paper::{generate_wallet, WalletOptions, restore_wallet, Network};
+        use crate::entropy::SystemEntropy;
+
+        // Hex seed
+        let w = generate_wallet(&WalletOptions { zcount: 3, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap().to_json();
+        let r = restore_wallet(w[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Test, 0..3, 0, 0, "").unwrap().to_json();
+        for i in 0..3 {
+            assert_eq!(r[i]["address"], w[i]["address"]);
+            assert_eq!(r[i]["private_key"], w[i]["private_key"]);
+            assert_eq!(r[i]["viewing_key"], w[i]["viewing_key"]);
+            assert_eq!(r[i]["seed"]["path"], w[i]["seed"]["path"]);
+        }
+
+        // Additional accounts from the same seed
+        let r = restore_wallet(w[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Test, 2..5, 0, 0, "").unwrap().to_json();
+        assert_eq!(r.len(), 3);
+        assert_eq!(r[0]["address"], w[2]["address"]);
+        assert_eq!(r[2]["seed"]["path"].as_str().unwrap(), "m/32'/1'/4'");
+
+        // Mnemonic, with a passphrase, and extra whitespace and capitalization from typing it in
+        let w = generate_wallet(&WalletOptions { zcount: 2, mnemonic: Some("TREZOR".to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap().to_json();
+        let words = w[0]["seed"]["mnemonic"].as_str().unwrap();
+        let typed = format!("  {}\n", words.to_uppercase().replace(" ", "   "));
+
+        let r = restore_wallet(&typed, &Network::Main, 0..2, 0, 0, "TREZOR").unwrap().to_json();
+        assert_eq!(r[0]["address"], w[0]["address"]);
+        assert_eq!(r[1]["private_key"], w[1]["private_key"]);
+        assert_eq!(r[0]["seed"]["mnemonic"].as_str().unwrap(), words);
+
+        // Wrong passphrase gives different addresses
+        let r = restore_wallet(words, &Network::Main, 0..1, 0, 0, "").unwrap().to_json();
+        assert_ne!(r[0]["address"], w[0]["address"]);
+
+        // Bad input
+        restore_wallet("abcd", &Network::Main, 0..1, 0, 0, "").expect_err("Seed too short");
+        restore_wallet("not a valid mnemonic", &Network::Main, 0..1, 0, 0, "").expect_err("Bad mnemonic");
+        restore_wallet(words, &Network::Main, 1..1, 0, 0, "").expect_err("Empty range");
+        restore_wallet(words, &Network::Main, 0..(1 << 31) + 1, 0, 0, "").expect_err("Account too large");
+    }
+
+    /// Test that diversified addresses share a single key, and the first one is the account's default address
+    #[test]
+    fn test_diversified() {
+        use crate::paper::{generate_diversified_wallet, restore_wallet, Network};
+        use crate::entropy::SystemEntropy;
+        use std::collections::HashSet;
+
+        let j = generate_diversified_wallet(&Network::Main, 5, &mut SystemEntropy, &[], None).unwrap().to_json();
+        assert_eq!(j.len(), 5);
+
+        let mut addrs = HashSet::new();
+        let mut last_di = None;
+        for i in 0..5 {
+            assert!(j[i]["address"].as_str().unwrap().starts_with("zs"));
+            assert_eq!(j[i]["num"], i);
+            assert_eq!(j[i]["private_key"], j[0]["private_key"]);
+            assert_eq!(j[i]["viewing_key"], j[0]["viewing_key"]);
+            assert_eq!(j[i]["seed"]["path"].as_str().unwrap(), "m/32'/133'/0'");
+
+            // Diversifier indexes are strictly increasing
+            let di = j[i]["diversifier_index"].as_u64().unwrap();
+            assert!(last_di.map(|l| di > l).unwrap_or(true));
+            last_di = Some(di);
+
+            addrs.insert(j[i]["address"].as_str().unwrap());
+        }
+        assert_eq!(addrs.len(), 5);
+
+        // The first diversified address is the default address for the account
+        let r = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Main, 0..1, 0, 0, "").unwrap().to_json();
+        assert_eq!(r[0]["address"], j[0]["address"]);
+        assert_eq!(r[0]["private_key"], j[0]["private_key"]);
+
+        generate_diversified_wallet(&Network::Main, 0, &mut SystemEntropy, &[], None).expect_err("No addresses");
+    }
+
+    /// Test that the untagged spending key used by the vanity search is the ZIP-32 key, apart from the parent tag
+    #[test]
+    fn test_untagged_spendingkey() {
+        use crate::paper::{get_zspendingkey, get_zspendingkey_untagged, encode_address, params, Network};
+        use crate::entropy::{EntropySource, SystemEntropy};
+
+        let mut coin = params(&Network::Main);
+        coin.cointype = 1234;
+        for network in [Network::Main, Network::Test, Network::Regtest, Network::Custom(Box::new(coin))].iter() {
+            for index in [0, 1, 7].iter() {
+                let mut seed = [0u8; 32];
+                SystemEntropy.fill_bytes(&mut seed).unwrap();
+
+                let (spk, _) = get_zspendingkey(network, &seed, *index);
+                let untagged = get_zspendingkey_untagged(network, &seed, *index).unwrap();
+
+                let (mut a, mut b) = (vec![], vec![]);
+                spk.write(&mut a).unwrap();
+                untagged.write(&mut b).unwrap();
+                assert_eq!(b[1..5], [0u8; 4]);
+                assert_eq!(a[0], b[0]);
+                assert_eq!(a[5..], b[5..]);
+
+                assert_eq!(encode_address(&untagged, network).unwrap(), encode_address(&spk, network).unwrap());
+            }
+        }
+    }
+
+    /// Test that the incoming/outgoing viewing keys and diversifier key are exported from the full viewing key
+    #[test]
+    fn test_account_keys() {
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
+        use bech32::FromBase32;
+
+        for (network, ivk_hrp) in [(Network::Main, "zivks"), (Network::Test, "zivktestsapling"), (Network::Regtest, "zivkregtestsapling")].iter() {
+            let j = generate_wallet(&WalletOptions { zcount: 2, ..WalletOptions::new(network.clone()) }, &mut SystemEntropy, &[]).unwrap().to_json();
+
+            for i in 0..2 {
+                let (_, vk_data, _) = bech32::decode(j[i]["viewing_key"].as_str().unwrap()).unwrap();
+                let efvk = Vec::<u8>::from_base32(&vk_data).unwrap();
+                assert_eq!(efvk.len(), 169);
+
+                assert_eq!(j[i]["outgoing_viewing_key"].as_str().unwrap(), hex::encode(&efvk[105..137]));
+                assert_eq!(j[i]["diversifier_key"].as_str().unwrap(), hex::encode(&efvk[137..169]));
+
+                let (hrp, ivk_data, _) = bech32::decode(j[i]["incoming_viewing_key"].as_str().unwrap()).unwrap();
+                let ivk = Vec::<u8>::from_base32(&ivk_data).unwrap();
+                assert_eq!(hrp, *ivk_hrp);
+                assert_eq!(ivk.len(), 32);
+                assert_eq!(ivk[31] & 0b1111_1000, 0);
+            }
+
+            assert_ne!(j[0]["incoming_viewing_key"], j[1]["incoming_viewing_key"]);
+        }
+    }
+
     /// Test the address derivation against the test data (see below)
-    fn test_address_derivation(testdata: &str, is_testnet: bool) {
-        use crate::paper::gen_addresses_with_seed_as_json;
+    fn test_address_derivation(testdata: &str, network: &crate::paper::Network) {
+        use crate::paper::gen_addresses_with_seed;
         let td = json::parse(&testdata.replace("'", "\"")).unwrap();
         
         for i in td.members() {
             let seed = hex::decode(i["seed"].as_str().unwrap()).unwrap();
             let num  = i["num"].as_u32().unwrap();
 
-            let addresses = gen_addresses_with_seed_as_json(is_testnet, num+1, 0, |child| (seed.clone(), child));
-
-            let j = json::parse(&addresses).unwrap();
+            let j = gen_addresses_with_seed(network, num+1, 0, 0, |child| Ok((seed.clone(), None, child))).unwrap().to_json();
             assert_eq!(j[num as usize]["address"], i["addr"]);
             assert_eq!(j[num as usize]["private_key"], i["pk"]);
         }
     }
 
-    #[test]
-    fn test_vanity() {
-        use crate::paper::generate_vanity_wallet;
-
-        // Single thread
-        let td = json::parse(&generate_vanity_wallet(false, 1, "te".to_string()).unwrap()).unwrap();
-        assert_eq!(td.len(), 1);
-        assert!(td[0]["address"].as_str().unwrap().starts_with("zs1te"));
+    /// Get a valid secp256k1 key from the RNG. There's a small chance the generated private key bytes are 
+    /// invalid, so we loop till we find bytes that are
+    fn random_sk(rng: &mut rand::ChaChaRng) -> secp256k1::SecretKey {
+        use rand::Rng;
 
-        // Multi thread
-        let td = json::parse(&generate_vanity_wallet(false, 4, "tt".to_string()).unwrap()).unwrap();
-        assert_eq!(td.len(), 1);
-        assert!(td[0]["address"].as_str().unwrap().starts_with("zs1tt"));
+        let mut sk_bytes: [u8; 32] = [0;32];
+        loop {    
+            rng.fill(&mut sk_bytes);
 
-        // Testnet
-        let td = json::parse(&generate_vanity_wallet(true, 4, "ts".to_string()).unwrap()).unwrap();
-        assert_eq!(td.len(), 1);
-        assert!(td[0]["address"].as_str().unwrap().starts_with("ztestsapling1ts"));
-
-        // Test for invalid chars
-        generate_vanity_wallet(false, 1, "b".to_string()).expect_err("b is not allowed");
-        generate_vanity_wallet(false, 1, "o".to_string()).expect_err("o is not allowed");
-        generate_vanity_wallet(false, 1, "i".to_string()).expect_err("i is not allowed");
-        generate_vanity_wallet(false, 1, "1".to_string()).expect_err("1 is not allowed");
+            match secp256k1::SecretKey::parse(&sk_bytes) {
+                Ok(s)  => return s,
+                Err(_) => continue
+            }
+        }
     }
 
     #[test]
     fn test_taddr_testnet() {
-        use crate::paper::get_taddress;
+        use crate::paper::{encode_taddress, Network};
         use rand::{ChaChaRng, SeedableRng};
 
         // 0-seeded, for predictable outcomes
@@ -708,7 +1278,7 @@ mod tests {
         ];
 
         for i in 0..5 {
-            let (a, sk) = get_taddress(true, &mut rng);
+            let (a, sk) = encode_taddress(&random_sk(&mut rng), &Network::Test);
             assert_eq!(a, testdata[i][0]);
             assert_eq!(sk, testdata[i][1]);
         }        
@@ -716,7 +1286,7 @@ mod tests {
 
     #[test]
     fn test_taddr_mainnet() {
-        use crate::paper::get_taddress;
+        use crate::paper::{encode_taddress, Network};
         use rand::{ChaChaRng, SeedableRng};
 
         // 0-seeded, for predictable outcomes
@@ -732,7 +1302,7 @@ mod tests {
         ];
 
         for i in 0..5 {
-            let (a, sk) = get_taddress(false, &mut rng);
+            let (a, sk) = encode_taddress(&random_sk(&mut rng), &Network::Main);
             assert_eq!(a, testdata[i][0]);
             assert_eq!(sk, testdata[i][1]);
         }
@@ -740,6 +1310,150 @@ mod tests {
     
 
     
+    /// Test the BIP-44 derivation of t addresses from the HD seed
+    #[test]
+    fn test_taddr_hd() {
+        use crate::paper::{generate_wallet, WalletOptions, restore_wallet, get_taddress, encode_taddress, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::bip32::{ExtendedPrivKey, HARDENED};
+
+        let j = generate_wallet(&WalletOptions { tcount: 3, ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap().to_json();
+        assert_eq!(j.len(), 4);
+
+        // All addresses share the HD seed, and the t addresses are at the BIP-44 path
+        let seed = hex::decode(j[0]["seed"]["HDSeed"].as_str().unwrap()).unwrap();
+        for i in 0..3 {
+            assert_eq!(j[i+1]["type"], "taddr");
+            assert_eq!(j[i+1]["seed"]["HDSeed"], j[0]["seed"]["HDSeed"]);
+            assert_eq!(j[i+1]["seed"]["path"].as_str().unwrap(), format!("m/44'/133'/0'/0/{}", i).as_str());
+
+            let key = ExtendedPrivKey::from_path(&seed, &[44 | HARDENED, 133 | HARDENED, HARDENED, 0, i as u32]).unwrap();
+            let (addr, pk) = encode_taddress(&key.secret_key, &Network::Main);
+            assert_eq!(j[i+1]["address"].as_str().unwrap(), addr);
+            assert_eq!(j[i+1]["private_key"].as_str().unwrap(), pk);
+        }
+
+        // Testnet uses cointype 1
+        let (addr, _, path) = get_taddress(&Network::Test, &seed, 7).unwrap();
+        assert!(addr.starts_with("tm"));
+        assert_eq!(path.path, "m/44'/1'/0'/0/7");
+
+        // ...and the t addresses can be restored from the seed
+        let r = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Main, 1..2, 2, 0, "").unwrap().to_json();
+        assert_eq!(r.len(), 3);
+        assert_eq!(r[1]["address"], j[2]["address"]);
+        assert_eq!(r[2]["address"], j[3]["address"]);
+
+        get_taddress(&Network::Main, &seed, HARDENED).expect_err("Hardened index");
+    }
+
+    /// Test generating addresses for a coin with custom parameters
+    #[test]
+    fn test_custom_coin() {
+        use crate::paper::{generate_wallet, WalletOptions, restore_wallet, params, CoinParams, Network};
+        use crate::entropy::SystemEntropy;
+
+        let coin = "{
+            'ticker'          : 'ABC',
+            'taddress_version': '1cb8',
+            'tsecret_prefix'  : '80',
+            'zaddress_prefix' : 'abcs',
+            'zsecret_prefix'  : 'secret-extended-key-abc',
+            'zviewkey_prefix' : 'zxviewsabc',
+            'zivk_prefix'     : 'zivksabc',
+            'cointype'        : 1234
+        }".replace("'", "\"");
+
+        let p = CoinParams::from_json(&coin).unwrap();
+        assert_eq!(p.taddress_version, [0x1C, 0xB8]);
+        assert_eq!(p.tsecret_prefix, [0x80]);
+        assert_eq!(CoinParams::from_json(&p.to_json().dump()).unwrap(), p);
+
+        let network = Network::Custom(Box::new(p));
+        assert_eq!(params(&network).cointype, 1234);
+
+        let w = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(network.clone()) }, &mut SystemEntropy, &[]).unwrap();
+        assert_eq!(w.ticker, "ABC");
+        let j = w.to_json();
+        assert!(j[0]["address"].as_str().unwrap().starts_with("abcs1"));
+        assert!(j[0]["private_key"].as_str().unwrap().starts_with("secret-extended-key-abc1"));
+        assert!(j[0]["viewing_key"].as_str().unwrap().starts_with("zxviewsabc1"));
+        assert!(j[0]["incoming_viewing_key"].as_str().unwrap().starts_with("zivksabc1"));
+        assert_eq!(j[0]["seed"]["path"], "m/32'/1234'/0'");
+        assert!(j[1]["address"].as_str().unwrap().starts_with("t1"));
+        assert_eq!(j[1]["seed"]["path"], "m/44'/1234'/0'/0/0");
+
+        // The same seed gives different keys on Zcash, since the coin type is different
+        let r = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Main, 0..1, 0, 0, "").unwrap();
+        assert_ne!(r.entries[0].private_key(), w.entries[0].private_key());
+        assert_eq!(r.ticker, "ZEC");
+
+        generate_wallet(&WalletOptions { zcount: 0, ucount: 1, ..WalletOptions::new(network.clone()) }, &mut SystemEntropy, &[]).expect_err("No unified addresses for custom coins");
+
+        CoinParams::from_json("{}").expect_err("Missing fields");
+        CoinParams::from_json(&coin.replace("1cb8", "1c")).expect_err("Version too short");
+        CoinParams::from_json(&coin.replace("\"abcs\"", "\"ABCS\"")).expect_err("Uppercase HRP");
+        CoinParams::from_json(&coin.replace("1234", "2147483648")).expect_err("Coin type too large");
+    }
+
+    /// Test that unified addresses contain the account's Sapling address and an Orchard receiver
+    #[test]
+    fn test_unified() {
+        use crate::paper::{generate_wallet, WalletOptions, restore_wallet, Network};
+        use crate::entropy::SystemEntropy;
+        use bech32::FromBase32;
+        use zcash_address::unified::{self, Container, Encoding};
+
+        let networks = [
+            (Network::Main,    "u1",        "secret-orchard-sk-main1",    "uview1"),
+            (Network::Test,    "utest1",    "secret-orchard-sk-test1",    "uviewtest1"),
+            (Network::Regtest, "uregtest1", "secret-orchard-sk-regtest1", "uviewregtest1"),
+        ];
+
+        for (network, ua_prefix, sk_prefix, vk_prefix) in networks.iter() {
+            let j = generate_wallet(&WalletOptions { zcount: 2, ucount: 2, ..WalletOptions::new(network.clone()) }, &mut SystemEntropy, &[]).unwrap().to_json();
+            assert_eq!(j.len(), 4);
+
+            for i in 0..2 {
+                let u = &j[2 + i];
+                assert_eq!(u["type"], "uaddr");
+                assert_eq!(u["num"], i);
+                assert!(u["address"].as_str().unwrap().starts_with(ua_prefix));
+                assert!(u["private_key"].as_str().unwrap().starts_with(sk_prefix));
+                assert!(u["viewing_key"].as_str().unwrap().starts_with(vk_prefix));
+                assert_eq!(u["seed"]["path"], j[i]["seed"]["path"]);
+
+                // The Sapling key is the z address's key for the same account
+                assert_eq!(u["sapling_private_key"], j[i]["private_key"]);
+
+                // The Sapling receiver is the z address for the same account
+                let (_, ua) = unified::Address::decode(u["address"].as_str().unwrap()).unwrap();
+                let (_, zdata, _) = bech32::decode(j[i]["address"].as_str().unwrap()).unwrap();
+                let zbytes = Vec::<u8>::from_base32(&zdata).unwrap();
+
+                let items = ua.items();
+                assert_eq!(items.len(), 2);
+                assert!(items.iter().any(|r| matches!(r, unified::Receiver::Orchard(_))));
+                assert!(items.iter().any(|r| match r {
+                    unified::Receiver::Sapling(b) => b[..] == zbytes[..],
+                    _ => false
+                }));
+
+                let (_, ufvk) = unified::Ufvk::decode(u["viewing_key"].as_str().unwrap()).unwrap();
+                assert_eq!(ufvk.items().len(), 2);
+            }
+            assert_ne!(j[2]["address"], j[3]["address"]);
+
+            // Unified addresses can be restored from the seed
+            let r = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), network, 1..2, 0, 1, "").unwrap().to_json();
+            assert_eq!(r.len(), 2);
+            assert_eq!(r[1]["address"], j[3]["address"]);
+            assert_eq!(r[1]["private_key"], j[3]["private_key"]);
+            assert_eq!(r[1]["sapling_private_key"], j[3]["sapling_private_key"]);
+            assert_eq!(r[1]["viewing_key"], j[3]["viewing_key"]);
+        }
+    }
+
     ///    Test data was derived from zcashd. It contains 20 sets of seeds, and for each seed, it contains 5 accounts that are derived for the testnet and mainnet. 
     ///    We'll use the same seed and derive the same set of addresses here, and then make sure that both the address and private key matches up.
     ///    To derive the test data, add something like this in test_wallet.cpp and run with
@@ -775,6 +1489,8 @@ mod tests {
     ///    ```
     #[test]
     fn test_address_derivation_testnet() {
+        use crate::paper::Network;
+
         let testdata = "[
             {'seed': '95b888ec34bc8bd9fa9ed85fe5d81098b7326aa230bed52cc41e940c2664d894', 'pk': 'secret-extended-key-test1qvc94y54qqqqpq8dek8kzzj8p9algn0x527np3fge269jnrw28yvmhkyx6k8mcmde0v4lnw34ds8ecjd0wjld7vqznxn7mdq75vrynehr6z80unnqshqnryrnkvemd2p8me2ttgj08y4pr3jddffndcupxq6caxuhaeh9wgvpnqge9vn30jfj65q4pklx6q2xmq5n85pre3u630zza7k2h722kperqfl28y5uwhduxgagr3gjq8fjprkn05dz2pw2cn5zl4sjyz9lhg69xjqn', 'addr': 'ztestsapling1gptd43m0nucwvay39zx9euvcl2ez6ddu6xnqdv2epjfhtnqq8nvrv72s9v803m63yy5jkaktnp7', 'num': 0},
             {'seed': '95b888ec34bc8bd9fa9ed85fe5d81098b7326aa230bed52cc41e940c2664d894', 'pk': 'secret-extended-key-test1qvc94y54qyqqpqxrjyzancu5c4h7wcqjss7tuxl3grjq9pzs478w7qfy74t2tdeydrz54fq75y2c6s494z46sz7vjfssn4t9470l828gjjnjmr2ehq8qjknenwnf7k7f697y4fv22maatklmngu25audf0glu0all08lrhqff8u0gvlfqxgdfah64vg9tud7c5avdkcqa38mrdhza5rrmgpsyfkspz4myjrhty4dy3en3rxvarncuup3dkzqs9fng48qsp38s5ty2aqnzlec0', 'addr': 'ztestsapling1vt9p9pu4yr8kv2ljja7wq2sennvwu9e5scu03q6k2afq7m826yqehp3kr9s40nyd2mcwymle7sj', 'num': 1},
@@ -878,11 +1594,13 @@ mod tests {
             {'seed': 'd2d9a450962c8d8e2ec62d07ed2574e4058151aa71ccee14788ca526c87535c6', 'pk': 'secret-extended-key-test1qd5ezlhfqsqqpqzaqgpldyx09xw4ne32nn6yzms6rcr2ysquvd75q63hmzden7qvhaeutmh6jhhr9xt0cfd5s3namzsv0cequkeycx6a5gm5cs8ycjqqdwpxtnv75zqns2n8psmnuvtt43jpus2hz77sd24j6zrw26hnpdgz9dey6m84r6us3msklht5d6jzcz6hapdpu5ycwxe3vmxjqmkjpmguhecqpmacu28qfch5p6pdfvqxskvju2ccqr5kgd559nqpkeywwys4pkt4z', 'addr': 'ztestsapling1hp53xpdczuepyqzv2ms79ggnqggph3nd5zpwuepvflj7lfrjj2pgswf5na4yxakucwdwuqjrthn', 'num': 4}
         ]";
 
-        test_address_derivation(&testdata, true)
+        test_address_derivation(&testdata, &Network::Test)
     }
 
     #[test]
     fn test_address_derivation_main() {
+        use crate::paper::Network;
+
         let testdata = "[
             {'seed': '56b923ff35452781aec5aa47dddae8c5af83d01eadd7c1c115f76c909de78b88', 'pk': 'secret-extended-key-main1qdelx076qqqqpqzgcp3chz8lk5dy45nz2xhzvmr4lw2ygfgyxguf9cn3lq95znpgk4ym0eh77d7znkgftnt5fj8qnp72vjamp8h4srhydwjdr3n9v30sph5wucxglm9xpse44wde776ave55g5fwh3ar6ajlymcdvl6queqg6645aah6wgd4zqx8qxvdjy2u66me8qfqs9aewkth267h4ll2flmtwqt6jl9mjktgvwkvs90agg9xk5gxfl97uh96rmlh9s58w3h8mnqxwvtvy', 'addr': 'zs1hgxld2zlh9jkredqknr3d2y6lkqh7duppr6wxh8sxgqc3pjc8sazgpr5cpyedqwz3v977kwtpfy', 'num': 0},
             {'seed': '56b923ff35452781aec5aa47dddae8c5af83d01eadd7c1c115f76c909de78b88', 'pk': 'secret-extended-key-main1qdelx076qyqqpqyzgcp5y5jp2jtcxw3ldes8zvd26qzxmcf6pqdfttxw4cwwl9s4rj6ru9g6st8u5x55l4kx0l2g07ak4exe9j3nxv0h0ka7fh2qsuqqn9uxu5ft4um5r0a37gkxzsgr5tukagfe8mrgev5lk75496849uctrxrl30q8dhvjnyn88lkwqtf86lmmc54vj2zfek6ysmj643hc0z03cnvnsn7ffzclnunf09rkgex3xg2zkz73wwwfx09edj3tsn03q2qcnlrmt', 'addr': 'zs1xsgew36t9ycvravz5u3kr7rrp9n5nutamqwgcjmyxxtsnzejhxfkpr6c6zc2k0e73gvgg9qxj2y', 'num': 1},
@@ -986,7 +1704,7 @@ mod tests {
             {'seed': '23a28d3ce99495f9e7449a1d222d9daaba3a575fd1ad13b551cf1e07e120fa9b', 'pk': 'secret-extended-key-main1qv5d2fvjqsqqpqy2zte8rwadsnknwl5m4uwtq9t66fuah87hrr73q56cyvtn0xt8hkvu2utshgmf3e3gxvf0khvpl84qpuvz46lmhe3xjhkr7pgx5jwqwt8lj4y6l3vrzprew3ukd0zpa0uxh8phvpld759hh9320ussj0gvh53r2zlayedjzyclr2c7dvgptvse5e9d53kp0gt73vg7773w345zqcrskrd6w9htz9ejj9fchzu35ex6zpsxqge4fe4eekndaagx5lslyts2m', 'addr': 'zs1ksryksvndjmvtchkzg8q2j9ya4hh20ymrl9n3k4l55hggs34sxtc9a2xz8gzp57eg47vusln3gs', 'num': 4}
         ]";
 
-        test_address_derivation(&testdata, false);
+        test_address_derivation(&testdata, &Network::Main);
     }
 
 }
@@ -1,6 +1,9 @@
 extern crate printpdf;
 
-use crate::paper::params;
+use crate::wallet::{PaperWallet, WalletEntry};
+use crate::shamir::Share;
+use crate::keycrypt::is_encrypted_key;
+use crate::error::Error;
 
 use qrcode::QrCode;
 use qrcode::types::Color;
@@ -15,23 +18,107 @@ use printpdf::*;
 /**
  * Save the list of wallets (address + private keys) to the given PDF file name.
  */
-pub fn save_to_pdf(is_testnet: bool, addresses: &str, filename: &str) -> Result<(), String> {
-    let (doc, page1, layer1) = PdfDocument::new("Zec Sapling Paper Wallet", Mm(210.0), Mm(297.0), "Layer 1");
+pub fn save_to_pdf(wallet: &PaperWallet, filename: &str) -> Result<(), Error> {
+    std::fs::write(filename, render_pdf(wallet, false)?)?;
+    Ok(())
+}
+
+/**
+ * Save a watch-only sheet to the given PDF file name. It has the addresses and their viewing keys, but none of the
+ * private keys or seeds, so it can be used to monitor the balances without being able to spend them.
+ */
+pub fn save_watch_only_to_pdf(wallet: &PaperWallet, filename: &str) -> Result<(), Error> {
+    std::fs::write(filename, render_pdf(wallet, true)?)?;
+    Ok(())
+}
+
+/**
+ * Render the wallet (or a watch-only sheet) to PDF in memory, for callers that don't write it to a file as is, like
+ * when the output is encrypted.
+ */
+pub fn wallet_to_pdf(wallet: &PaperWallet, watch_only: bool) -> Result<Vec<u8>, Error> {
+    render_pdf(wallet, watch_only)
+}
+
+/**
+ * Save one share of a split HD seed to the given PDF file name, on a sheet of its own with the share number, the 
+ * threshold and a QR code of the share. The wallet's addresses are listed too, so the custodian can tell which wallet 
+ * the share is for.
+ */
+pub fn save_share_to_pdf(wallet: &PaperWallet, share: &Share, filename: &str) -> Result<(), Error> {
+    let title = format!("{} Seed Share {} of {}", wallet.ticker, share.index, share.count);
+    let (doc, page1, layer1) = PdfDocument::new(&title, Mm(210.0), Mm(297.0), "Layer 1");
+
+    let font  = doc.add_builtin_font(BuiltinFont::Courier).map_err(|e| Error::Pdf(format!("{}", e)))?;
+    let font_bold = doc.add_builtin_font(BuiltinFont::CourierBold).map_err(|e| Error::Pdf(format!("{}", e)))?;
+
+    let current_layer = doc.get_page(page1).get_layer(layer1);
+
+    current_layer.use_text(title.as_str(), 18, Mm(10.0), Mm(275.0), &font_bold);
+    current_layer.use_text(format!("Any {} of the {} shares are needed to restore the wallet.", share.threshold, share.count).as_str(),
+                           12, Mm(10.0), Mm(265.0), &font);
+    current_layer.use_text(format!("Share set: {:04x}", share.id).as_str(), 12, Mm(10.0), Mm(259.0), &font);
+
+    let encoded = share.encode()?;
+    let (scaledimg, finalsize) = qrcode_scaled(&encoded, 10)?;
+    add_qrcode_image_to_page(&current_layer, &scaledimg, finalsize, Mm(10.0), Mm(150.0));
+
+    current_layer.use_text("Seed Share", 14, Mm(10.0), Mm(140.0), &font_bold);
+    let strs = split_to_max(&encoded, 45, 45);   // No spaces, so the share can be copied
+    for i in 0..strs.len() {
+        current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(134.0-((i*5) as f64)), &font);
+    }
+
+    current_layer.use_text("Addresses in this wallet", 12, Mm(10.0), Mm(95.0), &font_bold);
+    let mut ypos = 89.0;
+    for entry in wallet.entries.iter() {
+        for line in split_to_max(entry.address(), 78, 78) {
+            if ypos < 15.0 {
+                break;
+            }
+            current_layer.use_text(line, 8, Mm(10.0), Mm(ypos), &font);
+            ypos -= 4.0;
+        }
+    }
+
+    add_footer_to_page(&current_layer, &font, "Keep this share safe. It can't be used to spend the funds on its own.");
+
+    let file = File::create(filename)?;
+
+    match doc.save(&mut BufWriter::new(file)) {
+        Ok(_)   => (),
+        Err(e)  => {
+            return Err(Error::Pdf(format!("Couldn't save {}. Aborting. {}", filename, e)));
+        }
+    };
+
+    return Ok(());
+}
 
-    let font  = doc.add_builtin_font(BuiltinFont::Courier).unwrap();
-    let font_bold = doc.add_builtin_font(BuiltinFont::CourierBold).unwrap();
+fn wallet_title(wallet: &PaperWallet, watch_only: bool) -> String {
+    if watch_only {
+        format!("{} Watch-Only Wallet", wallet.ticker)
+    } else {
+        format!("{} Sapling Paper Wallet", wallet.ticker)
+    }
+}
 
-    let keys = json::parse(&addresses).unwrap();
+fn render_pdf(wallet: &PaperWallet, watch_only: bool) -> Result<Vec<u8>, Error> {
+    let title = wallet_title(wallet, watch_only);
+    let (doc, page1, layer1) = PdfDocument::new(&title, Mm(210.0), Mm(297.0), "Layer 1");
+
+    let font  = doc.add_builtin_font(BuiltinFont::Courier).map_err(|e| Error::Pdf(format!("{}", e)))?;
+    let font_bold = doc.add_builtin_font(BuiltinFont::CourierBold).map_err(|e| Error::Pdf(format!("{}", e)))?;
 
     // Position on the PDF page.
     let mut pos = 0;
 
     let mut current_layer = doc.get_page(page1).get_layer(layer1);
     
-    let total_pages      = f64::ceil(keys.len() as f64 / 2.0);   // 2 per page
+    let total_pages      = f64::ceil(wallet.len() as f64 / 2.0);   // 2 per page
     let mut current_page = 1; 
 
-    for kv in keys.members() {
+    for entry in wallet.entries.iter() {
         // Add next page when moving to the next position.
         if pos >= 2 {
             pos = 0;
@@ -42,19 +129,29 @@ pub fn save_to_pdf(is_testnet: bool, addresses: &str, filename: &str) -> Result<
             current_layer = doc.get_page(page2).add_layer("Layer 3");
         }
 
-        let address  = kv["address"].as_str().unwrap();
-        let pk       = kv["private_key"].as_str().unwrap();
-        let is_taddr = !address.starts_with(&params(is_testnet).zaddress_prefix);
+        let address  = entry.address();
+        let pk       = entry.private_key();
+        let layout   = layout(entry, &wallet.ticker);
 
-        let (seed, hdpath) = if kv["type"].as_str().unwrap() == "zaddr" && kv.contains("seed") {
-            (kv["seed"]["HDSeed"].as_str().unwrap(), kv["seed"]["path"].as_str().unwrap())
-        } else {
-            ("", "")
+        let (seed, hdpath, mnemonic) = match entry.seed() {
+            Some(s) => (s.encoded_seed(), s.path.as_str(), s.mnemonic.as_deref().unwrap_or("")),
+            None    => (String::default(), "", "")
         };
 
-        // Add address + private key
-        add_address_to_page(&current_layer, &font, &font_bold, address, is_taddr, pos);
-        add_pk_to_page(&current_layer, &font, &font_bold, pk, address, is_taddr, seed, hdpath, pos);
+        // Add address + private key, or the viewing key for watch-only wallets
+        add_address_to_page(&current_layer, &font, &font_bold, address, &layout, pos)?;
+        if watch_only {
+            let ivk = match entry {
+                WalletEntry::Sapling(e) => e.account_keys.as_ref().map(|k| k.incoming_viewing_key.as_str()),
+                _ => None
+            };
+            add_vk_to_page(&current_layer, &font, &font_bold, entry.viewing_key(), ivk, &layout, pos)?;
+        } else {
+            add_pk_to_page(&current_layer, &font, &font_bold, pk, address, &layout, &seed, hdpath, mnemonic, pos)?;
+            if let WalletEntry::Unified(e) = entry {
+                add_sapling_pk_to_page(&current_layer, &font, &font_bold, &e.sapling_private_key, pos);
+            }
+        }
  
         let line1 = Line {
             points: vec![(Point::new(Mm(5.0), Mm(160.0)), false), (Point::new(Mm(205.0), Mm(160.0)), false)],
@@ -82,28 +179,44 @@ pub fn save_to_pdf(is_testnet: bool, addresses: &str, filename: &str) -> Result<
         pos = pos + 1;        
     };
     
-    let file = match File::create(filename) {
-        Ok(f)  => f,
-        Err(e) => {            
-            return Err(format!("Couldn't open {} for writing. Aborting. {}", filename, e));
-        }
-    };
+    let mut pdf = vec![];
 
-    match doc.save(&mut BufWriter::new(file)) {
+    match doc.save(&mut BufWriter::new(&mut pdf)) {
         Ok(_)   => (),
         Err(e)  => {
-            return Err(format!("Couldn't save {}. Aborting. {}", filename, e));
+            return Err(Error::Pdf(format!("Couldn't render the PDF. Aborting. {}", e)));
         }
     };
 
-    return Ok(());
+    return Ok(pdf);
+}
+
+/**
+ * How each type of address is laid out on the page. The QR code scales are picked so that the codes are about the
+ * same size, even though the keys and addresses have very different lengths.
+ */
+struct Layout {
+    title          : String,
+    address_scale  : usize,
+    pk_scale       : usize,
+    vk_scale       : usize,
+    // Unified addresses are too long to print a second time under the private key
+    repeat_address : bool,
+}
+
+fn layout(entry: &WalletEntry, ticker: &str) -> Layout {
+    match entry {
+        WalletEntry::Transparent(_) => Layout { title: "T Address".to_string(),                  address_scale: 13, pk_scale: 20, vk_scale: 0,  repeat_address: true },
+        WalletEntry::Sapling(_)     => Layout { title: format!("{} Address (Sapling)", ticker), address_scale: 10, pk_scale: 10, vk_scale: 10, repeat_address: true },
+        WalletEntry::Unified(_)     => Layout { title: format!("{} Unified Address", ticker),   address_scale: 7,  pk_scale: 17, vk_scale: 8,  repeat_address: false },
+    }
 }
 
 /**
  * Generate a qrcode. The output is a vector of RGB values of size (qrcode_modules * scalefactor) + padding
  */
-fn qrcode_scaled(data: &str, scalefactor: usize) -> (Vec<u8>, usize) {
-    let code = QrCode::new(data.as_bytes()).unwrap();
+fn qrcode_scaled(data: &str, scalefactor: usize) -> Result<(Vec<u8>, usize), Error> {
+    let code = QrCode::new(data.as_bytes()).map_err(|e| Error::Pdf(format!("Couldn't generate QR code: {:?}", e)))?;
     let output_size = code.width();
 
     let imgdata = code.to_colors();
@@ -124,7 +237,7 @@ fn qrcode_scaled(data: &str, scalefactor: usize) -> (Vec<u8>, usize) {
         }
     }).collect();
 
-    return (scaledimg, finalsize);
+    return Ok((scaledimg, finalsize));
 }
 
 /**
@@ -138,14 +251,14 @@ fn add_footer_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef,
 /**
  * Add the address section to the PDF at `pos`. Note that each page can fit only 2 wallets, so pos has to effectively be either 0 or 1.
  */
-fn add_address_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, address: &str, is_taddr: bool, pos: u32) {
-    let (scaledimg, finalsize) = qrcode_scaled(address, if is_taddr {13} else {10});
+fn add_address_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, address: &str, layout: &Layout, pos: u32) -> Result<(), Error> {
+    let (scaledimg, finalsize) = qrcode_scaled(address, layout.address_scale)?;
 
     //         page_height  top_margin  vertical_padding  position               
     let ypos = 297.0        - 5.0       - 35.0            - (140.0 * pos as f64);
-    let title = if is_taddr {"T Address"} else {"ZEC Address (Sapling)"};
 
-    add_address_at(current_layer, font, font_bold, title, address, &scaledimg, finalsize, ypos);
+    add_address_at(current_layer, font, font_bold, &layout.title, address, &scaledimg, finalsize, ypos);
+    Ok(())
 }
 
 fn add_address_at(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, title: &str, address: &str, qrcode: &Vec<u8>, finalsize: usize, ypos: f64) {
@@ -161,7 +274,7 @@ fn add_address_at(current_layer: &PdfLayerReference, font: &IndirectFontRef, fon
 /**
  * Add the private key section to the PDF at `pos`, which can effectively be only 0 or 1.
  */
-fn add_pk_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, pk: &str, address: &str, is_taddr: bool, seed: &str, path: &str, pos: u32) {
+fn add_pk_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, pk: &str, address: &str, layout: &Layout, seed: &str, path: &str, mnemonic: &str, pos: u32) -> Result<(), Error> {
     //         page_height  top_margin  vertical_padding  position               
     let ypos = 297.0        - 5.0       - 90.0           - (140.0 * pos as f64);
     
@@ -187,28 +300,133 @@ fn add_pk_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, fon
     // Reset the dashed line pattern
     current_layer.set_line_dash_pattern(LineDashPattern::default());
 
-    let (scaledimg, finalsize) = qrcode_scaled(pk, if is_taddr {20} else {10});
+    if is_encrypted_key(pk) {
+        // Encrypted keys are about twice as long, so use a smaller QR code and font to fit them in the same space
+        let (scaledimg, finalsize) = qrcode_scaled(pk, 6)?;
+        add_qrcode_image_to_page(current_layer, &scaledimg, finalsize, Mm(145.0), Mm(ypos-17.5));
 
-    add_qrcode_image_to_page(current_layer, &scaledimg, finalsize, Mm(145.0), Mm(ypos-17.5));
+        current_layer.use_text("Encrypted Private Key (needs passphrase)", 14, Mm(10.0), Mm(ypos+37.5), &font_bold);
+        let strs = split_to_max(&pk, 70, 70);
+        for i in 0..strs.len() {
+            current_layer.use_text(strs[i].clone(), 8, Mm(10.0), Mm(ypos+32.5-((i as f64)*3.5)), &font);
+        }
+    } else {
+        let (scaledimg, finalsize) = qrcode_scaled(pk, layout.pk_scale)?;
 
-    current_layer.use_text("Private Key", 14, Mm(10.0), Mm(ypos+37.5), &font_bold);
-    let strs = split_to_max(&pk, 45, 45);   // No spaces, so user can copy the private key
-    for i in 0..strs.len() {
-        current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos+32.5-((i*5) as f64)), &font);
+        add_qrcode_image_to_page(current_layer, &scaledimg, finalsize, Mm(145.0), Mm(ypos-17.5));
+
+        current_layer.use_text("Private Key", 14, Mm(10.0), Mm(ypos+37.5), &font_bold);
+        let strs = split_to_max(&pk, 45, 45);   // No spaces, so user can copy the private key
+        for i in 0..strs.len() {
+            current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos+32.5-((i*5) as f64)), &font);
+        }
     }
 
     // Add the address a second time below the private key
-    let title = if is_taddr {"T Address"} else {"ZEC Address (Sapling)"};
-    current_layer.use_text(title, 12, Mm(10.0), Mm(ypos-10.0), &font_bold);    
-    let strs = split_to_max(&address, 39, 39);  // No spaces, so user can copy the address
-    for i in 0..strs.len() {
-        current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos-15.0-((i*5) as f64)), &font);
+    if layout.repeat_address {
+        current_layer.use_text(&layout.title, 12, Mm(10.0), Mm(ypos-10.0), &font_bold);    
+        let strs = split_to_max(&address, 39, 39);  // No spaces, so user can copy the address
+        for i in 0..strs.len() {
+            current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos-15.0-((i*5) as f64)), &font);
+        }
     }
 
-    // And add the seed too. 
-    if !seed.is_empty() {
+    // And add the seed too. If there is a mnemonic, it is the backup, so print the words instead of the (long) hex seed
+    if !mnemonic.is_empty() {
+        current_layer.use_text(format!("Seed words (BIP-39), Path: {}", path).as_str(), 8, Mm(10.0), Mm(ypos-25.0), &font_bold);
+
+        let words: Vec<String> = mnemonic.split_whitespace().enumerate()
+                                    .map(|(i, w)| format!("{:>2}.{:<8}", i+1, w)).collect();
+        for (i, line) in words.chunks(8).enumerate() {
+            current_layer.use_text(line.join(" "), 8, Mm(10.0), Mm(ypos-29.0-((i*4) as f64)), &font);
+        }
+    } else if is_encrypted_key(seed) {
+        current_layer.use_text(format!("Encrypted HDSeed (needs passphrase), Path: {}", path).as_str(), 8, Mm(10.0), Mm(ypos-25.0), &font_bold);
+
+        let strs = split_to_max(seed, 70, 70);
+        for i in 0..strs.len() {
+            current_layer.use_text(strs[i].clone(), 8, Mm(10.0), Mm(ypos-29.0-((i as f64)*3.5)), &font);
+        }
+    } else if !seed.is_empty() {
         current_layer.use_text(format!("HDSeed: {}, Path: {}", seed, path).as_str(), 8, Mm(10.0), Mm(ypos-35.0), &font);
     }
+
+    Ok(())
+}
+
+/**
+ * Add the Sapling private key of a unified address under its Orchard key, in the space where the other address types
+ * repeat the address. The Orchard key can only be read by this tool, so this is the key other wallets can import.
+ */
+fn add_sapling_pk_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, sapling_pk: &str, pos: u32) {
+    //         page_height  top_margin  vertical_padding  position               
+    let ypos = 297.0        - 5.0       - 90.0           - (140.0 * pos as f64);
+
+    current_layer.use_text("Sapling Private Key", 12, Mm(10.0), Mm(ypos+17.5), &font_bold);
+    let strs = split_to_max(sapling_pk, 70, 70);
+    for i in 0..strs.len() {
+        current_layer.use_text(strs[i].clone(), 8, Mm(10.0), Mm(ypos+12.5-((i as f64)*3.5)), &font);
+    }
+}
+
+/**
+ * Add the viewing key section of a watch-only sheet to the PDF at `pos`, which can effectively be only 0 or 1.
+ */
+fn add_vk_to_page(current_layer: &PdfLayerReference, font: &IndirectFontRef, font_bold: &IndirectFontRef, vk: Option<&str>, ivk: Option<&str>, layout: &Layout, pos: u32) -> Result<(), Error> {
+    //         page_height  top_margin  vertical_padding  position               
+    let ypos = 297.0        - 5.0       - 90.0           - (140.0 * pos as f64);
+
+    let line1 = Line {
+            points: vec![(Point::new(Mm(5.0), Mm(ypos + 50.0)), false), (Point::new(Mm(205.0), Mm(ypos + 50.0)), false)],
+            is_closed: true,
+            has_fill: false,
+            has_stroke: true,
+            is_clipping_path: false,
+        };
+
+    let outline_color = printpdf::Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None));
+
+    current_layer.set_outline_color(outline_color);
+    let mut dash_pattern = LineDashPattern::default();
+    dash_pattern.dash_1 = Some(5);
+    current_layer.set_line_dash_pattern(dash_pattern);
+    current_layer.set_outline_thickness(1.0);
+
+    // Draw first line
+    current_layer.add_shape(line1);
+
+    // Reset the dashed line pattern
+    current_layer.set_line_dash_pattern(LineDashPattern::default());
+
+    current_layer.use_text("Viewing Key (Watch-Only)", 14, Mm(10.0), Mm(ypos+37.5), &font_bold);
+
+    let vk = match vk {
+        Some(vk) => vk,
+        None     => {
+            // Transparent addresses are public, so the address is all that's needed to watch them
+            current_layer.use_text("No viewing key. Use the address to monitor the balance.", 12, Mm(10.0), Mm(ypos+32.5), &font);
+            return Ok(());
+        }
+    };
+
+    let (scaledimg, finalsize) = qrcode_scaled(vk, layout.vk_scale)?;
+    add_qrcode_image_to_page(current_layer, &scaledimg, finalsize, Mm(145.0), Mm(ypos-17.5));
+
+    let strs = split_to_max(&vk, 45, 45);   // No spaces, so user can copy the viewing key
+    for i in 0..strs.len() {
+        current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos+32.5-((i*5) as f64)), &font);
+    }
+
+    // The incoming viewing key can only see incoming funds, so it can be handed out to auditors separately
+    if let Some(ivk) = ivk {
+        current_layer.use_text("Incoming Viewing Key", 12, Mm(10.0), Mm(ypos-10.0), &font_bold);
+        let strs = split_to_max(&ivk, 45, 45);
+        for i in 0..strs.len() {
+            current_layer.use_text(strs[i].clone(), 12, Mm(10.0), Mm(ypos-15.0-((i*5) as f64)), &font);
+        }
+    }
+
+    Ok(())
 }
 
 /**
@@ -285,7 +503,7 @@ mod tests {
         let factor  = 10;
         let padding = 10;
 
-        let (scaled, size) = qrcode_scaled(testdata, factor);
+        let (scaled, size) = qrcode_scaled(testdata, factor).unwrap();
         let scaled_size = (width * factor)+(2*padding);
 
         assert_eq!(size, scaled_size);
@@ -324,6 +542,35 @@ mod tests {
         }
     }
     
+    /// Test that the titles of a custom coin's wallet are kept when the wallet is read back from JSON
+    #[test]
+    fn test_custom_coin_titles() {
+        use crate::pdf::{wallet_title, layout, wallet_to_pdf};
+        use crate::paper::{generate_wallet, WalletOptions, CoinParams, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::wallet::PaperWallet;
+
+        let coin = "{
+            'ticker'          : 'YEC',
+            'taddress_version': '1c28',
+            'tsecret_prefix'  : '80',
+            'zaddress_prefix' : 'ys',
+            'zsecret_prefix'  : 'secret-extended-key-ycash',
+            'zviewkey_prefix' : 'zxviewycash',
+            'zivk_prefix'     : 'zivkycash',
+            'cointype'        : 347
+        }".replace("'", "\"");
+        let network = Network::Custom(Box::new(CoinParams::from_json(&coin).unwrap()));
+
+        let w = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(network) }, &mut SystemEntropy, &[]).unwrap();
+        let w = PaperWallet::from_json(&w.to_json_string()).unwrap();
+
+        assert_eq!(wallet_title(&w, false), "YEC Sapling Paper Wallet");
+        assert_eq!(wallet_title(&w, true), "YEC Watch-Only Wallet");
+        assert_eq!(layout(&w.entries[0], &w.ticker).title, "YEC Address (Sapling)");
+        assert!(wallet_to_pdf(&w, false).is_ok());
+    }
+
     #[test]
     fn test_split() {
         use crate::pdf::split_to_max;
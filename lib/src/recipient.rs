@@ -0,0 +1,78 @@
+use std::io::Write;
+use crate::error::Error;
+
+pub use age::x25519::Recipient;
+
+/// Parse an age X25519 recipient, like "age1..."
+pub fn parse_recipient(s: &str) -> Result<Recipient, Error> {
+    s.trim().parse::<Recipient>()
+        .map_err(|e| Error::InvalidInput(format!("Invalid age recipient {}: {}", s.trim(), e)))
+}
+
+/// Encrypt the output (a wallet JSON or PDF) to the recipients, in the age file format. Any one of the recipients'
+/// identities can decrypt it, with `age -d -i key.txt`.
+pub fn encrypt_to_recipients(data: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>, Error> {
+    let recipients = recipients.iter()
+        .map(|r| Box::new(r.clone()) as Box<dyn age::Recipient + Send>)
+        .collect::<Vec<_>>();
+    let encryptor = age::Encryptor::with_recipients(recipients)
+        .ok_or_else(|| Error::InvalidInput("Need at least 1 recipient to encrypt to".to_string()))?;
+
+    let mut encrypted = vec![];
+    let mut writer = encryptor.wrap_output(&mut encrypted)
+        .map_err(|e| Error::Encoding(format!("Couldn't encrypt to the recipients: {}", e)))?;
+    writer.write_all(data)?;
+    writer.finish()?;
+
+    Ok(encrypted)
+}
+
+#[cfg(test)]
+mod tests {
+    /// Decrypt a file encrypted by `encrypt_to_recipients` with one of the recipients' identities
+    fn decrypt_with_identity(data: &[u8], identity: &age::x25519::Identity) -> Result<Vec<u8>, crate::error::Error> {
+        use std::io::Read;
+        use std::iter;
+
+        let decryptor = match age::Decryptor::new(data).map_err(|e| crate::error::Error::Encoding(format!("{}", e)))? {
+            age::Decryptor::Recipients(d) => d,
+            _ => return Err(crate::error::Error::Encoding("Not encrypted to recipients".to_string()))
+        };
+
+        let mut reader = decryptor.decrypt(iter::once(identity as &dyn age::Identity))
+            .map_err(|e| crate::error::Error::Encoding(format!("{}", e)))?;
+        let mut decrypted = vec![];
+        reader.read_to_end(&mut decrypted)?;
+
+        Ok(decrypted)
+    }
+
+    #[test]
+    fn test_encrypt_to_recipients() {
+        use crate::recipient::{parse_recipient, encrypt_to_recipients};
+        use age::secrecy::ExposeSecret;
+
+        let vault  = age::x25519::Identity::generate();
+        let backup = age::x25519::Identity::generate();
+        let other  = age::x25519::Identity::generate();
+
+        // Recipients roundtrip through their "age1..." strings
+        let recipients = vec![
+            parse_recipient(&vault.to_public().to_string()).unwrap(),
+            parse_recipient(&format!(" {}\n", backup.to_public())).unwrap(),
+        ];
+
+        let data = b"[{\"private_key\": \"secret-extended-key-main1...\"}]";
+        let encrypted = encrypt_to_recipients(data, &recipients).unwrap();
+        assert!(!encrypted.windows(data.len()).any(|w| w == &data[..]));
+
+        // Either recipient can decrypt it, but nobody else
+        assert_eq!(decrypt_with_identity(&encrypted, &vault).unwrap(), data.to_vec());
+        assert_eq!(decrypt_with_identity(&encrypted, &backup).unwrap(), data.to_vec());
+        decrypt_with_identity(&encrypted, &other).expect_err("Not a recipient");
+
+        encrypt_to_recipients(data, &[]).expect_err("No recipients");
+        parse_recipient("age1notakey").expect_err("Invalid recipient");
+        parse_recipient(vault.to_string().expose_secret()).expect_err("Secret key, not a recipient");
+    }
+}
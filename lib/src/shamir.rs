@@ -0,0 +1,362 @@
+//! M-of-N Shamir secret sharing of a wallet's HD seed, so the backup can be split between several custodians and any
+//! `threshold` of them can restore it, but fewer learn nothing about the seed.
+//!
+//! The scheme:
+//! 1. The secret is a kind byte and the seed (see `ShareSecret`), followed by the first 4 bytes of their double
+//!    SHA-256, so that combining the wrong shares is detected instead of restoring a different wallet. For wallets
+//!    generated from seed words, the BIP-39 entropy is split instead of the seed, so the words can be restored.
+//! 2. Each byte of the secret is shared separately, with a random polynomial of degree `threshold - 1` over GF(2^8)
+//!    (see `gf256`) whose constant term is the secret byte. Share number i (1 to count) holds the value of every
+//!    polynomial at x = i.
+//! 3. A share is encoded as bech32m with the "zecshare" prefix. The data is a version byte (1), a random 2-byte id
+//!    that is the same for all the shares of a split, the threshold, the number of shares, the share number, and then
+//!    the share bytes.
+//! 4. To combine, the constant terms are recovered by Lagrange interpolation at x = 0 from any `threshold` shares,
+//!    and the checksum is checked.
+
+use bech32::{ToBase32, FromBase32, Variant};
+use bip39::Mnemonic;
+use crate::entropy::EntropySource;
+use crate::error::Error;
+use crate::gf256;
+use crate::paper::{double_sha256, restore_wallet, Network};
+use crate::wallet::PaperWallet;
+use std::ops::Range;
+
+const SHARE_PREFIX: &str = "zecshare";
+const SHARE_VERSION: u8 = 1;
+const CHECKSUM_LEN: usize = 4;
+
+/// What the shares of a wallet restore
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShareSecret {
+    /// A raw HD seed
+    Seed(Vec<u8>),
+    /// The entropy of the BIP-39 seed words, and whether the seed was derived from them with a (non-empty) passphrase
+    Mnemonic { entropy: Vec<u8>, passphrase: bool },
+}
+
+impl ShareSecret {
+    /// The kind of secret (0 for a seed, 1 for seed words, 2 for seed words with a passphrase), then the seed or entropy
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let (kind, data) = match self {
+            ShareSecret::Seed(seed)                        => (0, seed),
+            ShareSecret::Mnemonic { entropy, passphrase } => (if *passphrase { 2 } else { 1 }, entropy),
+        };
+
+        let mut bytes = vec![kind];
+        bytes.extend_from_slice(data);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<ShareSecret, Error> {
+        match bytes.split_first() {
+            Some((0, seed))    => Ok(ShareSecret::Seed(seed.to_vec())),
+            Some((1, entropy)) => Ok(ShareSecret::Mnemonic { entropy: entropy.to_vec(), passphrase: false }),
+            Some((2, entropy)) => Ok(ShareSecret::Mnemonic { entropy: entropy.to_vec(), passphrase: true }),
+            Some((kind, _))    => Err(Error::Encoding(format!("Unknown kind of seed share secret {}", kind))),
+            None               => Err(Error::Encoding("The seed shares combine to an empty secret".to_string())),
+        }
+    }
+}
+
+/// One share of a split seed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Share {
+    /// Random id of the split, to tell apart shares from different splits
+    pub id       : u16,
+    /// How many shares are needed to restore the seed
+    pub threshold: u8,
+    /// How many shares there are in total
+    pub count    : u8,
+    /// The share number, 1 to count
+    pub index    : u8,
+    pub data     : Vec<u8>,
+}
+
+impl Share {
+    pub fn encode(&self) -> Result<String, Error> {
+        let mut payload = vec![SHARE_VERSION];
+        payload.extend_from_slice(&self.id.to_be_bytes());
+        payload.extend_from_slice(&[self.threshold, self.count, self.index]);
+        payload.extend_from_slice(&self.data);
+
+        Ok(bech32::encode(SHARE_PREFIX, payload.to_base32(), Variant::Bech32m)?)
+    }
+
+    pub fn decode(s: &str) -> Result<Share, Error> {
+        let (hrp, data, variant) = bech32::decode(s.trim())?;
+        if hrp != SHARE_PREFIX || variant != Variant::Bech32m {
+            return Err(Error::Encoding(format!("Not a seed share, expected it to start with {}1", SHARE_PREFIX)));
+        }
+
+        let payload = Vec::<u8>::from_base32(&data)?;
+        if payload.len() < 6 + CHECKSUM_LEN {
+            return Err(Error::Encoding("Seed share is too short".to_string()));
+        }
+        if payload[0] != SHARE_VERSION {
+            return Err(Error::Encoding(format!("Unknown seed share version {}", payload[0])));
+        }
+
+        let share = Share {
+            id       : u16::from_be_bytes([payload[1], payload[2]]),
+            threshold: payload[3],
+            count    : payload[4],
+            index    : payload[5],
+            data     : payload[6..].to_vec(),
+        };
+
+        if share.threshold == 0 || share.threshold > share.count || share.index == 0 || share.index > share.count {
+            return Err(Error::Encoding(format!("Invalid seed share {} of {}, with a threshold of {}", share.index, share.count, share.threshold)));
+        }
+
+        Ok(share)
+    }
+}
+
+/// Split the seed into `count` shares, any `threshold` of which can restore it. The random polynomials come from
+/// `source`.
+pub fn split_seed(seed: &[u8], threshold: u8, count: u8, source: &mut dyn EntropySource) -> Result<Vec<Share>, Error> {
+    if threshold == 0 || threshold > count {
+        return Err(Error::InvalidInput(format!("The threshold has to be between 1 and the number of shares ({}), got {}", count, threshold)));
+    }
+    if seed.is_empty() {
+        return Err(Error::InvalidInput("Can't split an empty seed".to_string()));
+    }
+
+    let mut secret = seed.to_vec();
+    secret.extend_from_slice(&double_sha256(seed)[..CHECKSUM_LEN]);
+
+    let mut id = [0u8; 2];
+    source.fill_bytes(&mut id)?;
+
+    let mut shares: Vec<Share> = (1..=count).map(|index| Share {
+        id: u16::from_be_bytes(id),
+        threshold,
+        count,
+        index,
+        data: Vec::with_capacity(secret.len()),
+    }).collect();
+
+    let mut coefficients = vec![0u8; threshold as usize];
+    for b in secret.iter() {
+        coefficients[0] = *b;
+        source.fill_bytes(&mut coefficients[1..])?;
+
+        for share in shares.iter_mut() {
+            share.data.push(gf256::eval(&coefficients, share.index));
+        }
+    }
+
+    // Don't leave the last coefficients around
+    for c in coefficients.iter_mut() {
+        *c = 0;
+    }
+
+    Ok(shares)
+}
+
+/// Combine the shares back into the seed. There have to be at least `threshold` different shares from the same split.
+pub fn combine_shares(shares: &[Share]) -> Result<Vec<u8>, Error> {
+    let first = shares.first().ok_or_else(|| Error::InvalidInput("No seed shares to combine".to_string()))?;
+
+    if first.data.len() < CHECKSUM_LEN {
+        return Err(Error::InvalidInput(format!("Seed share {} is too short", first.index)));
+    }
+
+    let mut unique: Vec<&Share> = vec![];
+    for share in shares {
+        if share.threshold == 0 || share.threshold > share.count || share.index == 0 || share.index > share.count {
+            return Err(Error::InvalidInput(format!("Invalid seed share {} of {}, with a threshold of {}", share.index, share.count, share.threshold)));
+        }
+        if share.id != first.id || share.threshold != first.threshold || share.count != first.count || share.data.len() != first.data.len() {
+            return Err(Error::InvalidInput(format!("Seed share {} is from a different split than share {}", share.index, first.index)));
+        }
+        if !unique.iter().any(|s| s.index == share.index) {
+            unique.push(share);
+        }
+    }
+
+    if unique.len() < first.threshold as usize {
+        return Err(Error::InvalidInput(format!("Need {} different seed shares to restore the seed, got {}", first.threshold, unique.len())));
+    }
+    let unique = &unique[..first.threshold as usize];
+
+    let secret: Vec<u8> = (0..first.data.len()).map(|i| {
+        let points: Vec<(u8, u8)> = unique.iter().map(|s| (s.index, s.data[i])).collect();
+        gf256::interpolate_at_zero(&points)
+    }).collect();
+
+    let (seed, checksum) = secret.split_at(secret.len() - CHECKSUM_LEN);
+    if double_sha256(seed)[..CHECKSUM_LEN] != *checksum {
+        return Err(Error::InvalidInput("The seed shares don't combine to a valid seed. One of them was probably mistyped".to_string()));
+    }
+
+    Ok(seed.to_vec())
+}
+
+/// Split the HD seed of a wallet. All the addresses in the wallet have to come from the same seed, so wallets
+/// generated with a new seed for each address (or vanity wallets) can't be split. If the wallet has seed words, their
+/// entropy is split instead of the seed, so the words can be restored too.
+pub fn split_wallet(wallet: &PaperWallet, threshold: u8, count: u8, source: &mut dyn EntropySource) -> Result<Vec<Share>, Error> {
+    let mut seeds = wallet.entries.iter().map(|e| e.seed());
+    let seed = match seeds.next() {
+        Some(Some(seed)) => seed,
+        _                => return Err(Error::InvalidInput("The wallet doesn't have an HD seed to split".to_string()))
+    };
+    if seed.encrypted.is_some() {
+        return Err(Error::InvalidInput("The wallet's HD seed is encrypted. Decrypt the wallet before splitting it".to_string()));
+    }
+
+    if seeds.any(|s| s.map(|s| (&s.seed, &s.mnemonic)) != Some((&seed.seed, &seed.mnemonic))) {
+        return Err(Error::InvalidInput("The addresses in the wallet don't all come from the same HD seed, so it can't be split".to_string()));
+    }
+
+    let secret = match &seed.mnemonic {
+        Some(words) => {
+            let m = Mnemonic::parse(words.as_str()).map_err(|e| Error::InvalidInput(format!("Invalid seed words: {}", e)))?;
+            ShareSecret::Mnemonic {
+                entropy   : m.to_entropy(),
+                passphrase: m.to_seed("")[..] != seed.seed[..],
+            }
+        },
+        None => ShareSecret::Seed(seed.seed.clone())
+    };
+
+    split_seed(&secret.to_bytes(), threshold, count, source)
+}
+
+/// Combine the shares and re-derive the addresses, like `restore_wallet`. If the wallet was generated from seed words,
+/// the restored wallet has the same words, and the `passphrase` the seed was derived with is needed.
+pub fn restore_from_shares(shares: &[Share], network: &Network, accounts: Range<u32>, tcount: u32, ucount: u32, passphrase: &str) -> Result<PaperWallet, Error> {
+    match ShareSecret::from_bytes(&combine_shares(shares)?)? {
+        ShareSecret::Seed(seed) => {
+            if !passphrase.is_empty() {
+                return Err(Error::InvalidInput("The wallet doesn't have seed words, so it has no passphrase".to_string()));
+            }
+            restore_wallet(&hex::encode(&seed), network, accounts, tcount, ucount, "")
+        },
+        ShareSecret::Mnemonic { entropy, passphrase: needs_passphrase } => {
+            if needs_passphrase && passphrase.is_empty() {
+                return Err(Error::InvalidInput("The wallet's seed words were used with a passphrase. Pass it to restore the wallet".to_string()));
+            }
+            if !needs_passphrase && !passphrase.is_empty() {
+                return Err(Error::InvalidInput("The wallet's seed words were used without a passphrase".to_string()));
+            }
+
+            let m = Mnemonic::from_entropy(&entropy).map_err(|e| Error::InvalidInput(format!("Invalid seed words: {}", e)))?;
+            restore_wallet(&m.to_string(), network, accounts, tcount, ucount, passphrase)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn test_split_combine() {
+        use crate::shamir::{split_seed, combine_shares, Share};
+        use crate::entropy::SystemEntropy;
+
+        let seed: Vec<u8> = (0..32).collect();
+        let shares = split_seed(&seed, 3, 5, &mut SystemEntropy).unwrap();
+        assert_eq!(shares.len(), 5);
+
+        // Every combination of 3 (or more) shares gives back the seed
+        for a in 0..5 {
+            for b in (a+1)..5 {
+                for c in (b+1)..5 {
+                    let picked = vec![shares[c].clone(), shares[a].clone(), shares[b].clone()];
+                    assert_eq!(combine_shares(&picked).unwrap(), seed);
+                }
+            }
+        }
+        assert_eq!(combine_shares(&shares).unwrap(), seed);
+
+        // 2 shares aren't enough, even if one is given twice
+        combine_shares(&shares[0..2]).expect_err("Not enough shares");
+        combine_shares(&[shares[0].clone(), shares[1].clone(), shares[1].clone()]).expect_err("Not enough different shares");
+
+        // A share with a wrong byte is caught by the checksum
+        let mut bad = shares[0..3].to_vec();
+        bad[1].data[5] ^= 1;
+        combine_shares(&bad).expect_err("Bad share");
+
+        // Shares from different splits can't be mixed
+        let other = split_seed(&seed, 3, 5, &mut SystemEntropy).unwrap();
+        if other[0].id != shares[0].id {
+            combine_shares(&[shares[0].clone(), shares[1].clone(), other[2].clone()]).expect_err("Different splits");
+        }
+
+        // 1 of N works too, and every share is enough on its own
+        let copies = split_seed(&seed, 1, 3, &mut SystemEntropy).unwrap();
+        assert_eq!(combine_shares(&copies[2..3]).unwrap(), seed);
+
+        split_seed(&seed, 0, 3, &mut SystemEntropy).expect_err("Zero threshold");
+        split_seed(&seed, 4, 3, &mut SystemEntropy).expect_err("Threshold above count");
+        combine_shares(&[]).expect_err("No shares");
+
+        // Shares that weren't decoded are checked too, instead of panicking or restoring garbage
+        let mut short = shares[0..3].to_vec();
+        for share in short.iter_mut() {
+            share.data.truncate(2);
+        }
+        combine_shares(&short).expect_err("Too short");
+        let mut zero = shares[0..3].to_vec();
+        zero[0].index = 0;
+        combine_shares(&zero).expect_err("Share number 0");
+        let mut high = shares[0..3].to_vec();
+        high[0].index = 6;
+        combine_shares(&high).expect_err("Share number above count");
+
+        // Encoding
+        for share in shares.iter() {
+            let s = share.encode().unwrap();
+            assert!(s.starts_with("zecshare1"));
+            assert_eq!(Share::decode(&s).unwrap(), *share);
+        }
+
+        let s = shares[0].encode().unwrap();
+        let mut typo = s.clone().into_bytes();
+        typo[20] = if typo[20] == b'q' { b'p' } else { b'q' };
+        Share::decode(&String::from_utf8(typo).unwrap()).expect_err("Typo");
+        Share::decode("zs1z7rejlpsa98s2rrrfkwmaxu53e4ue0ulcrw0h4x5g8jl04tak0d3mm47vdtahatqrlkngh9sly").expect_err("Not a share");
+    }
+
+    #[test]
+    fn test_restore_from_shares() {
+        use crate::shamir::{split_wallet, restore_from_shares, combine_shares, ShareSecret};
+        use crate::entropy::SystemEntropy;
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+
+        for mnemonic in [None, Some(""), Some("pass")].iter() {
+            let w = generate_wallet(&WalletOptions { zcount: 2, tcount: 1, ucount: 1, mnemonic: mnemonic.map(String::from), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+            let shares = split_wallet(&w, 2, 3, &mut SystemEntropy).unwrap();
+
+            // The restored wallet is the same, including the seed words
+            let restored = restore_from_shares(&shares[1..3], &Network::Main, 0..2, 1, 1, mnemonic.unwrap_or("")).unwrap();
+            assert_eq!(restored.entries, w.entries);
+            assert!(restored.entries.iter().all(|e| e.seed().unwrap().mnemonic.is_some() == mnemonic.is_some()));
+
+            // Seed words are split by their 32 bytes of entropy, not the 64-byte seed
+            match ShareSecret::from_bytes(&combine_shares(&shares[0..2]).unwrap()).unwrap() {
+                ShareSecret::Seed(seed)                       => { assert!(mnemonic.is_none()); assert_eq!(seed.len(), 32) },
+                ShareSecret::Mnemonic { entropy, passphrase } => { assert_eq!(entropy.len(), 32); assert_eq!(passphrase, *mnemonic == Some("pass")) }
+            }
+        }
+
+        // The passphrase has to be given if, and only if, the seed words were used with one
+        let w = generate_wallet(&WalletOptions { mnemonic: Some("pass".to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        let shares = split_wallet(&w, 2, 3, &mut SystemEntropy).unwrap();
+        restore_from_shares(&shares[0..2], &Network::Main, 0..1, 0, 0, "").expect_err("Missing passphrase");
+        let other = restore_from_shares(&shares[0..2], &Network::Main, 0..1, 0, 0, "wrong").unwrap();
+        assert_ne!(other.entries[0].address(), w.entries[0].address());
+
+        let w = generate_wallet(&WalletOptions::new(Network::Main), &mut SystemEntropy, &[]).unwrap();
+        let shares = split_wallet(&w, 2, 3, &mut SystemEntropy).unwrap();
+        restore_from_shares(&shares[0..2], &Network::Main, 0..1, 0, 0, "pass").expect_err("No seed words");
+
+        // Wallets with a seed for every address can't be split
+        let w = generate_wallet(&WalletOptions { nohd: true, zcount: 2, ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        split_wallet(&w, 2, 3, &mut SystemEntropy).expect_err("Different seeds");
+    }
+}
@@ -0,0 +1,1156 @@
+use std::fmt;
+use std::thread;
+use std::sync::mpsc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use base58::ToBase58;
+use bech32::{u5, ToBase32};
+use zcash_primitives::zip32::{DiversifierIndex, DiversifierKey, ExtendedSpendingKey, ExtendedFullViewingKey};
+use crate::paper::{Network, params, increment, encode_address, encode_payment_address, encode_privatekey, encode_viewingkey,
+                   encode_account_keys, get_zspendingkey, get_zspendingkey_untagged, get_zaddress, get_taddress};
+use crate::wallet::{PaperWallet, WalletEntry, SaplingEntry, TransparentEntry};
+use crate::checkpoint::{CheckpointConfig, ThreadState, VanityState, save_checkpoint};
+use crate::entropy::EntropySource;
+use crate::error::Error;
+
+/// Where in the address a vanity pattern has to appear. Patterns are matched against the address without the fixed
+/// "zs1" (or "t1") at the start.
+#[derive(Clone, Debug, PartialEq)]
+pub enum VanityPattern {
+    Prefix(String),
+    Suffix(String),
+    Contains(String),
+}
+
+impl VanityPattern {
+    pub fn text(&self) -> &str {
+        match self {
+            VanityPattern::Prefix(s) | VanityPattern::Suffix(s) | VanityPattern::Contains(s) => s
+        }
+    }
+
+    fn matches(&self, data: &str) -> bool {
+        match self {
+            VanityPattern::Prefix(s)   => data.starts_with(s.as_str()),
+            VanityPattern::Suffix(s)   => data.ends_with(s.as_str()),
+            VanityPattern::Contains(s) => data.contains(s.as_str()),
+        }
+    }
+
+    fn to_lowercase(&self) -> VanityPattern {
+        match self {
+            VanityPattern::Prefix(s)   => VanityPattern::Prefix(s.to_lowercase()),
+            VanityPattern::Suffix(s)   => VanityPattern::Suffix(s.to_lowercase()),
+            VanityPattern::Contains(s) => VanityPattern::Contains(s.to_lowercase()),
+        }
+    }
+}
+
+impl fmt::Display for VanityPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VanityPattern::Prefix(s)   => write!(f, "starting with \"{}\"", s),
+            VanityPattern::Suffix(s)   => write!(f, "ending with \"{}\"", s),
+            VanityPattern::Contains(s) => write!(f, "containing \"{}\"", s),
+        }
+    }
+}
+
+/// Messages sent from the vanity threads back to `generate_vanity_wallet`
+pub enum VanityMessage {
+    /// The thread (at this index) has checked this many seeds in total
+    Processed(usize, u64),
+    /// The thread found an address that matches the pattern at this index
+    Found(usize, WalletEntry),
+    /// The thread hit an error and stopped
+    Failed(Error),
+}
+
+// Turn the pattern into Vec<u5>, so it can be matched directly without any encoding overhead.
+fn get_bech32_for_pattern(pattern: &str) -> Result<Vec<u5>, Error> {
+    // Reverse character set. Maps ASCII byte -> CHARSET index on [0,31]
+    const CHARSET_REV: [i8; 128] = [
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1, -1,
+        15, -1, 10, 17, 21, 20, 26, 30,  7,  5, -1, -1, -1, -1, -1, -1,
+        -1, 29, -1, 24, 13, 25,  9,  8, 23, -1, 18, 22, 31, 27, 19, -1,
+        1,  0,  3, 16, 11, 28, 12, 14,  6,  4,  2, -1, -1, -1, -1, -1,
+        -1, 29, -1, 24, 13, 25,  9,  8, 23, -1, 18, 22, 31, 27, 19, -1,
+        1,  0,  3, 16, 11, 28, 12, 14,  6,  4,  2, -1, -1, -1, -1, -1
+    ];
+
+    let mut ans = Vec::new();
+    for c in pattern.chars() {
+        if !c.is_ascii() || CHARSET_REV[c as usize] == -1 {
+            return Err(Error::InvalidInput(format!("Invalid character in pattern: '{}'", c)));
+        }
+        ans.push(u5::try_from_u8(CHARSET_REV[c as usize] as u8)?);
+    }
+
+    return Ok(ans);
+}
+
+/// Add `n` to the little endian seed, which is the same as calling `increment` `n` times
+fn advance(s: &mut [u8; 32], n: u64) -> Result<(), ()> {
+    let mut carry = n as u128;
+    for k in 0..32 {
+        if carry == 0 {
+            return Ok(());
+        }
+        carry += s[k] as u128;
+        s[k] = carry as u8;
+        carry >>= 8;
+    }
+
+    if carry == 0 { Ok(()) } else { Err(()) }
+}
+
+/// Every 5000 seeds, report the progress and check if the thread should stop
+fn keep_going(thread: usize, checked: u64, tx: &mpsc::Sender<VanityMessage>, please_stop: &AtomicBool) -> bool {
+    if checked % 5000 != 0 {
+        return true;
+    }
+
+    !please_stop.load(Ordering::Relaxed) && tx.send(VanityMessage::Processed(thread, checked)).is_ok()
+}
+
+/// A single thread that grinds through HD seeds (or diversifier indexes, see `VanityMode`) to find addresses that match
+/// any of the patterns. In `VanityMode::Seeds`, every seed is used like a regular HD wallet seed: Sapling addresses are
+/// the default address of the ZIP-32 account at m/32'/cointype'/0', and t addresses are derived at the BIP-44 path
+/// m/44'/cointype'/0'/0/0. So the vanity addresses can be restored from the seed like any other address.
+///
+/// The thread tries the seeds after `start.seed` (or the diversifier indexes of `start.seed`), skipping the
+/// `start.checked` ones it already tried, so a resumed search checks exactly the same keys as an uninterrupted one. It
+/// keeps searching after a match, until `please_stop` is set or the receiver is dropped.
+pub fn vanity_thread(config: VanityConfig, thread: usize, start: ThreadState, tx: mpsc::Sender<VanityMessage>, please_stop: Arc<AtomicBool>) {
+    let result = match (config.mode, config.is_taddr) {
+        (VanityMode::Seeds, true)        => vanity_search_taddr(&config.network, thread, &start, &config.patterns, &tx, &please_stop),
+        (VanityMode::Seeds, false)       => vanity_search(&config.network, thread, &start, &config.patterns, &tx, &please_stop),
+        (VanityMode::Diversifier, false) => vanity_search_diversified(&config.network, thread, config.num_threads as u64, &start, &config.patterns, &tx, &please_stop),
+        (VanityMode::Diversifier, true)  => Err(Error::InvalidInput("t addresses don't have diversifiers".to_string())),
+    };
+
+    // Report any errors back to the main thread instead of panicking
+    if let Err(e) = result {
+        tx.send(VanityMessage::Failed(e)).ok();
+    }
+}
+
+fn vanity_search_taddr(network: &Network, thread: usize, start: &ThreadState, patterns: &[VanityPattern], tx: &mpsc::Sender<VanityMessage>, please_stop: &AtomicBool) -> Result<(), Error> {
+    let mut seed = start.seed;
+    let mut checked = start.checked;
+    if advance(&mut seed, checked).is_err() {
+        return Ok(());
+    }
+
+    loop {
+        if increment(&mut seed).is_err() {
+            return Ok(());
+        }
+        checked = checked + 1;
+
+        // The first 2 characters ("t1" or "tm") are the same for all addresses
+        let (addr, pk_wif, path) = get_taddress(network, &seed, 0)?;
+        if let Some(idx) = patterns.iter().position(|p| p.matches(&addr[2..])) {
+            let entry = WalletEntry::Transparent(TransparentEntry {
+                num         : idx as u32,
+                address     : addr,
+                private_key : pk_wif,
+                seed        : Some(path),
+            });
+
+            // If the receiver is gone, the search is over
+            if tx.send(VanityMessage::Found(idx, entry)).is_err() {
+                return Ok(());
+            }
+        }
+
+        if !keep_going(thread, checked, tx, please_stop) {
+            return Ok(());
+        }
+    }
+}
+
+fn vanity_search(network: &Network, thread: usize, start: &ThreadState, patterns: &[VanityPattern], tx: &mpsc::Sender<VanityMessage>, please_stop: &AtomicBool) -> Result<(), Error> {
+    // Bech32 is case insensitive, but addresses are always encoded in lowercase
+    let patterns: Vec<VanityPattern> = patterns.iter().map(|p| p.to_lowercase()).collect();
+    let prefixes = prefix_patterns(&patterns)?;
+    let need_address = prefixes.iter().any(|p| p.is_none());
+    let hrp_len = params(network).zaddress_prefix.len() + 1;
+
+    let mut seed = start.seed;
+    let mut checked = start.checked;
+    if advance(&mut seed, checked).is_err() {
+        return Ok(());
+    }
+
+    loop {
+        if increment(&mut seed).is_err() {
+            return Ok(());
+        }
+        checked = checked + 1;
+
+        // Only the address is needed to check for a match, so the account key is derived without its parent tag, and
+        // the viewing keys are derived for the matches
+        let spk = match get_zspendingkey_untagged(network, &seed, 0) {
+            Some(spk) => spk,
+            None      => get_zspendingkey(network, &seed, 0).0
+        };
+
+        // Prefixes can be checked on the default diversifier, without the scalar multiplications for pk_d
+        let (_, d) = diversifier_key(&spk)?.diversifier(DiversifierIndex::new())
+            .map_err(|_| Error::Derivation("Couldn't find a valid diversifier for the default address".to_string()))?;
+        let mut matched = match_prefix(&prefixes, &d.0.to_base32());
+
+        // Suffixes and substrings need the full address
+        if matched.is_none() && need_address {
+            let encoded = encode_address(&spk, network)?;
+            matched = patterns.iter().zip(prefixes.iter())
+                .position(|(p, v)| v.is_none() && p.matches(&encoded[hrp_len..]));
+        }
+
+        if let Some(idx) = matched {
+            let (address, pk, vk, account_keys, path) = get_zaddress(network, &seed, 0)?;
+
+            let entry = WalletEntry::Sapling(SaplingEntry {
+                num         : idx as u32,
+                address,
+                private_key : pk,
+                viewing_key : Some(vk),
+                account_keys: Some(account_keys),
+                seed        : Some(path),
+                diversifier_index: None,
+            });
+
+            // If the receiver is gone, the search is over
+            if tx.send(VanityMessage::Found(idx, entry)).is_err() {
+                return Ok(());
+            }
+        }
+
+        if !keep_going(thread, checked, tx, please_stop) {
+            return Ok(());
+        }
+    }
+}
+
+/// The prefix patterns as bech32 characters, and None for the other patterns. Prefixes only depend on the diversifier,
+/// so they can be checked without computing the whole address.
+fn prefix_patterns(patterns: &[VanityPattern]) -> Result<Vec<Option<Vec<u5>>>, Error> {
+    patterns.iter().map(|p| match p {
+        VanityPattern::Prefix(s) => get_bech32_for_pattern(s).map(Some),
+        _                        => Ok(None)
+    }).collect()
+}
+
+/// The first prefix that the diversifier (as bech32 characters) starts with
+fn match_prefix(prefixes: &[Option<Vec<u5>>], d_bytes: &[u5]) -> Option<usize> {
+    prefixes.iter().position(|p| match p {
+        Some(v) => v.len() <= d_bytes.len() && v[..] == d_bytes[..v.len()],
+        None    => false
+    })
+}
+
+/// The diversifier key is the last 32 bytes of the serialized spending key (or full viewing key)
+fn diversifier_key(spk: &ExtendedSpendingKey) -> Result<DiversifierKey, Error> {
+    let mut bytes = vec![];
+    spk.write(&mut bytes)?;
+
+    let mut dk = DiversifierKey([0u8; 32]);
+    dk.0.copy_from_slice(&bytes[bytes.len()-32..]);
+    Ok(dk)
+}
+
+/// Search the diversifier indexes of the ZIP-32 account at m/32'/cointype'/0' of the seed. All the threads share the
+/// seed, and take turns with the indexes: thread `i` of `n` checks the indexes `i`, `i + n`, `i + 2n` and so on.
+fn vanity_search_diversified(network: &Network, thread: usize, num_threads: u64, start: &ThreadState, patterns: &[VanityPattern], tx: &mpsc::Sender<VanityMessage>, please_stop: &AtomicBool) -> Result<(), Error> {
+    // Bech32 is case insensitive, but addresses are always encoded in lowercase
+    let patterns: Vec<VanityPattern> = patterns.iter().map(|p| p.to_lowercase()).collect();
+    let prefixes = prefix_patterns(&patterns)?;
+    let need_address = prefixes.iter().any(|p| p.is_none());
+    let hrp_len = params(network).zaddress_prefix.len() + 1;
+
+    // All the addresses share the account's keys
+    let (spk, path) = get_zspendingkey(network, &start.seed, 0);
+    let efvk = ExtendedFullViewingKey::from(&spk);
+    let encoded_pk = encode_privatekey(&spk, network)?;
+    let encoded_vk = encode_viewingkey(&spk, network)?;
+    let account_keys = encode_account_keys(&spk, network)?;
+    let dk = diversifier_key(&spk)?;
+
+    let mut checked = start.checked;
+    loop {
+        let index = match checked.checked_mul(num_threads).and_then(|i| i.checked_add(thread as u64)) {
+            Some(i) => i,
+            None    => return Ok(())
+        };
+        checked = checked + 1;
+
+        let mut di = DiversifierIndex::new();
+        di.0[..8].copy_from_slice(&index.to_le_bytes());
+
+        // About half the indexes don't give a valid diversifier. `diversifier` skips ahead to the next valid one, which
+        // is some other thread's index, so it's left to that thread
+        let (found_di, d) = dk.diversifier(di)
+            .map_err(|_| Error::Derivation("Ran out of diversifiers".to_string()))?;
+
+        if found_di.0 == di.0 {
+            let mut matched = match_prefix(&prefixes, &d.0.to_base32());
+
+            let address = if matched.is_some() || need_address {
+                let (_, addr) = efvk.address(di)
+                    .map_err(|_| Error::Derivation("Ran out of diversifiers".to_string()))?;
+                Some(encode_payment_address(&addr, network)?)
+            } else {
+                None
+            };
+
+            // Suffixes and substrings need the full address
+            if let (None, Some(encoded)) = (matched, &address) {
+                matched = patterns.iter().zip(prefixes.iter())
+                    .position(|(p, v)| v.is_none() && p.matches(&encoded[hrp_len..]));
+            }
+
+            if let (Some(idx), Some(address)) = (matched, address) {
+                let entry = WalletEntry::Sapling(SaplingEntry {
+                    num         : idx as u32,
+                    address,
+                    private_key : encoded_pk.clone(),
+                    viewing_key : Some(encoded_vk.clone()),
+                    account_keys: Some(account_keys.clone()),
+                    seed        : Some(path.clone()),
+                    diversifier_index: Some(index),
+                });
+
+                // If the receiver is gone, the search is over
+                if tx.send(VanityMessage::Found(idx, entry)).is_err() {
+                    return Ok(());
+                }
+            }
+        }
+
+        if !keep_going(thread, checked, tx, please_stop) {
+            return Ok(());
+        }
+    }
+}
+
+const BASE58_ALPHABET: &str = "123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// Sapling addresses have 69 characters of data (the 11 byte diversifier and the 32 byte pk_d), followed by the 6
+/// character bech32 checksum.
+const SAPLING_DATA_CHARS: usize = 69;
+const BECH32_CHECKSUM_CHARS: usize = 6;
+
+/// Check that every pattern can appear in an address, and return the expected number of addresses that need to be
+/// checked to find one that matches any of them.
+pub fn vanity_difficulty(network: &Network, is_taddr: bool, patterns: &[VanityPattern]) -> Result<f64, Error> {
+    if patterns.is_empty() {
+        return Err(Error::InvalidInput("Need at least one vanity pattern".to_string()));
+    }
+
+    // The chance that an address matches at least one of the patterns is (roughly) the sum of the chances
+    let mut chance = 0.0;
+    for pattern in patterns {
+        chance += 1.0 / pattern_difficulty(network, is_taddr, pattern)?;
+    }
+
+    Ok(1.0 / chance)
+}
+
+/// Check that the pattern can appear in an address, and return the expected number of addresses that need to be
+/// checked to find one that matches it. For Sapling addresses, the pattern is matched against the part after "zs1",
+/// and for t addresses against the part after "t1" (or "tm" on testnet and regtest).
+pub fn pattern_difficulty(network: &Network, is_taddr: bool, pattern: &VanityPattern) -> Result<f64, Error> {
+    if pattern.text().is_empty() {
+        return Err(Error::InvalidInput("Vanity pattern can't be empty".to_string()));
+    }
+
+    if is_taddr {
+        taddr_pattern_difficulty(network, pattern)
+    } else {
+        sapling_pattern_difficulty(pattern)
+    }
+}
+
+fn sapling_pattern_difficulty(pattern: &VanityPattern) -> Result<f64, Error> {
+    // Test the pattern first
+    let chars = match get_bech32_for_pattern(pattern.text()) {
+        Ok(c)  => c,
+        Err(e) => return Err(Error::InvalidInput(format!("{}. Note that ['b', 'i', 'o', '1'] are not allowed in addresses.", e)))
+    };
+
+    let len = chars.len();
+    let total = SAPLING_DATA_CHARS + BECH32_CHECKSUM_CHARS;
+    let combinations = (32 as f64).powf(len as f64);
+
+    match pattern {
+        VanityPattern::Prefix(_) => {
+            // Only the first 17 characters after the "1" are determined by the diversifier
+            if len > 17 {
+                return Err(Error::InvalidInput(format!("Vanity prefix can be at most 17 characters, got {}", len)));
+            }
+
+            Ok(combinations)
+        },
+        VanityPattern::Suffix(_) => {
+            if len > total {
+                return Err(Error::InvalidInput(format!("Vanity suffix can be at most {} characters, got {}", total, len)));
+            }
+
+            // The last data character only has 4 bits of data, and is padded with a 0 bit
+            if len > BECH32_CHECKSUM_CHARS && chars[len - BECH32_CHECKSUM_CHARS - 1].to_u8() & 1 == 1 {
+                return Err(Error::InvalidInput(format!("No address can end with {}. The character {} from the end has to be one of \"qz98ftd03n4he6u7\"",
+                                pattern.text(), BECH32_CHECKSUM_CHARS + 1)));
+            }
+
+            Ok(combinations)
+        },
+        VanityPattern::Contains(_) => {
+            if len > total {
+                return Err(Error::InvalidInput(format!("Vanity pattern can be at most {} characters, got {}", total, len)));
+            }
+
+            // The pattern can start at any position in the address
+            Ok(combinations / ((total - len + 1) as f64))
+        }
+    }
+}
+
+fn taddr_pattern_difficulty(network: &Network, pattern: &VanityPattern) -> Result<f64, Error> {
+    let text = pattern.text();
+    if let Some(c) = text.chars().find(|c| !BASE58_ALPHABET.contains(*c)) {
+        return Err(Error::InvalidInput(format!("Invalid character in pattern: '{}'. Note that ['0', 'O', 'I', 'l'] are not allowed in t-addresses.", c)));
+    }
+
+    let version = params(network).taddress_version;
+    let smallest = [&version[..], &[0x00; 24][..]].concat().to_base58();
+    let largest  = [&version[..], &[0xff; 24][..]].concat().to_base58();
+
+    // All t addresses are the same length, so they can be compared digit by digit
+    let data_len = largest.len() - 2;
+    if text.len() > data_len {
+        return Err(Error::InvalidInput(format!("Vanity pattern can be at most {} characters, got {}", data_len, text.len())));
+    }
+
+    let combinations = (58 as f64).powf(text.len() as f64);
+    match pattern {
+        VanityPattern::Prefix(_)   => taddr_prefix_difficulty(text, &smallest, &largest),
+        VanityPattern::Suffix(_)   => Ok(combinations),
+        VanityPattern::Contains(_) => Ok(combinations / ((data_len - text.len() + 1) as f64)),
+    }
+}
+
+/// t addresses are a base58 number, which is not a power of 2, so not every character is possible right after the
+/// version prefix. The smallest and largest addresses (for all-zero and all-one hashes) tell us which prefixes are
+/// reachable, and what fraction of the addresses start with the prefix.
+fn taddr_prefix_difficulty(prefix: &str, smallest: &str, largest: &str) -> Result<f64, Error> {
+    let len = largest.len();
+    let fixed = &smallest[..2];
+    let lowest  = format!("{}{}{}", fixed, prefix, "1".repeat(len - 2 - prefix.len()));
+    let highest = format!("{}{}{}", fixed, prefix, "z".repeat(len - 2 - prefix.len()));
+
+    let lo = if base58_diff(&lowest, smallest) > 0.0 { &lowest[..] } else { smallest };
+    let hi = if base58_diff(&highest, largest) < 0.0 { &highest[..] } else { largest };
+
+    let matching = base58_diff(hi, lo);
+    if matching <= 0.0 {
+        return Err(Error::InvalidInput(format!("No address can start with {}{}. The character after '{}' has to be between '{}' and '{}'",
+                        fixed, prefix, fixed, &smallest[2..3], &largest[2..3])));
+    }
+
+    Ok(base58_diff(largest, smallest) / matching)
+}
+
+/// a - b, for base58 strings of the same length. The digits are subtracted one by one, so the (large) common prefix
+/// doesn't lose any precision in the result.
+fn base58_diff(a: &str, b: &str) -> f64 {
+    a.chars().zip(b.chars()).fold(0.0, |acc, (x, y)| {
+        let dx = BASE58_ALPHABET.find(x).unwrap_or(0) as f64;
+        let dy = BASE58_ALPHABET.find(y).unwrap_or(0) as f64;
+        acc * 58.0 + (dx - dy)
+    })
+}
+
+/// What the vanity threads grind through to find a matching address
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VanityMode {
+    /// A new HD seed for every try. Each vanity address gets its own seed and keys.
+    Seeds,
+    /// The diversifier indexes of a single ZIP-32 account. The vanity addresses are diversified addresses that share
+    /// the account's seed, spending key and viewing key, and can be restored from the seed and the diversifier index.
+    /// This is much faster, since only the diversifier is recomputed for every try. Only for Sapling addresses.
+    Diversifier,
+}
+
+/// Options for `generate_vanity_wallet`
+#[derive(Clone, Debug, PartialEq)]
+pub struct VanityConfig {
+    pub network     : Network,
+    /// Generate t addresses instead of Sapling addresses
+    pub is_taddr    : bool,
+    pub num_threads : u32,
+    pub mode        : VanityMode,
+    pub patterns    : Vec<VanityPattern>,
+    /// Keep going until there is an address for every pattern, instead of stopping at the first match
+    pub collect_all : bool,
+    /// Stop the search after this long
+    pub time_limit  : Option<Duration>,
+    /// Stop the search after checking this many addresses (including the ones checked before a resume). The threads
+    /// report in batches of 5000, so a few more addresses than this may be checked.
+    pub max_attempts: Option<u64>,
+    /// Save the progress of the search to a file, so it can be resumed with `resume_vanity_wallet`
+    pub checkpoint  : Option<CheckpointConfig>,
+}
+
+impl VanityConfig {
+    /// A single threaded search through new seeds for the first address that matches any of the patterns, without
+    /// any limits
+    pub fn new(network: Network, is_taddr: bool, patterns: Vec<VanityPattern>) -> VanityConfig {
+        VanityConfig {
+            network,
+            is_taddr,
+            num_threads : 1,
+            mode        : VanityMode::Seeds,
+            patterns,
+            collect_all : false,
+            time_limit  : None,
+            max_attempts: None,
+            checkpoint  : None,
+        }
+    }
+}
+
+/// How far along a vanity search is
+#[derive(Clone, Debug, PartialEq)]
+pub struct VanityProgress {
+    /// Addresses checked so far, across all the threads
+    pub attempts         : u64,
+    /// Addresses checked per second
+    pub rate             : f64,
+    pub elapsed          : Duration,
+    /// The expected number of addresses to check, from `vanity_difficulty` (or the hardest pattern with `collect_all`)
+    pub expected_attempts: f64,
+    /// Time to check `expected_attempts` addresses at the current rate. Every address is an independent try, so this
+    /// doesn't go down as the search goes on.
+    pub eta              : Option<Duration>,
+    /// Number of patterns that have an address so far
+    pub found            : usize,
+}
+
+/// Events reported to the observer of `generate_vanity_wallet`
+pub enum VanityEvent<'a> {
+    /// More addresses were checked
+    Progress(&'a VanityProgress),
+    /// The first address that matches the pattern was found
+    Found(&'a VanityPattern, &'a WalletEntry),
+}
+
+/// Stops a running `generate_vanity_wallet` from another thread. All the clones of a handle share the same flag.
+#[derive(Clone, Debug, Default)]
+pub struct CancelHandle(Arc<AtomicBool>);
+
+impl CancelHandle {
+    pub fn new() -> CancelHandle {
+        CancelHandle::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Generate vanity addresses that match the patterns in the config.
+///
+/// Normally the search stops at the first address that matches any of the patterns. If `collect_all` is set, it keeps
+/// going until it has found an address for every pattern. The `num` of each address is the index of the pattern it
+/// matched.
+///
+/// The starting seeds of the threads (and the salts and nonces of the checkpoints) come from `source`. If it fails,
+/// the search doesn't start.
+///
+/// The `observer` is called on this thread with the progress of the search, and with every address as it is found.
+/// If the search is cancelled, or runs into the time or attempt limit, `Error::Cancelled` is returned. Any addresses
+/// found until then were already passed to the observer, and are in the checkpoint if there is one.
+pub fn generate_vanity_wallet<F>(config: &VanityConfig, source: &mut dyn EntropySource, cancel: &CancelHandle, observer: F) -> Result<PaperWallet, Error>
+        where F: FnMut(VanityEvent) {
+    if config.num_threads == 0 {
+        return Err(Error::InvalidInput("Need at least 1 thread for the vanity search".to_string()));
+    }
+
+    if config.mode == VanityMode::Diversifier && config.is_taddr {
+        return Err(Error::InvalidInput("Can't search diversifiers for t addresses".to_string()));
+    }
+
+    // Get 32 bytes of entropy for each thread. When searching diversifiers, all the threads share one seed
+    let mut seed: [u8; 32] = [0; 32];
+    let mut threads = Vec::new();
+    for i in 0..config.num_threads {
+        if config.mode == VanityMode::Seeds || i == 0 {
+            source.fill_bytes(&mut seed)?;
+        }
+        threads.push(ThreadState { seed, checked: 0 });
+    }
+
+    let state = VanityState {
+        network    : config.network.clone(),
+        is_taddr   : config.is_taddr,
+        mode       : config.mode,
+        patterns   : config.patterns.clone(),
+        collect_all: config.collect_all,
+        threads,
+        elapsed    : Duration::from_secs(0),
+        found      : vec![None; config.patterns.len()],
+    };
+
+    run_vanity_search(config, state, source, cancel, observer)
+}
+
+/// Carry on with a search from a checkpoint loaded with `load_checkpoint`. The threads continue exactly where they
+/// left off, so the search finds the same addresses it would have found without the interruption. Addresses found
+/// before the interruption are in the returned wallet, but aren't passed to the observer again. The `source` is only
+/// used for encrypting the checkpoints.
+pub fn resume_vanity_wallet<F>(config: &VanityConfig, state: VanityState, source: &mut dyn EntropySource, cancel: &CancelHandle, observer: F) -> Result<PaperWallet, Error>
+        where F: FnMut(VanityEvent) {
+    if config.network != state.network || config.is_taddr != state.is_taddr || config.mode != state.mode || config.patterns != state.patterns
+            || config.collect_all != state.collect_all || config.num_threads as usize != state.threads.len() {
+        return Err(Error::InvalidInput("The vanity config doesn't match the checkpoint".to_string()));
+    }
+
+    run_vanity_search(config, state, source, cancel, observer)
+}
+
+fn run_vanity_search<F>(config: &VanityConfig, mut state: VanityState, source: &mut dyn EntropySource, cancel: &CancelHandle, mut observer: F) -> Result<PaperWallet, Error>
+        where F: FnMut(VanityEvent) {
+    let network = &config.network;
+    let is_taddr = config.is_taddr;
+    let patterns = &config.patterns;
+
+    // Test the patterns first, and calculate the estimated time. When collecting all of them, the hardest pattern
+    // takes the longest
+    let mut expected_combinations = vanity_difficulty(network, is_taddr, patterns)?;
+    if config.collect_all {
+        for pattern in patterns.iter() {
+            expected_combinations = expected_combinations.max(pattern_difficulty(network, is_taddr, pattern)?);
+        }
+    }
+
+    // About half the diversifier indexes don't give a valid address
+    if config.mode == VanityMode::Diversifier {
+        expected_combinations = expected_combinations * 2.0;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let please_stop = Arc::new(AtomicBool::new(false));
+
+    let mut handles = Vec::new();
+
+    for (i, start) in state.threads.iter().enumerate() {
+        let config_local = config.clone();
+        let start_local = start.clone();
+        let tx_local = mpsc::Sender::clone(&tx);
+        let ps_local = please_stop.clone();
+
+        let handle = thread::spawn(move || {
+            vanity_thread(config_local, i, start_local, tx_local, ps_local);
+        });
+        handles.push(handle);
+    }
+
+    // Drop our sender, so that we notice if all the threads exit without finding anything
+    drop(tx);
+
+    let resumed_attempts = state.attempts();
+    let resumed_elapsed = state.elapsed;
+    let mut progress = VanityProgress {
+        attempts         : resumed_attempts,
+        rate             : 0.0,
+        elapsed          : resumed_elapsed,
+        expected_attempts: expected_combinations,
+        eta              : None,
+        found            : state.found.iter().filter(|e| e.is_some()).count(),
+    };
+    let now = Instant::now();
+    let mut last_checkpoint = Instant::now();
+
+    let result: Result<PaperWallet, Error>;
+
+    loop {
+        if cancel.is_cancelled() {
+            result = Err(Error::Cancelled("The vanity search was cancelled".to_string()));
+            break;
+        }
+        if config.time_limit.map(|limit| now.elapsed() >= limit).unwrap_or(false) {
+            result = Err(Error::Cancelled(format!("Stopped the vanity search after {} seconds", now.elapsed().as_secs())));
+            break;
+        }
+        if config.max_attempts.map(|max| progress.attempts >= max).unwrap_or(false) {
+            result = Err(Error::Cancelled(format!("Stopped the vanity search after checking {} addresses", progress.attempts)));
+            break;
+        }
+
+        if let Some(checkpoint) = &config.checkpoint {
+            if last_checkpoint.elapsed() >= checkpoint.interval {
+                state.elapsed = resumed_elapsed + now.elapsed();
+                if let Err(e) = save_checkpoint(&state, checkpoint, source) {
+                    result = Err(e);
+                    break;
+                }
+                last_checkpoint = Instant::now();
+            }
+        }
+
+        // Wake up regularly to check for cancellation, even if the threads are slow to report
+        let recv = match rx.recv_timeout(Duration::from_millis(100)) {
+            Ok(r)   => r,
+            Err(mpsc::RecvTimeoutError::Timeout) => continue,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                result = Err(Error::Derivation("All vanity threads exited without finding an address".to_string()));
+                break;
+            }
+        };
+
+        match recv {
+            VanityMessage::Processed(thread, checked) => {
+                state.threads[thread].checked = checked;
+                progress.attempts = state.attempts();
+                progress.elapsed = resumed_elapsed + now.elapsed();
+
+                let secs = now.elapsed().as_secs_f64();
+                progress.rate = if secs > 0.0 { (progress.attempts - resumed_attempts) as f64 / secs } else { 0.0 };
+                progress.eta = if progress.rate > 0.0 {
+                    // The cast saturates, so astronomically long searches don't overflow the Duration
+                    Some(Duration::from_secs((expected_combinations / progress.rate) as u64))
+                } else {
+                    None
+                };
+
+                observer(VanityEvent::Progress(&progress));
+            },
+            VanityMessage::Found(idx, entry) => {
+                if state.found[idx].is_none() {
+                    observer(VanityEvent::Found(&patterns[idx], &entry));
+                    state.found[idx] = Some(entry);
+                    progress.found = progress.found + 1;
+                }
+
+                // Found a solution
+                if !config.collect_all || progress.found == patterns.len() {
+                    let mut wallet = PaperWallet::new(state.found.iter().flatten().cloned().collect());
+                    wallet.ticker = params(&config.network).ticker;
+                    result = Ok(wallet);
+                    break;
+                }
+            },
+            VanityMessage::Failed(e) => {
+                result = Err(e);
+                break;
+            }
+        }
+    }
+
+    please_stop.store(true, Ordering::Relaxed);
+    for handle in handles {
+        if handle.join().is_err() {
+            return Err(Error::Derivation("A vanity thread panicked".to_string()));
+        }
+    }
+
+    // Save where the search stopped, so it can be resumed
+    if let (Err(Error::Cancelled(_)), Some(checkpoint)) = (&result, &config.checkpoint) {
+        state.elapsed = resumed_elapsed + now.elapsed();
+        save_checkpoint(&state, checkpoint, source)?;
+    }
+
+    return result;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::paper::Network;
+    use crate::vanity::VanityPattern;
+    use crate::wallet::PaperWallet;
+    use crate::error::Error;
+
+    /// Run a vanity search without any limits, ignoring the progress
+    fn vanity(network: Network, is_taddr: bool, num_threads: u32, patterns: Vec<VanityPattern>, collect_all: bool) -> Result<PaperWallet, Error> {
+        use crate::vanity::{generate_vanity_wallet, VanityConfig, CancelHandle};
+        use crate::entropy::SystemEntropy;
+
+        let mut config = VanityConfig::new(network, is_taddr, patterns);
+        config.num_threads = num_threads;
+        config.collect_all = collect_all;
+
+        generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {})
+    }
+
+    #[test]
+    fn test_vanity() {
+        let prefix = |s: &str| vec![VanityPattern::Prefix(s.to_string())];
+
+        // Single thread
+        let td = vanity(Network::Main, false, 1, prefix("te"), false).unwrap().to_json();
+        assert_eq!(td.len(), 1);
+        assert!(td[0]["address"].as_str().unwrap().starts_with("zs1te"));
+
+        // Multi thread
+        let td = vanity(Network::Main, false, 4, prefix("tt"), false).unwrap().to_json();
+        assert_eq!(td.len(), 1);
+        assert!(td[0]["address"].as_str().unwrap().starts_with("zs1tt"));
+
+        // Testnet
+        let td = vanity(Network::Test, false, 4, prefix("ts"), false).unwrap().to_json();
+        assert_eq!(td.len(), 1);
+        assert!(td[0]["address"].as_str().unwrap().starts_with("ztestsapling1ts"));
+
+        // Test for invalid chars
+        vanity(Network::Main, false, 1, prefix("b"), false).expect_err("b is not allowed");
+        vanity(Network::Main, false, 1, prefix("o"), false).expect_err("o is not allowed");
+        vanity(Network::Main, false, 1, prefix("i"), false).expect_err("i is not allowed");
+        vanity(Network::Main, false, 1, prefix("1"), false).expect_err("1 is not allowed");
+        vanity(Network::Main, false, 1, prefix("é"), false).expect_err("Non-ascii chars are not allowed");
+        vanity(Network::Main, false, 1, prefix("qqqqqqqqqqqqqqqqqq"), false).expect_err("Prefix is too long");
+        vanity(Network::Main, false, 1, prefix(""), false).expect_err("Empty prefix");
+        vanity(Network::Main, false, 1, vec![], false).expect_err("No patterns");
+    }
+
+    #[test]
+    fn test_vanity_recoverable() {
+        use crate::paper::restore_wallet;
+        use crate::verify::verify_wallet;
+
+        // Vanity addresses have their viewing keys, and can be restored from the HD seed like any other address
+        let w = vanity(Network::Main, false, 2, vec![VanityPattern::Prefix("z".to_string())], false).unwrap();
+        let j = w.to_json();
+        assert!(j[0]["viewing_key"].as_str().unwrap().starts_with("zxviews"));
+        assert!(j[0]["incoming_viewing_key"].as_str().unwrap().starts_with("zivks"));
+        assert_eq!(j[0]["seed"]["path"], "m/32'/133'/0'");
+        assert!(verify_wallet(&w, "", None).iter().all(|r| r.is_ok()));
+
+        let restored = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Main, 0..1, 0, 0, "").unwrap();
+        assert_eq!(restored.entries[0], w.entries[0]);
+
+        let w = vanity(Network::Test, true, 2, vec![VanityPattern::Prefix("b".to_string())], false).unwrap();
+        let j = w.to_json();
+        assert_eq!(j[0]["seed"]["path"], "m/44'/1'/0'/0/0");
+        assert!(verify_wallet(&w, "", None).iter().all(|r| r.is_ok()));
+
+        let restored = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Test, 0..1, 1, 0, "").unwrap();
+        assert_eq!(restored.entries[1], w.entries[0]);
+    }
+
+    #[test]
+    fn test_vanity_patterns() {
+        use crate::vanity::{vanity_difficulty, pattern_difficulty};
+        use crate::verify::verify_wallet;
+
+        // Suffix and substring
+        let w = vanity(Network::Main, false, 4, vec![VanityPattern::Suffix("qq".to_string())], false).unwrap();
+        assert!(w.entries[0].address().ends_with("qq"));
+        assert!(verify_wallet(&w, "", None)[0].is_ok());
+
+        let w = vanity(Network::Test, false, 4, vec![VanityPattern::Contains("XY".to_string())], false).unwrap();
+        assert!(w.entries[0].address()["ztestsapling1".len()..].contains("xy"));
+
+        let w = vanity(Network::Main, true, 4, vec![VanityPattern::Suffix("ab".to_string())], false).unwrap();
+        assert!(w.entries[0].address().ends_with("ab"));
+        assert!(verify_wallet(&w, "", None)[0].is_ok());
+
+        // First match wins, and its num is the pattern that matched
+        let patterns = vec![VanityPattern::Prefix("qqqqqq".to_string()), VanityPattern::Contains("z".to_string())];
+        let w = vanity(Network::Main, false, 1, patterns, false).unwrap();
+        assert_eq!(w.len(), 1);
+        assert_eq!(w.entries[0].num(), 1);
+        assert!(w.entries[0].address()[3..].contains("z"));
+
+        // Collect an address for every pattern
+        let patterns = vec![VanityPattern::Prefix("a".to_string()), VanityPattern::Suffix("c".to_string()),
+                            VanityPattern::Contains("dd".to_string())];
+        let w = vanity(Network::Main, false, 4, patterns, true).unwrap();
+        assert_eq!(w.len(), 3);
+        assert!(w.entries[0].address().starts_with("zs1a"));
+        assert!(w.entries[1].address().ends_with("c"));
+        assert!(w.entries[2].address()[3..].contains("dd"));
+        assert_eq!(w.entries.iter().map(|e| e.num()).collect::<Vec<_>>(), vec![0, 1, 2]);
+
+        let w = vanity(Network::Main, true, 4, vec![VanityPattern::Prefix("L".to_string()), VanityPattern::Contains("zz".to_string())], true).unwrap();
+        assert!(w.entries[0].address().starts_with("t1L"));
+        assert!(w.entries[1].address()[2..].contains("zz"));
+
+        // Difficulty of each pattern, and of the combination
+        let p = pattern_difficulty(&Network::Main, false, &VanityPattern::Prefix("abc".to_string())).unwrap();
+        assert_eq!(p, 32768.0);
+        assert_eq!(pattern_difficulty(&Network::Main, false, &VanityPattern::Suffix("abc".to_string())).unwrap(), p);
+        assert_eq!(pattern_difficulty(&Network::Main, false, &VanityPattern::Contains("abc".to_string())).unwrap(), p / 73.0);
+        let both = vec![VanityPattern::Prefix("abc".to_string()), VanityPattern::Suffix("xyz".to_string())];
+        assert_eq!(vanity_difficulty(&Network::Main, false, &both).unwrap(), p / 2.0);
+        assert_eq!(pattern_difficulty(&Network::Main, true, &VanityPattern::Suffix("ab".to_string())).unwrap(), 58.0 * 58.0);
+
+        // Sapling suffixes can't go past the padding bit of the last data character
+        pattern_difficulty(&Network::Main, false, &VanityPattern::Suffix("qqqqqqq".to_string())).unwrap();
+        pattern_difficulty(&Network::Main, false, &VanityPattern::Suffix("pqqqqqq".to_string())).expect_err("Odd padding character");
+        pattern_difficulty(&Network::Main, false, &VanityPattern::Contains("q".repeat(76))).expect_err("Too long");
+        pattern_difficulty(&Network::Main, true, &VanityPattern::Suffix("0".to_string())).expect_err("Not base58");
+        pattern_difficulty(&Network::Main, true, &VanityPattern::Contains("a".repeat(34))).expect_err("Too long");
+    }
+
+    #[test]
+    fn test_vanity_taddr() {
+        use crate::vanity::vanity_difficulty;
+        use crate::verify::verify_wallet;
+
+        let prefix = |s: &str| vec![VanityPattern::Prefix(s.to_string())];
+
+        let w = vanity(Network::Main, true, 4, prefix("L"), false).unwrap();
+        let td = w.to_json();
+        assert_eq!(td.len(), 1);
+        assert_eq!(td[0]["type"], "taddr");
+        assert!(td[0]["address"].as_str().unwrap().starts_with("t1L"));
+        assert!(verify_wallet(&w, "", None)[0].is_ok());
+
+        let td = vanity(Network::Test, true, 1, prefix("b"), false).unwrap().to_json();
+        assert!(td[0]["address"].as_str().unwrap().starts_with("tmb"));
+
+        // Mainnet t addresses are between t1Hsc1... and t1hDCz..., so about 1 in 24 starts with any of the characters
+        // in between
+        let d = vanity_difficulty(&Network::Main, true, &prefix("L")).unwrap();
+        assert!(d > 20.0 && d < 30.0);
+        let d2 = vanity_difficulty(&Network::Main, true, &prefix("Lz")).unwrap();
+        assert!((d2 / d - 58.0).abs() < 1.0);
+
+        // The edges are partially reachable, so they are harder
+        assert!(vanity_difficulty(&Network::Main, true, &prefix("H")).unwrap() > d);
+        assert!(vanity_difficulty(&Network::Main, true, &prefix("h")).unwrap() > d);
+
+        // Unreachable prefixes
+        vanity_difficulty(&Network::Main, true, &prefix("A")).expect_err("Smaller than all addresses");
+        vanity_difficulty(&Network::Main, true, &prefix("z")).expect_err("Larger than all addresses");
+        vanity_difficulty(&Network::Main, true, &prefix("Hs9")).expect_err("Just below the smallest address");
+        vanity_difficulty(&Network::Test, true, &prefix("L")).unwrap();
+
+        // Invalid characters
+        for c in ["0", "O", "I", "l", "é"].iter() {
+            vanity(Network::Main, true, 1, prefix(*c), false).expect_err("Not base58");
+        }
+        vanity(Network::Main, true, 1, prefix("L".repeat(34).as_str()), false).expect_err("Prefix is too long");
+    }
+
+    #[test]
+    fn test_vanity_progress_and_cancel() {
+        use std::time::{Duration, Instant};
+        use crate::vanity::{generate_vanity_wallet, VanityConfig, VanityEvent, CancelHandle};
+        use crate::entropy::SystemEntropy;
+
+        let impossible = vec![VanityPattern::Prefix("LLLLLLLLLLLL".to_string())];
+
+        // Attempt limit
+        let mut config = VanityConfig::new(Network::Main, true, impossible.clone());
+        config.max_attempts = Some(20000);
+        let mut attempts = 0;
+        let r = generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |e| if let VanityEvent::Progress(p) = e {
+            assert!(p.attempts > attempts);
+            assert!(p.expected_attempts > 1e20);
+            attempts = p.attempts;
+        });
+        match r {
+            Err(Error::Cancelled(_)) => assert!(attempts >= 20000),
+            _                        => panic!("Expected the search to stop at the attempt limit")
+        }
+
+        // Time limit
+        let mut config = VanityConfig::new(Network::Main, false, impossible.clone());
+        config.num_threads = 2;
+        config.time_limit = Some(Duration::from_millis(300));
+        let start = Instant::now();
+        match generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}) {
+            Err(Error::Cancelled(_)) => assert!(start.elapsed() < Duration::from_secs(10)),
+            _                        => panic!("Expected the search to stop at the time limit")
+        }
+
+        // Cancel from the observer, and from another thread
+        let config = VanityConfig::new(Network::Main, true, impossible);
+        let cancel = CancelHandle::new();
+        let c = cancel.clone();
+        match generate_vanity_wallet(&config, &mut SystemEntropy, &cancel, |e| if let VanityEvent::Progress(_) = e { c.cancel() }) {
+            Err(Error::Cancelled(_)) => assert!(cancel.is_cancelled()),
+            _                        => panic!("Expected the search to be cancelled")
+        }
+
+        let cancel = CancelHandle::new();
+        let c = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            c.cancel();
+        });
+        generate_vanity_wallet(&config, &mut SystemEntropy, &cancel, |_| {}).expect_err("Cancelled from another thread");
+
+        // Every pattern's address is reported as it is found
+        let mut config = VanityConfig::new(Network::Main, true, vec![VanityPattern::Prefix("L".to_string()), VanityPattern::Suffix("z".to_string())]);
+        config.collect_all = true;
+        let mut found = vec![];
+        let w = generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |e| if let VanityEvent::Found(p, entry) = e {
+            found.push((p.clone(), entry.clone()));
+        }).unwrap();
+        assert_eq!(found.len(), 2);
+        for (pattern, entry) in found {
+            assert_eq!(&w.entries[entry.num() as usize], &entry);
+            assert_eq!(config.patterns[entry.num() as usize], pattern);
+        }
+    }
+
+    #[test]
+    fn test_vanity_diversified() {
+        use crate::paper::restore_wallet;
+        use crate::vanity::{generate_vanity_wallet, VanityConfig, VanityMode, CancelHandle};
+        use crate::entropy::{SystemEntropy, NoEntropy};
+        use crate::verify::verify_wallet;
+        use crate::wallet::WalletEntry;
+
+        let patterns = vec![
+            VanityPattern::Prefix("zz".to_string()),
+            VanityPattern::Suffix("q".to_string()),
+            VanityPattern::Contains("xy".to_string()),
+        ];
+        let mut config = VanityConfig::new(Network::Main, false, patterns);
+        config.num_threads = 3;
+        config.mode        = VanityMode::Diversifier;
+        config.collect_all = true;
+
+        // All the addresses are diversified addresses of the same account
+        let w = generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap();
+        assert_eq!(w.entries.len(), 3);
+        assert!(w.entries[0].address().starts_with("zs1zz"));
+        assert!(w.entries[1].address().ends_with("q"));
+        assert!(w.entries[2].address()[4..].contains("xy"));
+        assert!(w.entries.iter().all(|e| e.private_key() == w.entries[0].private_key()));
+        assert!(verify_wallet(&w, "", None).iter().all(|r| r.is_ok()));
+
+        // The account is the first one of the seed
+        let j = w.to_json();
+        assert_eq!(j[0]["seed"]["path"], "m/32'/133'/0'");
+        let restored = restore_wallet(j[0]["seed"]["HDSeed"].as_str().unwrap(), &Network::Main, 0..1, 0, 0, "").unwrap();
+        assert_eq!(restored.entries[0].private_key(), w.entries[0].private_key());
+        for e in w.entries.iter() {
+            match e {
+                WalletEntry::Sapling(e) => assert!(e.diversifier_index.is_some()),
+                _                       => panic!("Expected a Sapling address")
+            }
+        }
+
+        // t addresses don't have diversifiers
+        let mut config = VanityConfig::new(Network::Main, true, vec![VanityPattern::Prefix("a".to_string())]);
+        config.mode = VanityMode::Diversifier;
+        generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}).expect_err("No diversifiers for t addresses");
+
+        // The search doesn't start without entropy
+        let config = VanityConfig::new(Network::Main, false, vec![VanityPattern::Prefix("a".to_string())]);
+        match generate_vanity_wallet(&config, &mut NoEntropy, &CancelHandle::new(), |_| {}) {
+            Err(Error::Rng(_)) => {},
+            r                  => panic!("Expected an entropy error, got {:?}", r.map(|w| w.to_json().dump()))
+        }
+    }
+
+    #[test]
+    fn test_advance() {
+        use crate::vanity::advance;
+        use crate::paper::increment;
+
+        for start in [[0u8; 32], [0xff; 32], [0x7f; 32]].iter() {
+            let mut s1 = *start;
+            s1[31] = 0;
+            let mut s2 = s1;
+            for n in 0..600u64 {
+                let mut s3 = s1;
+                advance(&mut s3, n).unwrap();
+                assert_eq!(s2, s3);
+                increment(&mut s2).unwrap();
+            }
+        }
+
+        let mut s = [0u8; 32];
+        advance(&mut s, u64::max_value()).unwrap();
+        assert_eq!(&s[..8], &[0xff; 8]);
+        assert_eq!(&s[8..], &[0; 24]);
+
+        advance(&mut [0xff; 32], 1).expect_err("Overflow");
+    }
+
+    #[test]
+    fn test_vanity_resume() {
+        use crate::checkpoint::{load_checkpoint, CheckpointConfig, ThreadState, VanityState};
+        use crate::paper::{increment, get_taddress};
+        use crate::vanity::{generate_vanity_wallet, resume_vanity_wallet, VanityConfig, VanityMode, CancelHandle};
+        use crate::entropy::SystemEntropy;
+
+        // Find the first seed after the start that gives a t1L... address
+        let start = [42u8; 32];
+        let mut seed = start;
+        let mut m = 0;
+        let expected = loop {
+            increment(&mut seed).unwrap();
+            m = m + 1;
+            let (addr, _, _) = get_taddress(&Network::Main, &seed, 0).unwrap();
+            if addr.starts_with("t1L") {
+                break addr;
+            }
+        };
+
+        // Resuming from just before it finds the same address, and resuming after it finds a later one
+        let config = VanityConfig::new(Network::Main, true, vec![VanityPattern::Prefix("L".to_string())]);
+        let state = |checked| VanityState {
+            network    : Network::Main,
+            is_taddr   : true,
+            mode       : VanityMode::Seeds,
+            patterns   : config.patterns.clone(),
+            collect_all: false,
+            threads    : vec![ThreadState { seed: start, checked }],
+            elapsed    : Default::default(),
+            found      : vec![None],
+        };
+        let w = resume_vanity_wallet(&config, state(0), &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap();
+        assert_eq!(w.entries[0].address(), expected);
+        let w = resume_vanity_wallet(&config, state(m - 1), &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap();
+        assert_eq!(w.entries[0].address(), expected);
+        let w = resume_vanity_wallet(&config, state(m), &mut SystemEntropy, &CancelHandle::new(), |_| {}).unwrap();
+        assert_ne!(w.entries[0].address(), expected);
+
+        let mut other = config.clone();
+        other.network = Network::Test;
+        resume_vanity_wallet(&other, state(0), &mut SystemEntropy, &CancelHandle::new(), |_| {}).expect_err("Different network");
+
+        // Stop a search at the attempt limit, and carry on from the checkpoint
+        let path = std::env::temp_dir().join(format!("zecpaper-vanity-checkpoint-{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut config = VanityConfig::new(Network::Main, true, vec![VanityPattern::Prefix("LLLLLLLLLLLL".to_string())]);
+        config.num_threads  = 2;
+        config.max_attempts = Some(10000);
+        config.checkpoint   = Some(CheckpointConfig::new(path, "pass"));
+        match generate_vanity_wallet(&config, &mut SystemEntropy, &CancelHandle::new(), |_| {}) {
+            Err(Error::Cancelled(_)) => (),
+            _                        => panic!("Expected the search to stop at the attempt limit")
+        }
+
+        let (mut resumed, state) = load_checkpoint(path, "pass").unwrap();
+        assert_eq!(state.threads.len(), 2);
+        assert!(state.attempts() >= 10000);
+        assert_eq!(resumed.patterns, config.patterns);
+
+        resumed.max_attempts = Some(state.attempts() + 10000);
+        match resume_vanity_wallet(&resumed, state.clone(), &mut SystemEntropy, &CancelHandle::new(), |_| {}) {
+            Err(Error::Cancelled(_)) => (),
+            _                        => panic!("Expected the search to stop at the attempt limit")
+        }
+
+        let (_, state2) = load_checkpoint(path, "pass").unwrap();
+        assert!(state2.attempts() >= state.attempts() + 10000);
+        assert_eq!(state2.threads[0].seed, state.threads[0].seed);
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,376 @@
+use bip39::Mnemonic;
+use crate::paper::{decode_privatekey, decode_taddr_privatekey, decode_orchard_privatekey, encode_address,
+                   encode_diversified_address, encode_viewingkey, encode_account_keys, encode_taddress,
+                   encode_uaddress, get_zaddress, get_taddress, get_uaddress, Network};
+use crate::wallet::{PaperWallet, WalletEntry, SaplingEntry, TransparentEntry, UnifiedEntry, HDSeed};
+use crate::keycrypt::is_encrypted_entry;
+use crate::error::Error;
+
+/// The result of verifying a single entry of a wallet. If there are no problems, the address, viewing keys and
+/// HD path were all re-derived from the private key (and seed) and matched what was in the wallet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntryReport {
+    pub num     : u32,
+    pub address : String,
+    pub problems: Vec<String>,
+}
+
+impl EntryReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Verify that every entry in the wallet is internally consistent. Each private key is decoded, and the address and
+/// viewing keys are re-derived from it. If the entry has an HD seed, the private key is also re-derived from the seed
+/// and path. The `passphrase` is used to check the seed against the mnemonic words, if there are any.
+/// 
+/// The keys of a custom coin are only recognized if its network is passed as the `hint`. Wallets encrypted with
+/// `--encrypt` have to be decrypted with `keycrypt::decrypt_wallet` first, otherwise their entries are reported as
+/// encrypted instead of being checked.
+pub fn verify_wallet(wallet: &PaperWallet, passphrase: &str, hint: Option<&Network>) -> Vec<EntryReport> {
+    wallet.entries.iter().map(|entry| {
+        let problems = match verify_entry(entry, passphrase, hint) {
+            Ok(p)  => p,
+            Err(e) => vec![format!("{}", e)]
+        };
+
+        EntryReport {
+            num     : entry.num(),
+            address : entry.address().to_string(),
+            problems,
+        }
+    }).collect()
+}
+
+/// Decode a single Sapling spending key or WIF private key, typed in from a paper wallet, and derive its (default)
+/// address and viewing keys. The keys of a custom coin are only recognized if its network is passed as the `hint`.
+pub fn verify_key(private_key: &str, hint: Option<&Network>) -> Result<WalletEntry, Error> {
+    let private_key = private_key.trim();
+
+    if let Ok((spk, network)) = decode_privatekey(private_key, hint) {
+        return Ok(WalletEntry::Sapling(SaplingEntry {
+            num         : 0,
+            address     : encode_address(&spk, &network)?,
+            private_key : private_key.to_string(),
+            viewing_key : Some(encode_viewingkey(&spk, &network)?),
+            account_keys: Some(encode_account_keys(&spk, &network)?),
+            seed        : None,
+            diversifier_index: None,
+        }));
+    }
+
+    if decode_orchard_privatekey(private_key, hint).is_ok() {
+        return Err(Error::InvalidInput("The unified address can't be derived from the Orchard key alone. Verify it along with its Sapling key".to_string()));
+    }
+
+    match decode_taddr_privatekey(private_key, hint) {
+        Ok((sk, network)) => {
+            let (address, _) = encode_taddress(&sk, &network);
+            Ok(WalletEntry::Transparent(TransparentEntry {
+                num         : 0,
+                address,
+                private_key : private_key.to_string(),
+                seed        : None,
+            }))
+        },
+        Err(_) => Err(Error::InvalidInput("Not a Sapling spending key or a WIF private key".to_string()))
+    }
+}
+
+/// Decode the Orchard and Sapling private keys of a unified address, typed in from a paper wallet, and derive the
+/// unified address and unified full viewing key.
+pub fn verify_unified_key(orchard_key: &str, sapling_key: &str, hint: Option<&Network>) -> Result<WalletEntry, Error> {
+    let (orchard_key, sapling_key) = (orchard_key.trim(), sapling_key.trim());
+    let (address, viewing_key) = derive_unified(orchard_key, sapling_key, hint)?;
+
+    Ok(WalletEntry::Unified(UnifiedEntry {
+        num         : 0,
+        address,
+        private_key : orchard_key.to_string(),
+        sapling_private_key: sapling_key.to_string(),
+        viewing_key,
+        seed        : None,
+    }))
+}
+
+fn verify_entry(entry: &WalletEntry, passphrase: &str, hint: Option<&Network>) -> Result<Vec<String>, Error> {
+    if is_encrypted_entry(entry) {
+        return Err(Error::InvalidInput("The private keys are encrypted, decrypt the wallet to verify them".to_string()));
+    }
+
+    let mut problems = vec![];
+
+    match entry {
+        WalletEntry::Sapling(e)     => verify_sapling(e, hint, &mut problems)?,
+        WalletEntry::Transparent(e) => verify_transparent(e, hint, &mut problems)?,
+        WalletEntry::Unified(e)     => verify_unified(e, hint, &mut problems)?,
+    }
+
+    if let Some(seed) = entry.seed() {
+        verify_mnemonic(seed, passphrase, &mut problems);
+    }
+
+    Ok(problems)
+}
+
+fn verify_sapling(e: &SaplingEntry, hint: Option<&Network>, problems: &mut Vec<String>) -> Result<(), Error> {
+    let (spk, network) = decode_privatekey(&e.private_key, hint)?;
+
+    let address = match e.diversifier_index {
+        Some(di) => encode_diversified_address(&spk, di, &network)?,
+        None     => encode_address(&spk, &network)?
+    };
+    check(problems, "address", &address, &e.address);
+
+    if let Some(vk) = &e.viewing_key {
+        check(problems, "viewing key", &encode_viewingkey(&spk, &network)?, vk);
+    }
+
+    if let Some(keys) = &e.account_keys {
+        let derived = encode_account_keys(&spk, &network)?;
+        check(problems, "incoming viewing key", &derived.incoming_viewing_key, &keys.incoming_viewing_key);
+        check(problems, "outgoing viewing key", &derived.outgoing_viewing_key, &keys.outgoing_viewing_key);
+        check(problems, "diversifier key", &derived.diversifier_key, &keys.diversifier_key);
+    }
+
+    if let Some(seed) = &e.seed {
+        let (_, pk, _, _, path) = get_zaddress(&network, &seed.seed, path_index(&seed.path)?)?;
+        check(problems, "HD path", &path.path, &seed.path);
+        check(problems, "private key derived from the HD seed", &pk, &e.private_key);
+    }
+
+    Ok(())
+}
+
+fn verify_transparent(e: &TransparentEntry, hint: Option<&Network>, problems: &mut Vec<String>) -> Result<(), Error> {
+    let (sk, network) = decode_taddr_privatekey(&e.private_key, hint)?;
+
+    let (address, _) = encode_taddress(&sk, &network);
+    check(problems, "address", &address, &e.address);
+
+    if let Some(seed) = &e.seed {
+        let (_, pk, path) = get_taddress(&network, &seed.seed, path_index(&seed.path)?)?;
+        check(problems, "HD path", &path.path, &seed.path);
+        check(problems, "private key derived from the HD seed", &pk, &e.private_key);
+    }
+
+    Ok(())
+}
+
+fn verify_unified(e: &UnifiedEntry, hint: Option<&Network>, problems: &mut Vec<String>) -> Result<(), Error> {
+    let (address, viewing_key) = derive_unified(&e.private_key, &e.sapling_private_key, hint)?;
+    check(problems, "address", &address, &e.address);
+    check(problems, "viewing key", &viewing_key, &e.viewing_key);
+
+    if let Some(seed) = &e.seed {
+        let (_, network) = decode_orchard_privatekey(&e.private_key, hint)?;
+        let (_, pk, sapling_pk, _, path) = get_uaddress(&network, &seed.seed, path_index(&seed.path)?)?;
+        check(problems, "HD path", &path.path, &seed.path);
+        check(problems, "private key derived from the HD seed", &pk, &e.private_key);
+        check(problems, "Sapling private key derived from the HD seed", &sapling_pk, &e.sapling_private_key);
+    }
+
+    Ok(())
+}
+
+/// The unified address and unified full viewing key of an Orchard and a Sapling private key, which have to be for the
+/// same network
+fn derive_unified(orchard_key: &str, sapling_key: &str, hint: Option<&Network>) -> Result<(String, String), Error> {
+    let (osk, network) = decode_orchard_privatekey(orchard_key, hint)?;
+    let (spk, sapling_network) = decode_privatekey(sapling_key, hint)?;
+    if network != sapling_network {
+        return Err(Error::InvalidInput(format!("The Orchard key is for {} but the Sapling key is for {}", network.name(), sapling_network.name())));
+    }
+
+    encode_uaddress(&osk, &spk, &network)
+}
+
+/// Check that the seed is the one derived from the mnemonic words and passphrase
+fn verify_mnemonic(seed: &HDSeed, passphrase: &str, problems: &mut Vec<String>) {
+    if let Some(words) = &seed.mnemonic {
+        match Mnemonic::parse(words.as_str()) {
+            Ok(m)  => if m.to_seed(passphrase)[..] != seed.seed[..] {
+                problems.push("The HD seed doesn't match the seed words and passphrase".to_string());
+            },
+            Err(e) => problems.push(format!("Invalid seed words: {}", e))
+        }
+    }
+}
+
+/// The index at the end of an HD path, for eg. 2 for "m/32'/133'/2'". The rest of the path is checked by comparing it
+/// with the path of the re-derived key.
+fn path_index(path: &str) -> Result<u32, Error> {
+    path.rsplit('/').next()
+        .map(|s| s.trim_end_matches('\''))
+        .and_then(|s| s.parse::<u32>().ok())
+        .ok_or_else(|| Error::Encoding(format!("Invalid HD path {}", path)))
+}
+
+fn check(problems: &mut Vec<String>, what: &str, derived: &str, found: &str) {
+    if derived != found {
+        problems.push(format!("The {} doesn't match. Expected {}, found {}", what, derived, found));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Test that generated wallets verify, and tampered ones don't
+    #[test]
+    fn test_verify_wallet() {
+        use crate::paper::{generate_wallet, WalletOptions, generate_diversified_wallet, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::verify::verify_wallet;
+        use crate::wallet::WalletEntry;
+
+        let w = generate_wallet(&WalletOptions { zcount: 2, tcount: 2, ucount: 1, mnemonic: Some("pass".to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        let reports = verify_wallet(&w, "pass", None);
+        assert_eq!(reports.len(), 5);
+        assert!(reports.iter().all(|r| r.is_ok()), "{:?}", reports);
+
+        // Wrong passphrase for the seed words
+        assert!(verify_wallet(&w, "", None).iter().all(|r| !r.is_ok()));
+
+        let w = generate_diversified_wallet(&Network::Test, 3, &mut SystemEntropy, &[], None).unwrap();
+        assert!(verify_wallet(&w, "", None).iter().all(|r| r.is_ok()));
+
+        let mut w = generate_wallet(&WalletOptions { tcount: 1, ucount: 1, ..WalletOptions::new(Network::Regtest) }, &mut SystemEntropy, &[]).unwrap();
+        assert!(verify_wallet(&w, "", None).iter().all(|r| r.is_ok()));
+
+        // A unified address is checked against both its keys
+        let other_pk = generate_wallet(&WalletOptions::new(Network::Regtest), &mut SystemEntropy, &[]).unwrap().entries[0].private_key().to_string();
+        if let WalletEntry::Unified(e) = &mut w.entries[2] {
+            e.sapling_private_key = other_pk;
+        }
+        let reports = verify_wallet(&w, "", None);
+        assert!(reports[..2].iter().all(|r| r.is_ok()));
+        assert_eq!(reports[2].problems.len(), 3);   // Address, viewing key and HD seed
+
+        // Swap the addresses and the private keys around
+        let mut w = generate_wallet(&WalletOptions { zcount: 2, tcount: 2, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap();
+        let other_address = w.entries[1].address().to_string();
+        let other_pk = w.entries[2].private_key().to_string();
+        if let WalletEntry::Sapling(e) = &mut w.entries[0] {
+            e.address = other_address;
+        }
+        if let WalletEntry::Transparent(e) = &mut w.entries[3] {
+            e.private_key = other_pk;
+        }
+        if let WalletEntry::Transparent(e) = &mut w.entries[2] {
+            e.seed.as_mut().unwrap().path = "m/44'/1'/0'/0/1".to_string();
+        }
+
+        let reports = verify_wallet(&w, "", None);
+        assert!(!reports[0].is_ok());
+        assert!(reports[1].is_ok());
+        assert!(!reports[2].is_ok());
+        assert!(!reports[3].is_ok());
+        assert_eq!(reports[3].problems.len(), 2);   // Address and HD seed
+
+        // Keys that can't be decoded are reported, not a panic
+        if let WalletEntry::Sapling(e) = &mut w.entries[1] {
+            e.private_key = "secret-extended-key-test1garbage".to_string();
+        }
+        assert!(!verify_wallet(&w, "", None)[1].is_ok());
+    }
+
+    /// Test that an encrypted wallet is reported as encrypted, and verifies once it is decrypted
+    #[test]
+    fn test_verify_encrypted() {
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::keycrypt::{encrypt_wallet, decrypt_wallet, is_encrypted_wallet};
+        use crate::verify::verify_wallet;
+
+        let w = generate_wallet(&WalletOptions { tcount: 1, ucount: 1, mnemonic: Some("".to_string()), ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        let encrypted = encrypt_wallet(&w, "correct horse", &mut SystemEntropy).unwrap();
+        assert!(is_encrypted_wallet(&encrypted));
+        assert!(!is_encrypted_wallet(&w));
+
+        let reports = verify_wallet(&encrypted, "", None);
+        assert_eq!(reports.len(), 3);
+        for report in reports.iter() {
+            assert_eq!(report.problems, vec!["The private keys are encrypted, decrypt the wallet to verify them".to_string()]);
+        }
+
+        let decrypted = decrypt_wallet(&encrypted, "correct horse").unwrap();
+        let reports = verify_wallet(&decrypted, "", None);
+        assert!(reports.iter().all(|r| r.is_ok()), "{:?}", reports);
+    }
+
+    #[test]
+    fn test_verify_key() {
+        use crate::paper::{generate_wallet, WalletOptions, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::verify::{verify_key, verify_unified_key};
+        use crate::wallet::WalletEntry;
+
+        let w = generate_wallet(&WalletOptions { tcount: 1, ucount: 1, ..WalletOptions::new(Network::Main) }, &mut SystemEntropy, &[]).unwrap();
+        for entry in w.entries[..2].iter() {
+            let v = verify_key(&format!("  {}\n", entry.private_key()), None).unwrap();
+            assert_eq!(v.address(), entry.address());
+            assert_eq!(v.viewing_key(), entry.viewing_key());
+        }
+
+        // Unified addresses need both the Orchard and the Sapling key
+        verify_key(w.entries[2].private_key(), None).expect_err("Orchard key");
+        let sapling_key = match &w.entries[2] {
+            WalletEntry::Unified(e) => e.sapling_private_key.clone(),
+            _ => panic!("Not a unified address")
+        };
+        let v = verify_unified_key(w.entries[2].private_key(), &format!("{}\n", sapling_key), None).unwrap();
+        assert_eq!(v.address(), w.entries[2].address());
+        assert_eq!(v.viewing_key(), w.entries[2].viewing_key());
+        verify_unified_key(w.entries[2].private_key(), w.entries[0].private_key(), None).expect("Same account");
+        verify_unified_key(w.entries[2].private_key(), w.entries[1].private_key(), None).expect_err("Not a Sapling key");
+
+        let t = generate_wallet(&WalletOptions { zcount: 0, ucount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap();
+        verify_unified_key(w.entries[2].private_key(), &match &t.entries[0] {
+            WalletEntry::Unified(e) => e.sapling_private_key.clone(),
+            _ => panic!("Not a unified address")
+        }, None).expect_err("Different networks");
+        verify_key("secret-extended-key-main1abc", None).expect_err("Bad bech32");
+        verify_key("KxFC1jmwwCoACiCAWZ3eXa96mBM6tb3TYzGmf6YwgdGWZgawvrtJ", None).expect_err("Bad checksum");
+    }
+
+    /// Test that the keys of a custom coin verify when its network is passed as the hint
+    #[test]
+    fn test_verify_custom_coin() {
+        use crate::paper::{generate_wallet, WalletOptions, CoinParams, Network};
+        use crate::entropy::SystemEntropy;
+        use crate::verify::{verify_wallet, verify_key};
+
+        let coin = "{
+            'ticker'          : 'ABC',
+            'taddress_version': '1cb8',
+            'tsecret_prefix'  : '80',
+            'zaddress_prefix' : 'abcs',
+            'zsecret_prefix'  : 'secret-extended-key-abc',
+            'zviewkey_prefix' : 'zxviewsabc',
+            'zivk_prefix'     : 'zivksabc',
+            'cointype'        : 1234
+        }".replace("'", "\"");
+        let network = Network::Custom(Box::new(CoinParams::from_json(&coin).unwrap()));
+
+        let w = generate_wallet(&WalletOptions { zcount: 2, tcount: 2, ..WalletOptions::new(network.clone()) }, &mut SystemEntropy, &[]).unwrap();
+        let reports = verify_wallet(&w, "", Some(&network));
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|r| r.is_ok()), "{:?}", reports);
+
+        for entry in w.entries.iter() {
+            let v = verify_key(entry.private_key(), Some(&network)).unwrap();
+            assert_eq!(v.address(), entry.address());
+            assert_eq!(v.viewing_key(), entry.viewing_key());
+        }
+
+        // Without the hint, the Sapling keys have an unknown prefix, and the WIF keys (which share the mainnet version 
+        // byte) are decoded as Zcash keys, with a different HD path
+        let reports = verify_wallet(&w, "", None);
+        assert!(reports.iter().all(|r| !r.is_ok()), "{:?}", reports);
+        verify_key(w.entries[0].private_key(), None).expect_err("Unknown prefix");
+
+        // Zcash keys still verify with a custom hint
+        let z = generate_wallet(&WalletOptions { tcount: 1, ..WalletOptions::new(Network::Test) }, &mut SystemEntropy, &[]).unwrap();
+        assert!(verify_wallet(&z, "", Some(&network)).iter().all(|r| r.is_ok()));
+    }
+}
@@ -0,0 +1,578 @@
+use json::{object, JsonValue};
+use crate::error::Error;
+use crate::keycrypt::is_encrypted_key;
+
+/// The HD seed and the derivation path that a key was derived from. Together they are enough
+/// to re-derive the key. If the seed was derived from a BIP-39 mnemonic, the mnemonic words are
+/// included too.
+#[derive(Clone, Debug, PartialEq)]
+pub struct HDSeed {
+    pub seed    : Vec<u8>,
+    pub path    : String,
+    pub mnemonic: Option<String>,
+    /// The seed and mnemonic encrypted with `keycrypt::encrypt_seed`, in an encrypted wallet. The
+    /// `seed` is then empty and the `mnemonic` is None until the wallet is decrypted.
+    pub encrypted: Option<String>,
+}
+
+impl HDSeed {
+    /// The seed as it is written on the wallet: hex, or the encrypted seed
+    pub fn encoded_seed(&self) -> String {
+        match &self.encrypted {
+            Some(encrypted) => encrypted.clone(),
+            None            => hex::encode(&self.seed)
+        }
+    }
+}
+
+/// The parts of a Sapling full viewing key that can be handed out separately. The incoming
+/// viewing key can see incoming funds but not spends, the outgoing viewing key can decrypt
+/// outgoing notes, and the diversifier key can derive the account's diversified addresses.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaplingAccountKeys {
+    pub incoming_viewing_key: String,
+    pub outgoing_viewing_key: String,
+    pub diversifier_key     : String,
+}
+
+/// A Sapling z-address with its spending key and (if available) the full viewing key and
+/// the HD seed it was derived from. Diversified addresses also record the diversifier index
+/// they were derived at; several of them share the same spending key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaplingEntry {
+    pub num         : u32,
+    pub address     : String,
+    pub private_key : String,
+    pub viewing_key : Option<String>,
+    pub account_keys: Option<SaplingAccountKeys>,
+    pub seed        : Option<HDSeed>,
+    pub diversifier_index: Option<u64>,
+}
+
+/// A transparent t-address, its WIF encoded private key and (if available) the HD seed it was
+/// derived from.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransparentEntry {
+    pub num         : u32,
+    pub address     : String,
+    pub private_key : String,
+    pub seed        : Option<HDSeed>,
+}
+
+/// A ZIP-316 unified address with Orchard and Sapling receivers. The private key is the Orchard
+/// spending key, in this tool's own encoding, and the Sapling private key is the extended spending
+/// key of the Sapling receiver. Together they are enough to re-derive the address. The viewing key
+/// is the unified full viewing key.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnifiedEntry {
+    pub num         : u32,
+    pub address     : String,
+    pub private_key : String,
+    pub sapling_private_key: String,
+    pub viewing_key : String,
+    pub seed        : Option<HDSeed>,
+}
+
+/// A single address in a paper wallet
+#[derive(Clone, Debug, PartialEq)]
+pub enum WalletEntry {
+    Sapling(SaplingEntry),
+    Transparent(TransparentEntry),
+    Unified(UnifiedEntry),
+}
+
+impl WalletEntry {
+    pub fn num(&self) -> u32 {
+        match self {
+            WalletEntry::Sapling(e)     => e.num,
+            WalletEntry::Transparent(e) => e.num,
+            WalletEntry::Unified(e)     => e.num,
+        }
+    }
+
+    pub fn address(&self) -> &str {
+        match self {
+            WalletEntry::Sapling(e)     => &e.address,
+            WalletEntry::Transparent(e) => &e.address,
+            WalletEntry::Unified(e)     => &e.address,
+        }
+    }
+
+    pub fn private_key(&self) -> &str {
+        match self {
+            WalletEntry::Sapling(e)     => &e.private_key,
+            WalletEntry::Transparent(e) => &e.private_key,
+            WalletEntry::Unified(e)     => &e.private_key,
+        }
+    }
+
+    /// The full viewing key, if there is one. Transparent addresses don't have viewing keys.
+    pub fn viewing_key(&self) -> Option<&str> {
+        match self {
+            WalletEntry::Sapling(e)     => e.viewing_key.as_deref(),
+            WalletEntry::Transparent(_) => None,
+            WalletEntry::Unified(e)     => Some(&e.viewing_key),
+        }
+    }
+
+    pub fn seed(&self) -> Option<&HDSeed> {
+        match self {
+            WalletEntry::Sapling(e)     => e.seed.as_ref(),
+            WalletEntry::Transparent(e) => e.seed.as_ref(),
+            WalletEntry::Unified(e)     => e.seed.as_ref(),
+        }
+    }
+
+    /// The "type" field used in the JSON output
+    pub fn type_str(&self) -> &'static str {
+        match self {
+            WalletEntry::Sapling(_)     => "zaddr",
+            WalletEntry::Transparent(_) => "taddr",
+            WalletEntry::Unified(_)     => "uaddr",
+        }
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        let mut j = object!{
+            "num"           => self.num(),
+            "address"       => self.address(),
+            "private_key"   => self.private_key(),
+            "type"          => self.type_str()
+        };
+
+        if let Some(vk) = self.viewing_key() {
+            j["viewing_key"] = vk.into();
+        }
+
+        if let WalletEntry::Sapling(e) = self {
+            if let Some(keys) = &e.account_keys {
+                j["incoming_viewing_key"] = keys.incoming_viewing_key.clone().into();
+                j["outgoing_viewing_key"] = keys.outgoing_viewing_key.clone().into();
+                j["diversifier_key"]      = keys.diversifier_key.clone().into();
+            }
+            if let Some(di) = e.diversifier_index {
+                j["diversifier_index"] = di.into();
+            }
+        }
+
+        if let WalletEntry::Unified(e) = self {
+            j["sapling_private_key"] = e.sapling_private_key.clone().into();
+        }
+
+        if let Some(seed) = self.seed() {
+            j["seed"] = object!{
+                "HDSeed"    => seed.encoded_seed(),
+                "path"      => seed.path.clone()
+            };
+            if let Some(words) = &seed.mnemonic {
+                j["seed"]["mnemonic"] = words.clone().into();
+            }
+        }
+
+        j
+    }
+
+    /// The JSON for a watch-only wallet, which has the address and viewing key but no private key or seed
+    pub fn to_watch_only_json(&self) -> JsonValue {
+        let mut j = object!{
+            "num"           => self.num(),
+            "address"       => self.address(),
+            "type"          => self.type_str()
+        };
+
+        if let Some(vk) = self.viewing_key() {
+            j["viewing_key"] = vk.into();
+        }
+
+        if let WalletEntry::Sapling(SaplingEntry { account_keys: Some(keys), .. }) = self {
+            j["incoming_viewing_key"] = keys.incoming_viewing_key.clone().into();
+        }
+
+        j
+    }
+
+    pub fn from_json(j: &JsonValue) -> Result<WalletEntry, Error> {
+        let field = |name: &str| match j[name].as_str() {
+            Some(s) => Ok(s.to_string()),
+            None    => Err(Error::Encoding(format!("Missing \"{}\" in wallet entry", name)))
+        };
+
+        let num = j["num"].as_u32().unwrap_or(0);
+
+        let seed = if j["seed"].is_object() {
+            let seed_str = j["seed"]["HDSeed"].as_str()
+                .ok_or_else(|| Error::Encoding("Missing \"HDSeed\" in wallet entry".to_string()))?;
+            let (seed, encrypted) = if is_encrypted_key(seed_str) {
+                (vec![], Some(seed_str.to_string()))
+            } else {
+                (hex::decode(seed_str).map_err(|e| Error::Encoding(format!("Invalid HDSeed: {}", e)))?, None)
+            };
+            Some(HDSeed {
+                seed,
+                path: j["seed"]["path"].as_str()
+                    .ok_or_else(|| Error::Encoding("Missing \"path\" in wallet entry".to_string()))?.to_string(),
+                mnemonic: j["seed"]["mnemonic"].as_str().map(|s| s.to_string()),
+                encrypted,
+            })
+        } else {
+            None
+        };
+
+        match j["type"].as_str() {
+            Some("zaddr") => Ok(WalletEntry::Sapling(SaplingEntry {
+                num,
+                address     : field("address")?,
+                private_key : field("private_key")?,
+                viewing_key : j["viewing_key"].as_str().map(|s| s.to_string()),
+                account_keys: if j.has_key("incoming_viewing_key") {
+                    Some(SaplingAccountKeys {
+                        incoming_viewing_key: field("incoming_viewing_key")?,
+                        outgoing_viewing_key: field("outgoing_viewing_key")?,
+                        diversifier_key     : field("diversifier_key")?,
+                    })
+                } else {
+                    None
+                },
+                seed,
+                diversifier_index: j["diversifier_index"].as_u64(),
+            })),
+            Some("taddr") => Ok(WalletEntry::Transparent(TransparentEntry {
+                num,
+                address     : field("address")?,
+                private_key : field("private_key")?,
+                seed,
+            })),
+            Some("uaddr") => Ok(WalletEntry::Unified(UnifiedEntry {
+                num,
+                address     : field("address")?,
+                private_key : field("private_key")?,
+                sapling_private_key: field("sapling_private_key")?,
+                viewing_key : field("viewing_key")?,
+                seed,
+            })),
+            t => Err(Error::Encoding(format!("Unknown wallet entry type {:?}", t)))
+        }
+    }
+}
+
+/// A generated paper wallet. All the generators in `paper` return one of these, and it can be
+/// rendered as JSON or (with the `printpdf` feature) as a PDF.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PaperWallet {
+    pub entries: Vec<WalletEntry>,
+    /// The coin the addresses are for, used in the titles of the PDF. It is written as the "coin" of each entry in
+    /// the JSON, and wallets read from JSON without one are "ZEC".
+    pub ticker : String,
+}
+
+impl Default for PaperWallet {
+    fn default() -> PaperWallet {
+        PaperWallet::new(vec![])
+    }
+}
+
+impl PaperWallet {
+    pub fn new(entries: Vec<WalletEntry>) -> PaperWallet {
+        PaperWallet { entries, ticker: "ZEC".to_string() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_json(&self) -> JsonValue {
+        JsonValue::Array(self.entries.iter().map(|e| self.with_coin(e.to_json())).collect())
+    }
+
+    fn with_coin(&self, mut j: JsonValue) -> JsonValue {
+        j["coin"] = self.ticker.clone().into();
+        j
+    }
+
+    /// Serialize to the pretty-printed JSON format that zecpaperwallet has always written.
+    pub fn to_json_string(&self) -> String {
+        json::stringify_pretty(self.to_json(), 2)
+    }
+
+    /// JSON with only the addresses and viewing keys, so the wallet's balance can be monitored without any
+    /// of the spending keys or seeds
+    pub fn to_watch_only_json(&self) -> JsonValue {
+        JsonValue::Array(self.entries.iter().map(|e| self.with_coin(e.to_watch_only_json())).collect())
+    }
+
+    pub fn to_watch_only_json_string(&self) -> String {
+        json::stringify_pretty(self.to_watch_only_json(), 2)
+    }
+
+    /// CSV with one row per address, for importing into spreadsheets. The columns are type, num, address,
+    /// viewing_key, private_key, path and sapling_private_key (only for unified addresses). Watch-only CSV
+    /// leaves the private keys empty.
+    pub fn to_csv_string(&self, watch_only: bool) -> String {
+        let mut csv = "type,num,address,viewing_key,private_key,path,sapling_private_key\r\n".to_string();
+        for e in self.entries.iter() {
+            let sapling_pk = match e {
+                WalletEntry::Unified(u) if !watch_only => u.sapling_private_key.clone(),
+                _                                      => String::default()
+            };
+            let row = [
+                e.type_str().to_string(),
+                e.num().to_string(),
+                e.address().to_string(),
+                e.viewing_key().unwrap_or("").to_string(),
+                if watch_only { String::default() } else { e.private_key().to_string() },
+                e.seed().map(|s| s.path.clone()).unwrap_or_default(),
+                sapling_pk,
+            ];
+            csv.push_str(&row.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(","));
+            csv.push_str("\r\n");
+        }
+        csv
+    }
+
+    /// A plain text listing of the addresses and their keys, for reading or printing without a PDF viewer
+    pub fn to_text_string(&self, watch_only: bool) -> String {
+        let mut text = String::default();
+        for e in self.entries.iter() {
+            let name = match e {
+                WalletEntry::Sapling(_)     => "Sapling address",
+                WalletEntry::Transparent(_) => "Transparent address",
+                WalletEntry::Unified(_)     => "Unified address",
+            };
+            text.push_str(&format!("{} {} {}\n", self.ticker, name, e.num()));
+            text.push_str(&format!("  Address          : {}\n", e.address()));
+            if let WalletEntry::Sapling(SaplingEntry { diversifier_index: Some(di), .. }) = e {
+                text.push_str(&format!("  Diversifier index: {}\n", di));
+            }
+            if let Some(vk) = e.viewing_key() {
+                text.push_str(&format!("  Viewing key      : {}\n", vk));
+            }
+            if !watch_only {
+                text.push_str(&format!("  Private key      : {}\n", e.private_key()));
+                if let WalletEntry::Unified(u) = e {
+                    text.push_str(&format!("  Sapling key      : {}\n", u.sapling_private_key));
+                }
+                if let Some(seed) = e.seed() {
+                    match &seed.mnemonic {
+                        Some(words) => text.push_str(&format!("  Seed words       : {}\n", words)),
+                        None        => text.push_str(&format!("  HD seed          : {}\n", seed.encoded_seed())),
+                    }
+                }
+            }
+            if let Some(seed) = e.seed() {
+                text.push_str(&format!("  Path             : {}\n", seed.path));
+            }
+            text.push_str("\n");
+        }
+        text
+    }
+
+    /// Parse a wallet previously written by `to_json_string`
+    pub fn from_json(s: &str) -> Result<PaperWallet, Error> {
+        let j = json::parse(s)?;
+        if !j.is_array() {
+            return Err(Error::Encoding("Wallet JSON should be an array of addresses".to_string()));
+        }
+
+        let entries = j.members().map(WalletEntry::from_json).collect::<Result<Vec<_>, _>>()?;
+        let mut wallet = PaperWallet::new(entries);
+
+        if let Some(coin) = j.members().find_map(|e| e["coin"].as_str()) {
+            if j.members().any(|e| e["coin"].as_str() != Some(coin)) {
+                return Err(Error::Encoding("All the addresses in a wallet should be for the same coin".to_string()));
+            }
+            wallet.ticker = coin.to_string();
+        }
+
+        Ok(wallet)
+    }
+}
+
+/// Quote a CSV field if it has a comma, quote or line break in it (RFC 4180)
+fn csv_field(f: &str) -> String {
+    if f.contains(|c: char| c == ',' || c == '"' || c == '\r' || c == '\n') {
+        format!("\"{}\"", f.replace('"', "\"\""))
+    } else {
+        f.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::wallet::PaperWallet;
+
+    /// A wallet with one entry of each kind: a diversified Sapling address with its account keys, a Sapling address
+    /// without a seed or viewing key (like a vanity address), a transparent address from seed words and a unified
+    /// address
+    fn sample_wallet() -> PaperWallet {
+        use crate::wallet::{WalletEntry, SaplingEntry, SaplingAccountKeys, TransparentEntry, UnifiedEntry, HDSeed};
+
+        let seed = |path: &str, mnemonic: Option<&str>| Some(HDSeed {
+            seed     : vec![1, 2, 3],
+            path     : path.to_string(),
+            mnemonic : mnemonic.map(|m| m.to_string()),
+            encrypted: None,
+        });
+
+        PaperWallet::new(vec![
+            WalletEntry::Sapling(SaplingEntry {
+                num: 0,
+                address: "zs1address".to_string(),
+                private_key: "secret-extended-key-main1pk".to_string(),
+                viewing_key: Some("zxviews1vk".to_string()),
+                account_keys: Some(SaplingAccountKeys {
+                    incoming_viewing_key: "zivks1ivk".to_string(),
+                    outgoing_viewing_key: "0a".to_string(),
+                    diversifier_key: "0b".to_string(),
+                }),
+                seed: seed("m/32'/133'/0'", None),
+                diversifier_index: Some(7),
+            }),
+            WalletEntry::Sapling(SaplingEntry {
+                num: 1,
+                address: "zs1vanity".to_string(),
+                private_key: "secret-extended-key-main1vanity".to_string(),
+                viewing_key: None,
+                account_keys: None,
+                seed: None,
+                diversifier_index: None,
+            }),
+            WalletEntry::Transparent(TransparentEntry {
+                num: 2,
+                address: "t1address".to_string(),
+                private_key: "Lpk".to_string(),
+                seed: seed("m/44'/133'/0'/0/0", Some("abandon art")),
+            }),
+            WalletEntry::Unified(UnifiedEntry {
+                num: 3,
+                address: "u1address".to_string(),
+                private_key: "secret-orchard-sk-main1pk".to_string(),
+                sapling_private_key: "secret-extended-key-main1upk".to_string(),
+                viewing_key: "uview1vk".to_string(),
+                seed: seed("m/32'/133'/0'", None),
+            }),
+        ])
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        use crate::wallet::WalletEntry;
+
+        let w = sample_wallet();
+        let j = w.to_json();
+        assert_eq!(j[0]["type"], "zaddr");
+        assert_eq!(j[0]["seed"]["HDSeed"], "010203");
+        assert_eq!(j[0]["seed"]["path"], "m/32'/133'/0'");
+        assert!(!j[0]["seed"].has_key("mnemonic"));
+        assert_eq!(j[0]["diversifier_index"], 7);
+        assert_eq!(j[0]["incoming_viewing_key"], "zivks1ivk");
+        assert_eq!(j[0]["outgoing_viewing_key"], "0a");
+        assert_eq!(j[0]["diversifier_key"], "0b");
+        assert!(!j[1].has_key("incoming_viewing_key"));
+        assert!(!j[1].has_key("seed"));
+        assert!(!j[1].has_key("diversifier_index"));
+        assert!(!j[1].has_key("viewing_key"));
+        assert_eq!(j[2]["type"], "taddr");
+        assert_eq!(j[2]["seed"]["path"], "m/44'/133'/0'/0/0");
+        assert_eq!(j[2]["seed"]["mnemonic"], "abandon art");
+        assert_eq!(j[3]["type"], "uaddr");
+        assert_eq!(j[3]["viewing_key"], "uview1vk");
+        assert_eq!(j[3]["sapling_private_key"], "secret-extended-key-main1upk");
+
+        assert_eq!(j[0]["coin"], "ZEC");
+
+        assert_eq!(PaperWallet::from_json(&w.to_json_string()).unwrap(), w);
+
+        // The coin is read back, and wallets written before it was in the JSON are ZEC
+        let mut yec = w.clone();
+        yec.ticker = "YEC".to_string();
+        assert_eq!(yec.to_json()[3]["coin"], "YEC");
+        assert_eq!(PaperWallet::from_json(&yec.to_json_string()).unwrap().ticker, "YEC");
+        assert_eq!(PaperWallet::from_json(&w.to_json_string().replace("\"coin\": \"ZEC\"", "\"x\": 0")).unwrap().ticker, "ZEC");
+
+        let mut mixed = yec.to_json();
+        mixed[1]["coin"] = "ZEC".into();
+        PaperWallet::from_json(&mixed.dump()).expect_err("Entries for different coins");
+
+        // An encrypted seed is written in place of the hex seed
+        let mut encrypted = w.clone();
+        if let WalletEntry::Sapling(e) = &mut encrypted.entries[0] {
+            let seed = e.seed.as_mut().unwrap();
+            seed.seed = vec![];
+            seed.encrypted = Some("zecencrypted1seed".to_string());
+        }
+        assert_eq!(encrypted.to_json()[0]["seed"]["HDSeed"], "zecencrypted1seed");
+        assert_eq!(PaperWallet::from_json(&encrypted.to_json_string()).unwrap(), encrypted);
+
+        // Watch-only JSON has no private keys or seeds
+        let j = json::parse(&w.to_watch_only_json_string()).unwrap();
+        assert_eq!(j.len(), 4);
+        for i in 0..4 {
+            assert_eq!(j[i]["address"], w.entries[i].address());
+            assert!(!j[i].has_key("private_key"));
+            assert!(!j[i].has_key("sapling_private_key"));
+            assert!(!j[i].has_key("seed"));
+        }
+        assert_eq!(j[0]["viewing_key"], "zxviews1vk");
+        assert_eq!(j[0]["incoming_viewing_key"], "zivks1ivk");
+        assert!(!j[0].has_key("outgoing_viewing_key"));
+        assert!(!j[1].has_key("viewing_key"));
+        assert!(!j[2].has_key("viewing_key"));
+        assert_eq!(j[3]["viewing_key"], "uview1vk");
+        assert_eq!(j[3]["coin"], "ZEC");
+
+        // Bad input is an error, not a panic
+        PaperWallet::from_json("{}").expect_err("Not an array");
+        PaperWallet::from_json("[{'type': 'zaddr'}]").expect_err("Not JSON");
+        PaperWallet::from_json("[{\"type\": \"zaddr\"}]").expect_err("Missing fields");
+        PaperWallet::from_json("[{\"type\": \"uaddr\", \"address\": \"a\", \"private_key\": \"b\", \"viewing_key\": \"c\"}]").expect_err("Missing Sapling key");
+        PaperWallet::from_json("[{\"type\": \"yaddr\", \"address\": \"a\", \"private_key\": \"b\"}]").expect_err("Unknown type");
+    }
+
+    #[test]
+    fn test_csv_text() {
+        use crate::wallet::csv_field;
+
+        let mut w = sample_wallet();
+        w.ticker = "YEC".to_string();
+
+        let csv = w.to_csv_string(false);
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(rows.len(), 6);
+        assert_eq!(rows[0], "type,num,address,viewing_key,private_key,path,sapling_private_key");
+        assert_eq!(rows[1], "zaddr,0,zs1address,zxviews1vk,secret-extended-key-main1pk,m/32'/133'/0',");
+        assert_eq!(rows[2], "zaddr,1,zs1vanity,,secret-extended-key-main1vanity,,");
+        assert_eq!(rows[3], "taddr,2,t1address,,Lpk,m/44'/133'/0'/0/0,");
+        assert_eq!(rows[4], "uaddr,3,u1address,uview1vk,secret-orchard-sk-main1pk,m/32'/133'/0',secret-extended-key-main1upk");
+        assert_eq!(rows[5], "");
+
+        let csv = w.to_csv_string(true);
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(rows[1], "zaddr,0,zs1address,zxviews1vk,,m/32'/133'/0',");
+        assert_eq!(rows[4], "uaddr,3,u1address,uview1vk,,m/32'/133'/0',");
+
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+
+        let text = w.to_text_string(false);
+        assert!(text.starts_with("YEC Sapling address 0\n  Address          : zs1address\n  Diversifier index: 7\n"));
+        assert!(text.contains("  Private key      : secret-extended-key-main1pk\n  HD seed          : 010203\n"));
+        assert!(text.contains("YEC Sapling address 1\n  Address          : zs1vanity\n  Private key      : secret-extended-key-main1vanity\n\n"));
+        assert!(text.contains("YEC Transparent address 2\n"));
+        assert!(text.contains("  Seed words       : abandon art\n  Path             : m/44'/133'/0'/0/0\n"));
+        assert!(text.contains("  Private key      : secret-orchard-sk-main1pk\n  Sapling key      : secret-extended-key-main1upk\n"));
+        assert!(!text.contains("Viewing key      : \n"));
+
+        let text = w.to_text_string(true);
+        assert!(!text.contains("secret-extended-key"));
+        assert!(!text.contains("secret-orchard-sk"));
+        assert!(!text.contains("Lpk"));
+        assert!(!text.contains("abandon"));
+        assert!(!text.contains("010203"));
+        assert!(text.contains("  Viewing key      : zxviews1vk\n"));
+        assert!(text.contains("  Viewing key      : uview1vk\n"));
+    }
+}
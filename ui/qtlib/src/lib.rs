@@ -1,39 +1,140 @@
 use libc::{c_char};
 use std::ffi::{CStr, CString};
-use zecpaperlib::{pdf, paper};
+use std::ptr;
+use zecpaperlib::{pdf, paper, vanity, wallet};
+use zecpaperlib::entropy::SystemEntropy;
 
 /**
- * Call into rust to generate a paper wallet. Returns the paper wallet in JSON form. 
+ * The networks are passed in as numbers: 0 for mainnet, 1 for testnet and 2 for regtest. See zecpaperrust.h
+ */
+fn network_from_u32(network: u32) -> Option<paper::Network> {
+    match network {
+        0 => Some(paper::Network::Main),
+        1 => Some(paper::Network::Test),
+        2 => Some(paper::Network::Regtest),
+        _ => {
+            eprintln!("Unknown network {}", network);
+            None
+        }
+    }
+}
+
+/**
+ * Call into rust to generate a paper wallet. Returns the paper wallet in JSON form, or NULL if 
+ * the wallet couldn't be generated.
  * NOTE: the returned string is owned by rust, so the caller needs to call rust_free_string with it
  * after using it to free it properly
  */ 
 #[no_mangle]
-pub extern fn rust_generate_wallet(is_testnet: bool, zcount: u32, tcount: u32, entropy: *const c_char) -> *mut c_char {
-    let entropy_str = unsafe {
-        assert!(!entropy.is_null());
+pub extern fn rust_generate_wallet(network: u32, zcount: u32, tcount: u32, entropy: *const c_char) -> *mut c_char {
+    if entropy.is_null() {
+        return ptr::null_mut();
+    }
+
+    let network = match network_from_u32(network) {
+        Some(n) => n,
+        None    => return ptr::null_mut()
+    };
 
+    let entropy_str = unsafe {
         CStr::from_ptr(entropy)
     };
 
-    let c_str = CString::new(paper::generate_wallet(is_testnet, false, zcount, tcount, entropy_str.to_bytes())).unwrap();
-    return c_str.into_raw();
+    let options = paper::WalletOptions { zcount, tcount, ..paper::WalletOptions::new(network) };
+    let w = match paper::generate_wallet(&options, &mut SystemEntropy, entropy_str.to_bytes()) {
+        Ok(w)   => w,
+        Err(e)  => {
+            eprintln!("{}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(w.to_json_string()) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_)    => ptr::null_mut()
+    }
+}
+
+/**
+ * Call into rust to search for a vanity z-address that starts with the given prefix. Returns the paper wallet in JSON 
+ * form, or NULL if the search failed or was cancelled.
+ * The progress callback (which can be NULL) is called on this thread with the number of addresses checked so far, 
+ * the rate per second and the ETA in seconds (or -1 if it isn't known yet). Return false from it to cancel the search.
+ * NOTE: the returned string is owned by rust, so the caller needs to call rust_free_string with it
+ * after using it to free it properly
+ */ 
+#[no_mangle]
+pub extern fn rust_generate_vanity_wallet(network: u32, num_threads: u32, prefix: *const c_char, 
+                                          progress: Option<extern fn(u64, f64, f64) -> bool>) -> *mut c_char {
+    if prefix.is_null() {
+        return ptr::null_mut();
+    }
+
+    let network = match network_from_u32(network) {
+        Some(n) => n,
+        None    => return ptr::null_mut()
+    };
+
+    let prefix_str = match unsafe { CStr::from_ptr(prefix) }.to_str() {
+        Ok(p)   => p.to_string(),
+        Err(_)  => {
+            eprintln!("Vanity prefix should be valid UTF-8");
+            return ptr::null_mut();
+        }
+    };
+
+    let mut config = vanity::VanityConfig::new(network, false, vec![vanity::VanityPattern::Prefix(prefix_str)]);
+    config.num_threads = num_threads;
+
+    let cancel = vanity::CancelHandle::new();
+    let w = match vanity::generate_vanity_wallet(&config, &mut SystemEntropy, &cancel, |event| {
+        if let (vanity::VanityEvent::Progress(p), Some(callback)) = (event, progress) {
+            let eta = p.eta.map(|d| d.as_secs_f64()).unwrap_or(-1.0);
+            if !callback(p.attempts, p.rate, eta) {
+                cancel.cancel();
+            }
+        }
+    }) {
+        Ok(w)   => w,
+        Err(e)  => {
+            eprintln!("{}", e);
+            return ptr::null_mut();
+        }
+    };
+
+    match CString::new(w.to_json_string()) {
+        Ok(c_str) => c_str.into_raw(),
+        Err(_)    => ptr::null_mut()
+    }
 }
 
 #[no_mangle]
-pub extern fn rust_save_as_pdf(is_testnet: bool, json: *const c_char, file: *const c_char)-> bool {
-    let json_str = unsafe {
-        assert!(!json.is_null());
+pub extern fn rust_save_as_pdf(json: *const c_char, file: *const c_char)-> bool {
+    if json.is_null() || file.is_null() {
+        return false;
+    }
 
-        CStr::from_ptr(json)
+    let (json_str, file_str) = unsafe {
+        (CStr::from_ptr(json), CStr::from_ptr(file))
     };
 
-    let file_str = unsafe {
-        assert!(!file.is_null());
+    let (json_str, file_str) = match (json_str.to_str(), file_str.to_str()) {
+        (Ok(j), Ok(f)) => (j, f),
+        _              => {
+            eprintln!("Wallet JSON and file name should be valid UTF-8");
+            return false;
+        }
+    };
 
-        CStr::from_ptr(file)
+    let wallet = match wallet::PaperWallet::from_json(json_str) {
+        Ok(w)   => w,
+        Err(e)  => {
+            eprintln!("{}", e);
+            return false;
+        }
     };
 
-    match pdf::save_to_pdf(is_testnet, json_str.to_str().unwrap(), file_str.to_str().unwrap()) {
+    match pdf::save_to_pdf(&wallet, file_str) {
         Ok(_)   => return true,
         Err(e)  => {
             eprintln!("{}", e);
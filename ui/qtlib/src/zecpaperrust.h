@@ -5,9 +5,16 @@
 extern "C"{
 #endif
 
-extern char * rust_generate_wallet(bool is_testnet, unsigned int zcount, unsigned int tcount, const char* entropy);
+/* The network argument to the generate functions */
+#define ZECPAPER_NETWORK_MAIN    0
+#define ZECPAPER_NETWORK_TEST    1
+#define ZECPAPER_NETWORK_REGTEST 2
+
+extern char * rust_generate_wallet(unsigned int network, unsigned int zcount, unsigned int tcount, const char* entropy);
+extern char * rust_generate_vanity_wallet(unsigned int network, unsigned int num_threads, const char* prefix,
+                                          bool (*progress)(unsigned long long attempts, double rate, double eta_secs));
 extern void   rust_free_string    (char* s);
-extern bool   rust_save_as_pdf    (bool is_testnet, const char* json, const char* filename);
+extern bool   rust_save_as_pdf    (const char* json, const char* filename);
 
 #ifdef __cplusplus
 }
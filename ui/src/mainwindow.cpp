@@ -59,7 +59,10 @@ void AddWallet(QString address, QString pk, QWidget* scroll) {
  */
 QString Generate(int zaddrs, int taddrs, QString entropy) {
     // Call into rust to get the addresses
-    char* wallet = rust_generate_wallet(false, zaddrs, taddrs, entropy.toStdString().c_str());
+    char* wallet = rust_generate_wallet(ZECPAPER_NETWORK_MAIN, zaddrs, taddrs, entropy.toStdString().c_str());
+    if (wallet == nullptr) {
+        return QString();
+    }
     QString walletJson(wallet);
     
     // We'll overwrite the privatekeys for safety before sending it back to rust
@@ -105,7 +108,7 @@ void MainWindow::SaveAsPDFButton() {
         if (!filename.endsWith(".pdf"))
             filename = filename + ".pdf";
 
-        bool success = rust_save_as_pdf(false, this->currentWallets.toStdString().c_str(), filename.toStdString().c_str());
+        bool success = rust_save_as_pdf(this->currentWallets.toStdString().c_str(), filename.toStdString().c_str());
         if (success) {
             QMessageBox::information(this, tr("Saved!"), tr("The wallets were saved to ") + filename);
         } else {
@@ -1,6 +1,7 @@
 mod utils;
 
-use zecpaperlib::paper::{generate_wallet, double_sha256};
+use zecpaperlib::paper::{generate_wallet, WalletOptions, Network};
+use zecpaperlib::entropy::NoEntropy;
 use wasm_bindgen::prelude::*;
 
 // When the `wee_alloc` feature is enabled, use `wee_alloc` as the global
@@ -10,8 +11,19 @@ use wasm_bindgen::prelude::*;
 static ALLOC: wee_alloc::WeeAlloc = wee_alloc::WeeAlloc::INIT;
 
 
+/// There's no system RNG in the browser, so the page passes in its own entropy (which includes the browser's
+/// crypto.getRandomValues), and it has to be long enough to generate the wallet from on its own.
 #[wasm_bindgen]
-pub fn get_wallet(zaddrs: u32, taddrs: u32, entropy: String) -> String {    
-    let w = generate_wallet(false, false, zaddrs, taddrs, &double_sha256(entropy.as_bytes()));
-    return w;
+pub fn get_wallet(zaddrs: u32, taddrs: u32, uaddrs: u32, entropy: String, use_mnemonic: bool, passphrase: String, network: String) -> Result<String, JsValue> {    
+    let network = Network::from_name(&network).map_err(|e| JsValue::from_str(&format!("{}", e)))?;
+    let options = WalletOptions {
+        zcount  : zaddrs,
+        tcount  : taddrs,
+        ucount  : uaddrs,
+        mnemonic: if use_mnemonic { Some(passphrase) } else { None },
+        ..WalletOptions::new(network)
+    };
+    let w = generate_wallet(&options, &mut NoEntropy, entropy.as_bytes())
+        .map_err(|e| JsValue::from_str(&format!("{}", e)))?;
+    return Ok(w.to_json_string());
 }
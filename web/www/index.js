@@ -11,7 +11,8 @@ function add_section(wallet_item) {
             <div class="col-sm-9" style="word-break: break-word;">
                 <h1> Address 
                 ${(() => {
-                    if (wallet_item["address"].startsWith("z")) { return "(Sapling)"; }
+                    if (wallet_item["type"] === "uaddr") { return "(Unified)"; }
+                    else if (wallet_item["address"].startsWith("z")) { return "(Sapling)"; }
                     else { return "(Transparent)"; }
                    }) ()
                 }
@@ -43,7 +44,7 @@ function add_section(wallet_item) {
         </div>`;
 
     let vk_section = '';
-    if (wallet_item["address"].startsWith("z")) {
+    if (wallet_item["viewing_key"]) {
         vk_section = `
             <div class="row pk-section">
                 <div class="h-dashed"></div>
@@ -56,7 +57,11 @@ function add_section(wallet_item) {
                     <h2> Address </h2>
                     <p class="fixed-width"> ${wallet_item["address"]} </p>
                     ${(() => {
-                            if (wallet_item.seed) {
+                            if (wallet_item.seed && wallet_item.seed.mnemonic) {
+                                return `<h2> Seed Words (BIP-39) </h2>
+                                        <p class="fixed-width"> ${wallet_item["seed"]["mnemonic"]} </p>
+                                        <code> path: ${wallet_item["seed"]["path"]} </code>`;
+                            } else if (wallet_item.seed) {
                                 return `<code> HD Key: ${wallet_item["seed"]["HDSeed"]}, path: ${wallet_item["seed"]["path"]} </code>`;
                             } else { return ""; }
                     }) ()
@@ -164,16 +169,23 @@ jQuery("#configdialog").on("hidden.bs.modal", function (e) {
 
     let numzAddresses = jQuery("#numzAddresses").val();
     let numtAddresses = jQuery("#numtAddresses").val();
+    let numuAddresses = jQuery("#numuAddresses").val();
+    let useMnemonic   = jQuery("#useMnemonic").is(":checked");
+    let passphrase    = jQuery("#passphrase").val();
 
     jQuery("#pleasewait").modal('show');
 
     // Run this async so that the please wait dialog can show
     setTimeout(() => {
-        let w = JSON.parse(wasm.get_wallet(numzAddresses, numtAddresses, user_entropy + system_entropy));
-    
-        w.forEach(wallet_item => {
-            add_section(wallet_item); 
-        });    
+        try {
+            let w = JSON.parse(wasm.get_wallet(numzAddresses, numtAddresses, numuAddresses, user_entropy + system_entropy, useMnemonic, passphrase, "main"));
+        
+            w.forEach(wallet_item => {
+                add_section(wallet_item); 
+            });    
+        } catch (err) {
+            alert("Couldn't generate wallet: " + err);
+        }
         jQuery("#pleasewait").modal("hide");
     }, 1);
     